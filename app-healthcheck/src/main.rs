@@ -27,37 +27,27 @@ struct AmqpResource {
 }
 
 impl ApplicationResource for AmqpResource {
-    fn ping(&mut self) -> bool {
-        // connection alive
-        if self.is_amqp_connection_alive() {
-            eprintln!("connection alive");
-            return true;
+    fn id(&self) -> &str {
+        "amqp"
+    }
+
+    fn ping(&mut self) -> ResourceHealth {
+        match self.proc.result() {
+            Some(Ok(connection)) => self.connection = Some(connection),
+            Some(Err(error)) => eprintln!("Error while connecting to AMQP: {}", error),
+            None => (),
         }
 
-        // connection in progress
-        if self.is_amqp_connecting() {
-            eprintln!("connection still connecting");
-            return false;
+        // once established, connection reconnects on its own
+        if self.is_amqp_connection_alive() {
+            return ResourceHealth::Healthy;
         }
 
-        let maybe_result = self.proc.result();
-        match maybe_result {
-            None => {
-                eprintln!("Starting connection");
-                self.start_connection()
-            },
-            Some(Ok(connection)) => {
-                eprintln!("Connection established");
-                self.connection = Some(connection);
-                return true;
-            },
-            Some(Err(error)) => {
-                eprintln!("Error while connecting to AMQP: {}. Reconnecting", error);
-                self.start_connection();
-            },
+        if self.connection.is_none() && self.proc.is_completed() {
+            self.start_connection();
         }
 
-        false
+        ResourceHealth::Unhealthy
     }
 }
 
@@ -72,19 +62,16 @@ impl AmqpResource {
             params.password = "guest".to_string();
             params.vhost = "/".to_string();
             params.heartbeat = 5;
+            params.reconnect = Some(AmqpReconnectPolicy { max_retries: u32::MAX, backoff: Duration::new(2, 0) });
 
             let notifier2 = notifier.clone();
-            params.on_error = Some(Box::new(move |err| {
-                eprintln!("AMQP connection error: {}", err);
-                notifier2.send_system_event(SystemEvent::ResourceStateChanged);
+            params.on_state_change = Some(Box::new(move |state| {
+                eprintln!("AMQP connection state: {:?}", state);
+                notifier2.send_system_event(SystemEvent::ResourceActivity);
             }));
 
             let result = AmqpConnection::connect(params).await;
-            if result.is_err() {
-                async_sleep(Duration::new(2, 0)).await;
-            }
-
-            notifier.send_system_event(SystemEvent::ResourceStateChanged);
+            notifier.send_system_event(SystemEvent::ResourceActivity);
 
             result
         });
@@ -96,10 +83,6 @@ impl AmqpResource {
             Some(connection) => connection.is_alive(),
         }
     }
-
-    fn is_amqp_connecting(&self) -> bool {
-        !self.proc.is_completed()
-    }
 }
 
 impl ApplicationLogic for HealthcheckApp {
@@ -123,7 +106,7 @@ impl ApplicationLogic for HealthcheckApp {
         eprintln!("App::handle_system_event - {:?}", event);
     }
 
-    async fn handle_app_event(&mut self, _notifier: ApplicationStateNotifier, event: Self::Event) -> EventProcessing {
+    async fn handle_app_event(&self, _notifier: ApplicationStateNotifier, event: Self::Event) -> EventProcessing {
         eprintln!("App::handle_app_event - {:?}", event);
         EventProcessing::Completed
     }
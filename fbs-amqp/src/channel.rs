@@ -1,16 +1,21 @@
 use std::rc::Rc;
 use std::cmp::min;
 use std::cell::{RefCell, Cell};
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
+use std::future::Future;
 use std::fmt::{Debug, Formatter};
+use std::time::{Duration, Instant};
 
 use super::*;
 use super::defines::*;
 use super::connection::AmqpConnectionInternal;
 use super::frame::{AmqpFrame, AmqpFramePayload, AmqpMethod};
 
+use fbs_runtime::async_sleep;
 use fbs_runtime::async_utils::{AsyncChannelRx, AsyncChannelTx, async_channel_create};
 
+const GET_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct AmqpChannel {
     pub(super) ptr: Rc<AmqpChannelInternals>,
 }
@@ -26,10 +31,23 @@ impl AmqpChannel {
         }
     }
 
+    pub fn pipeline(&self) -> AmqpChannelPipeline {
+        AmqpChannelPipeline {
+            ptr: self.ptr.clone(),
+            frames: Vec::new(),
+            replies: 0,
+        }
+    }
+
     pub fn set_on_return(&mut self, callback: Option<Box<dyn Fn(i16, String, String, String, &mut AmqpMessage)>>) {
         *self.ptr.on_return.borrow_mut() = callback;
     }
 
+    // Called with consumer tag when broker cancels consumer on its own, e.g. because queue was deleted
+    pub fn set_on_cancel(&mut self, callback: Option<Box<dyn Fn(String)>>) {
+        *self.ptr.on_cancel.borrow_mut() = callback;
+    }
+
     pub async fn close(self) -> Result<(), AmqpConnectionError> {
         self.ptr.is_channel_valid()?;
 
@@ -43,6 +61,9 @@ impl AmqpChannel {
         self.ptr.wait_list.channel_close_ok.set(true);
         self.ptr.rx.receive().await?;
 
+        *self.ptr.last_error.borrow_mut() = Some(AmqpConnectionError::ChannelClosed);
+        self.ptr.fail_pending_confirms(AmqpConnectionError::ChannelClosed);
+        self.ptr.fail_consumer_streams(AmqpConnectionError::ChannelClosed);
         self.ptr.connection.clear_channel(self.ptr.number.get());
 
         Ok(())
@@ -100,12 +121,55 @@ impl AmqpChannel {
         Ok(())
     }
 
+    // Messages published to source are routed to destination exchange as if it was a queue
+    pub async fn bind_exchange(&mut self, destination: String, source: String, routing_key: String, no_wait: bool) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        let frame = AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::ExchangeBind(destination, source, routing_key, no_wait as u8, HashMap::new())),
+        };
+
+        self.ptr.connection.writer_queue.send(Some(frame));
+
+        if !no_wait {
+            self.ptr.wait_list.exchange_bind_ok.set(true);
+            self.ptr.rx.receive().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn unbind_exchange(&mut self, destination: String, source: String, routing_key: String, no_wait: bool) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        let frame = AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::ExchangeUnbind(destination, source, routing_key, no_wait as u8, HashMap::new())),
+        };
+
+        self.ptr.connection.writer_queue.send(Some(frame));
+
+        if !no_wait {
+            self.ptr.wait_list.exchange_unbind_ok.set(true);
+            self.ptr.rx.receive().await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn declare_queue(&mut self, name: String, flags: AmqpQueueFlags) -> Result<(String, i32, i32), AmqpConnectionError> {
+        self.declare_queue_with_args(name, flags, HashMap::new()).await
+    }
+
+    // Arguments are broker specific, e.g. x-message-ttl, x-max-length or x-dead-letter-exchange.
+    // Redeclaring existing queue with different arguments closes the channel.
+    pub async fn declare_queue_with_args(&mut self, name: String, flags: AmqpQueueFlags, arguments: HashMap<String, AmqpData>) -> Result<(String, i32, i32), AmqpConnectionError> {
         self.ptr.is_channel_valid()?;
 
         let frame = AmqpFrame {
             channel: self.ptr.number.get() as u16,
-            payload: AmqpFramePayload::Method(AmqpMethod::QueueDeclare(name, flags.into(), HashMap::new())),
+            payload: AmqpFramePayload::Method(AmqpMethod::QueueDeclare(name, flags.into(), arguments)),
         };
 
         self.ptr.connection.writer_queue.send(Some(frame));
@@ -250,9 +314,30 @@ impl AmqpChannel {
         }
     }
 
+    // Polls with basic.get every GET_WAIT_POLL_INTERVAL until message arrives or timeout elapses,
+    // returning None on timeout. Each poll is a broker round-trip and message may wait up to one
+    // interval in the queue - use consume() for anything beyond occasional pulls.
+    pub async fn get_wait(&mut self, queue: String, no_ack: bool, timeout: Duration) -> Result<Option<(u64, bool, String, String, u32, AmqpMessage)>, AmqpConnectionError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(result) = self.get(queue.clone(), no_ack).await? {
+                return Ok(Some(result));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            async_sleep(min(GET_WAIT_POLL_INTERVAL, deadline - now)).await;
+        }
+    }
+
     pub async fn confirm_select(&mut self, callbacks: (AmqpConfirmAckCallback, AmqpConfirmNackCallback), no_wait: bool) -> Result<(), AmqpConnectionError> {
         self.ptr.is_channel_valid()?;
         *self.ptr.confirm_callbacks.borrow_mut() = Some(callbacks);
+        self.ptr.enable_confirms();
 
         let frame = AmqpFrame {
             channel: self.ptr.number.get() as u16,
@@ -275,6 +360,60 @@ impl AmqpChannel {
         }
     }
 
+    pub async fn tx_select(&mut self) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        let frame = AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::TxSelect()),
+        };
+
+        self.ptr.connection.writer_queue.send(Some(frame));
+        self.ptr.wait_list.tx_select_ok.set(true);
+
+        let frame = self.ptr.rx.receive().await?;
+        match frame.payload {
+            AmqpFramePayload::Method(AmqpMethod::TxSelectOk()) => Ok(()),
+            _ => Err(AmqpConnectionError::ProtocolError("tx.select-ok frame expected")),
+        }
+    }
+
+    pub async fn tx_commit(&mut self) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        let frame = AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::TxCommit()),
+        };
+
+        self.ptr.connection.writer_queue.send(Some(frame));
+        self.ptr.wait_list.tx_commit_ok.set(true);
+
+        let frame = self.ptr.rx.receive().await?;
+        match frame.payload {
+            AmqpFramePayload::Method(AmqpMethod::TxCommitOk()) => Ok(()),
+            _ => Err(AmqpConnectionError::ProtocolError("tx.commit-ok frame expected")),
+        }
+    }
+
+    pub async fn tx_rollback(&mut self) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        let frame = AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::TxRollback()),
+        };
+
+        self.ptr.connection.writer_queue.send(Some(frame));
+        self.ptr.wait_list.tx_rollback_ok.set(true);
+
+        let frame = self.ptr.rx.receive().await?;
+        match frame.payload {
+            AmqpFramePayload::Method(AmqpMethod::TxRollbackOk()) => Ok(()),
+            _ => Err(AmqpConnectionError::ProtocolError("tx.rollback-ok frame expected")),
+        }
+    }
+
     pub async fn consume(&mut self, queue: String, tag: String, callback: AmqpConsumer, flags: AmqpConsumeFlags) -> Result<String, AmqpConnectionError> {
         self.ptr.is_channel_valid()?;
 
@@ -313,6 +452,8 @@ impl AmqpChannel {
             self.ptr.consumers.borrow_mut().remove(&tag);
         }
 
+        self.ptr.consumer_streams.borrow_mut().remove(&tag);
+
         let frame = AmqpFrame {
             channel: self.ptr.number.get() as u16,
             payload: AmqpFramePayload::Method(AmqpMethod::BasicCancel(tag, no_wait as u8)),
@@ -335,10 +476,46 @@ impl AmqpChannel {
         }
     }
 
+    pub async fn consume_stream(&mut self, queue: String, tag: String, flags: AmqpConsumeFlags) -> Result<AmqpConsumerStream, AmqpConnectionError> {
+        let (rx, tx) = async_channel_create();
+
+        let stream_tx = tx.clone();
+        let callback = Box::new(move |delivery_tag, redelivered, exchange, routing_key, message: &mut AmqpMessage| {
+            tx.send(Ok((delivery_tag, redelivered, exchange, routing_key, std::mem::take(message))));
+        });
+
+        let consumer_tag = self.consume(queue, tag.clone(), callback, flags).await?;
+        let consumer_tag = if flags.has_no_wait() { tag } else { consumer_tag };
+
+        // closing channel or server side cancel ends the stream with an error
+        self.ptr.consumer_streams.borrow_mut().insert(consumer_tag.clone(), stream_tx);
+
+        Ok(AmqpConsumerStream {
+            channel: self.ptr.clone(),
+            tag: consumer_tag,
+            rx,
+            error: None,
+        })
+    }
+
+    // Publishing is refused while server paused the channel (channel.flow) or blocked the connection.
+    pub fn is_publish_paused(&self) -> bool {
+        self.ptr.is_publish_paused()
+    }
+
     pub fn publish(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> Result<(), AmqpConnectionError> {
         self.ptr.publish(exchange, routing_key, properties, flags, content)
     }
 
+    pub fn publish_chunks(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, chunks: Vec<Vec<u8>>) -> Result<(), AmqpConnectionError> {
+        self.ptr.publish_chunks(exchange, routing_key, properties, flags, chunks)
+    }
+
+    // Requires confirm_select, resolves once broker confirms this particular message.
+    pub fn publish_awaitable(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> impl Future<Output = Result<(), AmqpConnectionError>> {
+        self.ptr.publish_awaitable(exchange, routing_key, properties, flags, content)
+    }
+
     pub fn ack(&self, delivery_tag: u64, multiple: bool) {
         self.ptr.ack(delivery_tag, multiple)
     }
@@ -350,6 +527,158 @@ impl AmqpChannel {
     pub fn nack(&self, delivery_tag: u64, flags: AmqpNackFlags) {
         self.ptr.nack(delivery_tag, flags)
     }
+
+    // basic.reject affects single message only, to reject many at once use nack with multiple flag.
+    // Rejects all unacknowledged messages up to and including delivery_tag, 0 means all outstanding.
+    pub fn nack_all(&self, up_to_delivery_tag: u64, requeue: bool) {
+        self.ptr.nack(up_to_delivery_tag, AmqpNackFlags::new().multiple(true).requeue(requeue))
+    }
+}
+
+// Operations are sent back-to-back, replies are awaited once all frames are queued. Server
+// answers methods on a channel in order, so only the number of outstanding replies is tracked.
+pub struct AmqpChannelPipeline {
+    ptr: Rc<AmqpChannelInternals>,
+    frames: Vec<AmqpFrame>,
+    replies: usize,
+}
+
+impl AmqpChannelPipeline {
+    fn push(mut self, method: AmqpMethod, no_wait: bool) -> Self {
+        self.frames.push(AmqpFrame {
+            channel: self.ptr.number.get() as u16,
+            payload: AmqpFramePayload::Method(method),
+        });
+
+        if !no_wait {
+            self.replies += 1;
+        }
+
+        self
+    }
+
+    pub fn declare_exchange(self, name: String, exchange_type: String, flags: AmqpExchangeFlags) -> Self {
+        self.push(AmqpMethod::ExchangeDeclare(name, exchange_type, flags.into(), HashMap::new()), flags.has_no_wait())
+    }
+
+    pub fn delete_exchange(self, name: String, flags: AmqpDeleteExchangeFlags) -> Self {
+        self.push(AmqpMethod::ExchangeDelete(name, flags.into()), flags.has_no_wait())
+    }
+
+    pub fn bind_exchange(self, destination: String, source: String, routing_key: String, no_wait: bool) -> Self {
+        self.push(AmqpMethod::ExchangeBind(destination, source, routing_key, no_wait as u8, HashMap::new()), no_wait)
+    }
+
+    pub fn unbind_exchange(self, destination: String, source: String, routing_key: String, no_wait: bool) -> Self {
+        self.push(AmqpMethod::ExchangeUnbind(destination, source, routing_key, no_wait as u8, HashMap::new()), no_wait)
+    }
+
+    pub fn declare_queue(self, name: String, flags: AmqpQueueFlags) -> Self {
+        self.declare_queue_with_args(name, flags, HashMap::new())
+    }
+
+    pub fn declare_queue_with_args(self, name: String, flags: AmqpQueueFlags, arguments: HashMap<String, AmqpData>) -> Self {
+        self.push(AmqpMethod::QueueDeclare(name, flags.into(), arguments), flags.has_no_wait())
+    }
+
+    pub fn bind_queue(self, name: String, exchange: String, routing_key: String, no_wait: bool) -> Self {
+        self.push(AmqpMethod::QueueBind(name, exchange, routing_key, no_wait as u8, HashMap::new()), no_wait)
+    }
+
+    pub fn unbind_queue(self, name: String, exchange: String, routing_key: String) -> Self {
+        self.push(AmqpMethod::QueueUnbind(name, exchange, routing_key, HashMap::new()), false)
+    }
+
+    pub fn purge_queue(self, name: String, no_wait: bool) -> Self {
+        self.push(AmqpMethod::QueuePurge(name, no_wait as u8), no_wait)
+    }
+
+    pub fn delete_queue(self, name: String, flags: AmqpDeleteQueueFlags) -> Self {
+        self.push(AmqpMethod::QueueDelete(name, flags.into()), flags.has_no_wait())
+    }
+
+    pub async fn execute(self) -> Result<(), AmqpConnectionError> {
+        self.ptr.is_channel_valid()?;
+
+        self.ptr.wait_list.pipelined.set(self.ptr.wait_list.pipelined.get() + self.replies);
+        for frame in self.frames {
+            self.ptr.connection.writer_queue.send(Some(frame));
+        }
+
+        for received in 0..self.replies {
+            if let Err(error) = self.ptr.rx.receive().await {
+                // replies that won't arrive anymore mustn't capture frames awaited by later calls
+                let pipelined = self.ptr.wait_list.pipelined.get();
+                self.ptr.wait_list.pipelined.set(pipelined.saturating_sub(self.replies - received));
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AmqpConsumerStream {
+    channel: Rc<AmqpChannelInternals>,
+    tag: String,
+    rx: AsyncChannelRx<Result<AmqpDelivery, AmqpConnectionError>>,
+    error: Option<AmqpConnectionError>,
+}
+
+impl AmqpConsumerStream {
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    // Once stream ended, every following call returns the same error
+    pub async fn next(&mut self) -> Result<AmqpDelivery, AmqpConnectionError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+
+        if self.rx.is_empty() {
+            self.channel.is_channel_valid()?;
+        }
+
+        let delivery = self.rx.receive().await;
+        if let Err(error) = &delivery {
+            // consumer no longer exists on server, so there's nothing to cancel on drop
+            if let AmqpConnectionError::ConsumerCancelled(_) = error {
+                self.tag.clear();
+            }
+
+            self.error = Some(error.clone());
+        }
+
+        delivery
+    }
+
+    pub async fn cancel(mut self) -> Result<(), AmqpConnectionError> {
+        let tag = std::mem::take(&mut self.tag);
+        let mut channel = AmqpChannel { ptr: self.channel.clone() };
+
+        channel.cancel(tag, false).await?;
+        Ok(())
+    }
+}
+
+impl Drop for AmqpConsumerStream {
+    fn drop(&mut self) {
+        if self.tag.is_empty() || self.channel.is_channel_valid().is_err() {
+            return;
+        }
+
+        // Can't wait for basic.cancel-ok here, so cancel in no-wait mode
+        self.channel.consumers.borrow_mut().remove(&self.tag);
+        self.channel.consumer_streams.borrow_mut().remove(&self.tag);
+
+        let frame = AmqpFrame {
+            channel: self.channel.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::BasicCancel(std::mem::take(&mut self.tag), 1)),
+        };
+
+        self.channel.connection.writer_queue.send(Some(frame));
+    }
 }
 
 #[derive(Clone)]
@@ -370,9 +699,26 @@ impl AmqpChannelPublisher {
         self.ptr.nack(delivery_tag, flags)
     }
 
+    // See AmqpChannel::nack_all
+    pub fn nack_all(&self, up_to_delivery_tag: u64, requeue: bool) {
+        self.ptr.nack(up_to_delivery_tag, AmqpNackFlags::new().multiple(true).requeue(requeue))
+    }
+
+    pub fn is_publish_paused(&self) -> bool {
+        self.ptr.is_publish_paused()
+    }
+
     pub fn publish(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> Result<(), AmqpConnectionError> {
         self.ptr.publish(exchange, routing_key, properties, flags, content)
     }
+
+    pub fn publish_chunks(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, chunks: Vec<Vec<u8>>) -> Result<(), AmqpConnectionError> {
+        self.ptr.publish_chunks(exchange, routing_key, properties, flags, chunks)
+    }
+
+    pub fn publish_awaitable(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> impl Future<Output = Result<(), AmqpConnectionError>> {
+        self.ptr.publish_awaitable(exchange, routing_key, properties, flags, content)
+    }
 }
 
 pub(super) struct AmqpChannelInternals {
@@ -384,12 +730,19 @@ pub(super) struct AmqpChannelInternals {
     pub wait_list: FrameWaiter,
     pub number: Cell<usize>,
     active: Cell<bool>,
+    // channel.close was sent by client side, slot stays reserved until server confirms it
+    closing: Cell<bool>,
     last_error: RefCell<Option<AmqpConnectionError>>,
     on_return: RefCell<Option<Box<dyn Fn(i16, String, String, String, &mut AmqpMessage)>>>,
+    on_cancel: RefCell<Option<Box<dyn Fn(String)>>>,
     message_in_flight: RefCell<AmqpMessageBuilder>,
     consumers: RefCell<HashMap<String, AmqpConsumer>>,
+    consumer_streams: RefCell<HashMap<String, AsyncChannelTx<Result<AmqpDelivery, AmqpConnectionError>>>>,
     install_consumer: Cell<Option<AmqpConsumer>>,
     confirm_callbacks: RefCell<Option<(AmqpConfirmAckCallback, AmqpConfirmNackCallback)>>,
+    // 0 means confirm mode is not enabled
+    next_publish_tag: Cell<u64>,
+    pending_confirms: RefCell<BTreeMap<u64, AsyncChannelTx<Result<(), AmqpConnectionError>>>>,
 }
 
 impl Debug for AmqpChannelInternals {
@@ -409,6 +762,8 @@ pub(super) struct FrameWaiter {
     pub channel_flow_ok: Cell<bool>,
     pub exchange_declare_ok: Cell<bool>,
     pub exchange_delete_ok: Cell<bool>,
+    pub exchange_bind_ok: Cell<bool>,
+    pub exchange_unbind_ok: Cell<bool>,
     pub queue_declare_ok: Cell<bool>,
     pub queue_bind_ok: Cell<bool>,
     pub queue_unbind_ok: Cell<bool>,
@@ -420,6 +775,10 @@ pub(super) struct FrameWaiter {
     pub basic_get: Cell<bool>,
     pub basic_recover_ok: Cell<bool>,
     pub confirm_select_ok: Cell<bool>,
+    pub tx_select_ok: Cell<bool>,
+    pub tx_commit_ok: Cell<bool>,
+    pub tx_rollback_ok: Cell<bool>,
+    pub pipelined: Cell<usize>,
 }
 
 impl AmqpChannelInternals {
@@ -432,21 +791,69 @@ impl AmqpChannelInternals {
             wait_list: FrameWaiter::default(),
             number: Cell::new(0),
             active: Cell::new(true),
+            closing: Cell::new(false),
             rx,
             tx,
             message_rx,
             message_tx,
             last_error: RefCell::new(None),
             on_return: RefCell::new(None),
+            on_cancel: RefCell::new(None),
             message_in_flight: RefCell::new(AmqpMessageBuilder::default()),
             consumers: RefCell::new(HashMap::new()),
+            consumer_streams: RefCell::new(HashMap::new()),
             install_consumer: Cell::new(None),
             confirm_callbacks: RefCell::new(None),
+            next_publish_tag: Cell::new(0),
+            pending_confirms: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    fn publish(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> Result<(), AmqpConnectionError> {
+        self.publish_slices(exchange, routing_key, properties, flags, &[content])
+    }
+
+    fn publish_chunks(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, chunks: Vec<Vec<u8>>) -> Result<(), AmqpConnectionError> {
+        let slices: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.as_slice()).collect();
+        self.publish_slices(exchange, routing_key, properties, flags, &slices)
+    }
+
+    fn publish_awaitable(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[u8]) -> impl Future<Output = Result<(), AmqpConnectionError>> {
+        let delivery_tag = self.next_publish_tag.get();
+        let confirm = if delivery_tag == 0 {
+            Err(AmqpConnectionError::InvalidParameters)
+        } else {
+            self.publish(exchange, routing_key, properties, flags, content).map(|_| {
+                let (rx, tx) = async_channel_create();
+                self.pending_confirms.borrow_mut().insert(delivery_tag, tx);
+                rx
+            })
+        };
+
+        async move {
+            confirm?.receive().await
         }
     }
 
-    fn publish(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, mut content: &[u8]) -> Result<(), AmqpConnectionError> {
+    fn publish_slices(&self, exchange: String, routing_key: String, properties: AmqpBasicProperties, flags: AmqpPublishFlags, content: &[&[u8]]) -> Result<(), AmqpConnectionError> {
         self.is_channel_valid()?;
+        if flags.has_immediate() {
+            return Err(AmqpConnectionError::InvalidParameters);
+        }
+
+        if !self.active.get() {
+            return Err(AmqpConnectionError::ChannelPaused);
+        }
+
+        if self.connection.is_blocked() {
+            return Err(AmqpConnectionError::ConnectionBlocked);
+        }
+
+        // broker numbers confirms per published message, starting from 1
+        let delivery_tag = self.next_publish_tag.get();
+        if delivery_tag > 0 {
+            self.next_publish_tag.set(delivery_tag + 1);
+        }
 
         let frame = AmqpFrame {
             channel: self.number.get() as u16,
@@ -457,26 +864,41 @@ impl AmqpChannelInternals {
 
         let frame = AmqpFrame {
             channel: self.number.get() as u16,
-            payload: AmqpFramePayload::Header(AMQP_CLASS_BASIC, content.len() as u64, properties),
+            payload: AmqpFramePayload::Header(AMQP_CLASS_BASIC, content.iter().map(|chunk| chunk.len()).sum::<usize>() as u64, properties),
         };
 
         self.connection.writer_queue.send(Some(frame));
 
-        let mut total_bytes_to_send = content.len();
-        while total_bytes_to_send > 0 {
-            let bytes_in_frame = min(total_bytes_to_send, self.connection.max_frame_size.get() as usize);
+        let max_frame_size = self.connection.max_frame_size.get() as usize;
+        let mut data_buffer = self.connection.buffers.get_buffer();
+
+        // content frames are filled up to max_frame_size, regardless of chunk boundaries
+        for mut chunk in content.iter().copied() {
+            while !chunk.is_empty() {
+                let bytes_to_copy = min(chunk.len(), max_frame_size - data_buffer.len());
+                data_buffer.extend_from_slice(&chunk[..bytes_to_copy]);
+                chunk = &chunk[bytes_to_copy..];
 
-            let mut data_buffer = self.connection.buffers.get_buffer();
-            data_buffer.extend_from_slice(&content[..bytes_in_frame]);
+                if data_buffer.len() == max_frame_size {
+                    let frame = AmqpFrame {
+                        channel: self.number.get() as u16,
+                        payload: AmqpFramePayload::Content(std::mem::replace(&mut data_buffer, self.connection.buffers.get_buffer())),
+                    };
 
+                    self.connection.writer_queue.send(Some(frame));
+                }
+            }
+        }
+
+        if data_buffer.is_empty() {
+            self.connection.buffers.put_buffer(data_buffer);
+        } else {
             let frame = AmqpFrame {
                 channel: self.number.get() as u16,
                 payload: AmqpFramePayload::Content(data_buffer),
             };
 
             self.connection.writer_queue.send(Some(frame));
-            content = &content[bytes_in_frame..];
-            total_bytes_to_send -= bytes_in_frame;
         }
 
         Ok(())
@@ -517,50 +939,73 @@ impl AmqpChannelInternals {
         }
     }
 
+    fn dispatch_completed_message(&self) -> Result<(), AmqpConnectionError> {
+        let frame = self.message_in_flight.borrow_mut().build_if_completed()?;
+        match frame {
+            None | Some((MessageDeliveryMode::None, _))=> (),
+            Some((MessageDeliveryMode::Return(code, reason, exchange, routing_key), mut message)) => {
+                if let Some(callback) = &*self.on_return.borrow() {
+                    callback(code, reason, exchange, routing_key, &mut message);
+                }
+
+                self.message_in_flight.borrow_mut().return_buffer(message.content);
+            },
+            Some((MessageDeliveryMode::Deliver(consumer_tag, delivery_tag, redelivered, exchange, routing_key), mut message)) => {
+                let consumers = self.consumers.borrow();
+                let consumer = consumers.get(&consumer_tag);
+
+                match consumer {
+                    None => eprintln!("Received message with consumer tag {}, but no consumer installed", consumer_tag),
+                    Some(callback) => {
+                        callback(delivery_tag, redelivered, exchange, routing_key, &mut message);
+                        self.message_in_flight.borrow_mut().return_buffer(message.content);
+                    },
+                }
+            },
+            Some((MessageDeliveryMode::Get, message)) => {
+                self.message_tx.send(Ok(message));
+            },
+        };
+
+        Ok(())
+    }
+
     pub fn handle_frame(&self, frame: AmqpFrame) -> Result<(), AmqpConnectionError> {
+        if self.closing.get() {
+            return self.handle_closing_frame(frame);
+        }
+
+        // content of a message is never interleaved with methods on the same channel, so
+        // mixing it into the next message would only hide broken stream
+        if matches!(frame.payload, AmqpFramePayload::Method(_)) && self.message_in_flight.borrow().is_prepared() {
+            return Err(AmqpConnectionError::ProtocolError("Method frame received while message content is incomplete"));
+        }
+
         match frame.payload {
             AmqpFramePayload::Header(_, size, properties) => {
-                self.message_in_flight.borrow_mut().prepare_from_header(size, properties)?;
-                Ok(())
+                let max_message_size = self.connection.max_message_size.get();
+                let prepared = self.message_in_flight.borrow_mut().prepare_from_header(size, properties, max_message_size);
+                match prepared {
+                    // empty message has no content frames
+                    Ok(_) if size == 0 => self.dispatch_completed_message(),
+                    Ok(_) => Ok(()),
+                    Err(error) if size > max_message_size as u64 => {
+                        self.close_with_error(error, AMQP_REPLY_CONTENT_TOO_LARGE, "content too large");
+                        Ok(())
+                    },
+                    Err(error) => Err(error),
+                }
             },
             AmqpFramePayload::Content(data) => {
                 self.message_in_flight.borrow_mut().append_data(&data)?;
                 self.connection.buffers.put_buffer(data);
-
-                let frame = self.message_in_flight.borrow_mut().build_if_completed()?;
-                match frame {
-                    None | Some((MessageDeliveryMode::None, _))=> (),
-                    Some((MessageDeliveryMode::Return(code, reason, class, method), mut message)) => {
-                        match &*self.on_return.borrow_mut() {
-                            None => (),
-                            Some(callback) => {
-                                callback(code, reason, class, method, &mut message);
-                                self.message_in_flight.borrow_mut().return_buffer(message.content);
-                            },
-                        }
-                    },
-                    Some((MessageDeliveryMode::Deliver(consumer_tag, delivery_tag, redelivered, exchange, routing_key), mut message)) => {
-                        let consumers = self.consumers.borrow();
-                        let consumer = consumers.get(&consumer_tag);
-
-                        match consumer {
-                            None => eprintln!("Received message with consumer tag {}, but no consumer installed", consumer_tag),
-                            Some(callback) => {
-                                callback(delivery_tag, redelivered, exchange, routing_key, &mut message);
-                                self.message_in_flight.borrow_mut().return_buffer(message.content);
-                            },
-                        }
-                    },
-                    Some((MessageDeliveryMode::Get, message)) => {
-                        self.message_tx.send(Ok(message));
-                    },
-                };
-
-                Ok(())
+                self.dispatch_completed_message()
             },
             AmqpFramePayload::Method(AmqpMethod::ChannelClose(code, reason, class, method)) => {
                 let error = AmqpConnectionError::ChannelClosedByServer(code, reason, class, method);
                 *self.last_error.borrow_mut() = Some(error.clone());
+                self.fail_pending_confirms(error.clone());
+                self.fail_consumer_streams(error.clone());
                 self.tx.send(Err(error.clone()));
                 Err(error)
             },
@@ -608,6 +1053,16 @@ impl AmqpChannelInternals {
                 self.tx.send(Ok(frame));
                 Ok(())
             },
+            AmqpFramePayload::Method(AmqpMethod::ExchangeBindOk()) if self.wait_list.exchange_bind_ok.get() => {
+                self.wait_list.exchange_bind_ok.set(false);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
+            AmqpFramePayload::Method(AmqpMethod::ExchangeUnbindOk()) if self.wait_list.exchange_unbind_ok.get() => {
+                self.wait_list.exchange_unbind_ok.set(false);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
             AmqpFramePayload::Method(AmqpMethod::QueueDeclareOk(_, _, _)) if self.wait_list.queue_declare_ok.get() => {
                 self.wait_list.queue_declare_ok.set(false);
                 self.tx.send(Ok(frame));
@@ -655,6 +1110,28 @@ impl AmqpChannelInternals {
                 self.tx.send(Ok(frame));
                 Ok(())
             },
+            AmqpFramePayload::Method(AmqpMethod::BasicCancel(tag, flags)) => {
+                self.consumers.borrow_mut().remove(&tag);
+                if let Some(stream) = self.consumer_streams.borrow_mut().remove(&tag) {
+                    stream.send(Err(AmqpConnectionError::ConsumerCancelled(tag.clone())));
+                }
+
+                // no-wait bit
+                if flags & 1 == 0 {
+                    let frame = AmqpFrame {
+                        channel: self.number.get() as u16,
+                        payload: AmqpFramePayload::Method(AmqpMethod::BasicCancelOk(tag.clone())),
+                    };
+
+                    self.connection.writer_queue.send(Some(frame));
+                }
+
+                if let Some(callback) = &*self.on_cancel.borrow() {
+                    callback(tag);
+                }
+
+                Ok(())
+            },
             AmqpFramePayload::Method(AmqpMethod::BasicCancelOk(_)) if self.wait_list.basic_cancel_ok.get() => {
                 self.wait_list.basic_cancel_ok.set(false);
                 self.tx.send(Ok(frame));
@@ -676,6 +1153,36 @@ impl AmqpChannelInternals {
                 self.tx.send(Ok(frame));
                 Ok(())
             },
+            AmqpFramePayload::Method(AmqpMethod::TxSelectOk()) if self.wait_list.tx_select_ok.get() => {
+                self.wait_list.tx_select_ok.set(false);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
+            AmqpFramePayload::Method(AmqpMethod::TxCommitOk()) if self.wait_list.tx_commit_ok.get() => {
+                self.wait_list.tx_commit_ok.set(false);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
+            AmqpFramePayload::Method(AmqpMethod::TxRollbackOk()) if self.wait_list.tx_rollback_ok.get() => {
+                self.wait_list.tx_rollback_ok.set(false);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
+            AmqpFramePayload::Method(
+                AmqpMethod::ExchangeDeclareOk() |
+                AmqpMethod::ExchangeDeleteOk() |
+                AmqpMethod::ExchangeBindOk() |
+                AmqpMethod::ExchangeUnbindOk() |
+                AmqpMethod::QueueDeclareOk(_, _, _) |
+                AmqpMethod::QueueBindOk() |
+                AmqpMethod::QueueUnbindOk() |
+                AmqpMethod::QueuePurgeOk(_) |
+                AmqpMethod::QueueDeleteOk(_)
+            ) if self.wait_list.pipelined.get() > 0 => {
+                self.wait_list.pipelined.set(self.wait_list.pipelined.get() - 1);
+                self.tx.send(Ok(frame));
+                Ok(())
+            },
             AmqpFramePayload::Method(AmqpMethod::BasicAck(delivery_tag, multiple)) => {
                 self.on_ack(delivery_tag, multiple);
                 Ok(())
@@ -688,9 +1195,85 @@ impl AmqpChannelInternals {
         }
     }
 
+    // Until close is confirmed frames already sent by server are dropped. ChannelClosed tells
+    // connection to release the slot, channel number can't be reused before that.
+    fn handle_closing_frame(&self, frame: AmqpFrame) -> Result<(), AmqpConnectionError> {
+        match frame.payload {
+            AmqpFramePayload::Method(AmqpMethod::ChannelCloseOk()) => Err(AmqpConnectionError::ChannelClosed),
+            // server closed the channel at the same time, it is done once it gets close-ok
+            AmqpFramePayload::Method(AmqpMethod::ChannelClose(_, _, _, _)) => {
+                let frame = AmqpFrame {
+                    channel: self.number.get() as u16,
+                    payload: AmqpFramePayload::Method(AmqpMethod::ChannelCloseOk()),
+                };
+
+                self.connection.writer_queue.send(Some(frame));
+                Err(AmqpConnectionError::ChannelClosed)
+            },
+            AmqpFramePayload::Content(data) => {
+                self.connection.buffers.put_buffer(data);
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // Closes channel on client side, remaining frames sent by server for this channel are ignored
+    fn close_with_error(&self, error: AmqpConnectionError, code: u16, reason: &str) {
+        let frame = AmqpFrame {
+            channel: self.number.get() as u16,
+            payload: AmqpFramePayload::Method(AmqpMethod::ChannelClose(code, reason.to_string(), 0, 0)),
+        };
+
+        self.connection.writer_queue.send(Some(frame));
+        self.closing.set(true);
+
+        *self.last_error.borrow_mut() = Some(error.clone());
+        self.fail_pending_confirms(error.clone());
+        self.fail_consumer_streams(error.clone());
+        self.message_tx.send(Err(error.clone()));
+        self.tx.send(Err(error));
+    }
+
+    fn is_publish_paused(&self) -> bool {
+        !self.active.get() || self.connection.is_blocked()
+    }
+
+    fn enable_confirms(&self) {
+        if self.next_publish_tag.get() == 0 {
+            self.next_publish_tag.set(1);
+        }
+    }
+
+    // delivery_tag 0 with multiple flag refers to all outstanding messages
+    // Entries are returned with their own delivery tags, multiple flag confirms a whole range at once
+    fn take_pending_confirms(&self, delivery_tag: u64, multiple: bool) -> Vec<(u64, AsyncChannelTx<Result<(), AmqpConnectionError>>)> {
+        let mut pending = self.pending_confirms.borrow_mut();
+        if !multiple {
+            return pending.remove_entry(&delivery_tag).into_iter().collect();
+        }
+
+        let remaining = if delivery_tag == 0 { BTreeMap::new() } else { pending.split_off(&(delivery_tag + 1)) };
+        std::mem::replace(&mut *pending, remaining).into_iter().collect()
+    }
+
+    pub(super) fn fail_pending_confirms(&self, error: AmqpConnectionError) {
+        let pending = std::mem::take(&mut *self.pending_confirms.borrow_mut());
+        pending.into_values().for_each(|tx| tx.send(Err(error.clone())));
+    }
+
+    pub(super) fn fail_consumer_streams(&self, error: AmqpConnectionError) {
+        let streams = std::mem::take(&mut *self.consumer_streams.borrow_mut());
+        streams.into_values().for_each(|tx| tx.send(Err(error.clone())));
+    }
+
     fn on_ack(&self, delivery_tag: u64, multiple: bool) {
+        let pending = self.take_pending_confirms(delivery_tag, multiple);
+        pending.iter().for_each(|(_, tx)| tx.send(Ok(())));
+
         match &*self.confirm_callbacks.borrow() {
-            None => eprintln!("Received basic.on-ack without confirm callbacks"),
+            None if pending.is_empty() => eprintln!("Received basic.on-ack without confirm callbacks"),
+            None => (),
             Some((on_ack, _)) => {
                 on_ack(delivery_tag, multiple);
             },
@@ -698,8 +1281,12 @@ impl AmqpChannelInternals {
     }
 
     fn on_nack(&self, delivery_tag: u64, flags: AmqpNackFlags) {
+        let pending = self.take_pending_confirms(delivery_tag, flags.has_multiple());
+        pending.iter().for_each(|(tag, tx)| tx.send(Err(AmqpConnectionError::MessageNacked(*tag))));
+
         match &*self.confirm_callbacks.borrow() {
-            None => eprintln!("Received basic.on-ack without confirm callbacks"),
+            None if pending.is_empty() => eprintln!("Received basic.on-nack without confirm callbacks"),
+            None => (),
             Some((_, on_nack)) => {
                 on_nack(delivery_tag, flags);
             },
@@ -718,28 +1305,25 @@ enum MessageDeliveryMode {
 #[derive(Debug, Default, Clone)]
 struct AmqpMessageBuilder {
     mode: MessageDeliveryMode,
+    header_received: bool,
     size: usize,
     properties: AmqpBasicProperties,
     content: Vec<u8>,
 }
 
 impl AmqpMessageBuilder {
+    // Message stays in progress until all content declared by header arrives
     fn build_if_completed(&mut self) -> Result<Option<(MessageDeliveryMode, AmqpMessage)>, AmqpConnectionError> {
-        if !self.is_prepared() {
+        if !self.header_received || !self.is_complete() {
             return Ok(None);
         }
 
-        let result = if self.is_complete() {
-            Ok(Some((std::mem::take(&mut self.mode), AmqpMessage { properties: std::mem::take(&mut self.properties), content: std::mem::take(&mut self.content) })))
-        } else {
-            eprintln!("Discarding incomplete frame");
-            Ok(None)
-        };
+        let result = Some((std::mem::take(&mut self.mode), AmqpMessage { properties: std::mem::take(&mut self.properties), content: std::mem::take(&mut self.content) }));
 
-        self.mode = MessageDeliveryMode::None;
+        self.header_received = false;
         self.size = 0;
 
-        result
+        Ok(result)
     }
 
     fn return_buffer(&mut self, buffer: Vec<u8>) {
@@ -750,12 +1334,31 @@ impl AmqpMessageBuilder {
     }
 
     fn prepare_mode(&mut self, mode: MessageDeliveryMode) -> Result<(), AmqpConnectionError> {
+        if self.is_prepared() {
+            return Err(AmqpConnectionError::ProtocolError("New message started while previous one is incomplete"));
+        }
+
         self.mode = mode;
         Ok(())
     }
 
-    fn prepare_from_header(&mut self, size: u64, properties: AmqpBasicProperties) -> Result<(), AmqpConnectionError> {
+    fn prepare_from_header(&mut self, size: u64, properties: AmqpBasicProperties, max_size: usize) -> Result<(), AmqpConnectionError> {
+        // size is declared by the server, content buffer is reserved upfront
+        if size > max_size as u64 {
+            self.mode = MessageDeliveryMode::None;
+            return Err(AmqpConnectionError::ProtocolError("message size exceeds limit"));
+        }
+
+        if !self.is_prepared() {
+            return Err(AmqpConnectionError::ProtocolError("Header frame received without method first"));
+        }
+
+        if self.header_received {
+            return Err(AmqpConnectionError::ProtocolError("Header frame received twice for one message"));
+        }
+
         let size = size as usize;
+        self.header_received = true;
 
         self.properties = properties;
         self.size = size;
@@ -768,10 +1371,14 @@ impl AmqpMessageBuilder {
     }
 
     fn append_data(&mut self, data: &[u8]) -> Result<(), AmqpConnectionError> {
-        if !self.is_prepared() {
+        if !self.header_received {
             return Err(AmqpConnectionError::ProtocolError("Content frame received without header first"));
         }
 
+        if self.content.len() + data.len() > self.size {
+            return Err(AmqpConnectionError::ProtocolError("Content frame exceeds size declared in header"));
+        }
+
         self.content.extend_from_slice(data);
         Ok(())
     }
@@ -787,3 +1394,53 @@ impl AmqpMessageBuilder {
         self.content.len() == self.size
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_header_test() {
+        let mut builder = AmqpMessageBuilder::default();
+        builder.prepare_mode(MessageDeliveryMode::Get).unwrap();
+
+        // header frame claiming absurd content size must not reserve memory
+        let result = builder.prepare_from_header(u64::MAX, AmqpBasicProperties::default(), 1024);
+        assert!(matches!(result, Err(AmqpConnectionError::ProtocolError(_))));
+        assert_eq!(builder.content.capacity(), 0);
+        assert!(!builder.is_prepared());
+
+        builder.prepare_mode(MessageDeliveryMode::Get).unwrap();
+        assert!(builder.prepare_from_header(1024, AmqpBasicProperties::default(), 1024).is_ok());
+        assert!(builder.content.capacity() >= 1024);
+    }
+
+    #[test]
+    fn out_of_order_content_test() {
+        let mut builder = AmqpMessageBuilder::default();
+
+        // content before any method or header
+        assert!(matches!(builder.append_data(b"test"), Err(AmqpConnectionError::ProtocolError(_))));
+
+        builder.prepare_mode(MessageDeliveryMode::Get).unwrap();
+        assert!(matches!(builder.append_data(b"test"), Err(AmqpConnectionError::ProtocolError(_))));
+
+        builder.prepare_from_header(8, AmqpBasicProperties::default(), 1024).unwrap();
+        builder.append_data(b"test").unwrap();
+        assert!(builder.build_if_completed().unwrap().is_none());
+
+        // next delivery starts before previous content is complete
+        let result = builder.prepare_mode(MessageDeliveryMode::Get);
+        assert!(matches!(result, Err(AmqpConnectionError::ProtocolError(_))));
+
+        assert!(matches!(builder.append_data(b"too-long"), Err(AmqpConnectionError::ProtocolError(_))));
+
+        builder.append_data(b"data").unwrap();
+        match builder.build_if_completed().unwrap() {
+            Some((MessageDeliveryMode::Get, message)) => assert_eq!(message.content.as_slice(), b"testdata"),
+            _ => panic!("message expected"),
+        }
+
+        assert!(!builder.is_prepared());
+    }
+}
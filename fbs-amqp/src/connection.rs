@@ -1,24 +1,107 @@
 use std::cell::{Cell, RefCell};
-use std::cmp::min;
 use std::collections::{HashMap, VecDeque};
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::fmt::{Debug, Formatter};
 use std::time::Duration;
 
-use fbs_library::socket::{Socket, SocketDomain, SocketType, SocketFlags};
+use fbs_library::socket::{Socket, SocketDomain, SocketType, SocketFlags, SocketShutdown};
 use fbs_library::indexed_list::IndexedList;
 use fbs_runtime::async_utils::{AsyncSignal, AsyncChannelRx, AsyncChannelTx, async_channel_create};
-use fbs_runtime::{async_connect, async_write, async_read_into, async_spawn, async_sleep};
+use fbs_runtime::{async_connect, async_connect_timeout, async_write, async_spawn, async_sleep, async_shutdown, AsyncBufReader, AsyncBufReadError, AsyncBufWriter, CancellationScope};
 use fbs_resolver::resolve_address;
 use fbs_executor::TaskHandle;
 
-use super::{AmqpConnectionError, AmqpChannel};
+use super::{AmqpConnectionError, AmqpChannel, AmqpData, AmqpStateCallback, AmqpConnectedHook};
 use super::channel::AmqpChannelInternals;
 use super::frame::{AmqpProtocolHeader, AmqpFrame, AmqpFramePayload, AmqpMethod};
 use super::frame_reader::AmqpFrameReader;
 use super::frame_writer::FrameWriter;
 
 const FRAME_EXTRA_SIZE: u32 = 8;  // size of frame header and footer
+const FRAME_MIN_SIZE: u32 = 4096;  // frame-min-size from the spec
+const READ_BUFFER_RETAIN_SIZE: usize = 16384;  // bigger read buffers are released after frame is parsed
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 128 * 1024 * 1024;  // same as RabbitMQ default
+const DEFAULT_MAX_COALESCED_FRAMES: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AmqpReconnectPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for AmqpReconnectPolicy {
+    fn default() -> Self {
+        Self { max_retries: 0, backoff: Duration::new(1, 0) }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AmqpAuth {
+    Plain { username: String, password: String },
+    External,
+}
+
+impl AmqpAuth {
+    fn mechanism(&self) -> &'static str {
+        match self {
+            AmqpAuth::Plain { .. } => "PLAIN",
+            AmqpAuth::External => "EXTERNAL",
+        }
+    }
+
+    fn response(&self) -> String {
+        match self {
+            AmqpAuth::Plain { username, password } => {
+                let mut sasl = String::new();
+                sasl.push('\x00');
+                sasl.push_str(username);
+                sasl.push('\x00');
+                sasl.push_str(password);
+                sasl
+            },
+            AmqpAuth::External => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AmqpServerProperties {
+    pub version: (u8, u8),
+    pub properties: HashMap<String, AmqpData>,
+    pub mechanisms: Vec<String>,
+    pub locales: Vec<String>,
+}
+
+impl AmqpServerProperties {
+    fn new(major: u8, minor: u8, properties: HashMap<String, AmqpData>, mechanisms: &str, locales: &str) -> Self {
+        Self {
+            version: (major, minor),
+            properties,
+            mechanisms: mechanisms.split(' ').filter(|v| !v.is_empty()).map(String::from).collect(),
+            locales: locales.split(' ').filter(|v| !v.is_empty()).map(String::from).collect(),
+        }
+    }
+
+    // Looks up flag in server-properties "capabilities" table, e.g. publisher_confirms
+    pub fn has_capability(&self, name: &str) -> bool {
+        match self.properties.get("capabilities") {
+            Some(AmqpData::FieldTable(capabilities)) => matches!(capabilities.get(name), Some(AmqpData::Bool(true))),
+            _ => false,
+        }
+    }
+
+    pub fn has_mechanism(&self, mechanism: &str) -> bool {
+        self.mechanisms.iter().any(|v| v == mechanism)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AmqpConnectionState {
+    Connecting,
+    Reconnecting(u32),
+    Connected,
+    Disconnected(AmqpConnectionError),
+}
 
 #[derive(Default)]
 pub struct AmqpConnectionParams {
@@ -27,7 +110,26 @@ pub struct AmqpConnectionParams {
     pub password: String,
     pub vhost: String,
     pub heartbeat: u16,
+    pub connect_timeout: Option<Duration>,   // when not set, kernel TCP connect timeout applies
+    pub auth: Option<AmqpAuth>,      // when not set, PLAIN with username/password is used
     pub on_error: Option<Box<dyn Fn(AmqpConnectionError)>>,
+    pub reconnect: Option<AmqpReconnectPolicy>,
+    pub on_state_change: Option<AmqpStateCallback>,
+    pub on_connected: Option<AmqpConnectedHook>,
+    // broker stopped reading from publishing connections due to resource alarm, argument is reason
+    pub on_blocked: Option<Box<dyn Fn(String)>>,
+    pub on_unblocked: Option<Box<dyn Fn()>>,
+    // merged over defaults from default_client_properties, so single entries can be overridden
+    pub client_properties: HashMap<String, AmqpData>,
+    // upper bound for negotiated frame_max, server value is used when it is smaller
+    pub max_frame_size: Option<u32>,
+    // limits stalls in the middle of a frame, when not set twice the heartbeat interval is used
+    pub read_timeout: Option<Duration>,
+    // messages declared bigger than this close the channel, DEFAULT_MAX_MESSAGE_SIZE when not set
+    pub max_message_size: Option<usize>,
+    // frames already queued are sent together with a single write, up to this many at once.
+    // DEFAULT_MAX_COALESCED_FRAMES when not set, 1 writes every frame separately
+    pub max_coalesced_frames: Option<usize>,
 }
 
 impl Debug for AmqpConnectionParams {
@@ -38,33 +140,205 @@ impl Debug for AmqpConnectionParams {
         .field("password", &self.password)
         .field("vhost", &self.vhost)
         .field("heartbeat", &self.heartbeat)
+        .field("connect_timeout", &self.connect_timeout)
+        .field("auth", &self.auth.as_ref().map(|auth| auth.mechanism()))
         .field("on_error", &self.on_error.is_some())
+        .field("reconnect", &self.reconnect)
+        .field("on_state_change", &self.on_state_change.is_some())
+        .field("on_connected", &self.on_connected.is_some())
+        .field("on_blocked", &self.on_blocked.is_some())
+        .field("on_unblocked", &self.on_unblocked.is_some())
+        .field("client_properties", &self.client_properties)
+        .field("max_frame_size", &self.max_frame_size)
+        .field("read_timeout", &self.read_timeout)
+        .field("max_message_size", &self.max_message_size)
+        .field("max_coalesced_frames", &self.max_coalesced_frames)
         .finish()
     }
 }
 
-#[derive(Debug)]
+impl AmqpConnectionParams {
+    pub fn default_client_properties() -> HashMap<String, AmqpData> {
+        let mut capabilities = HashMap::new();
+        capabilities.insert("publisher_confirms".to_string(), AmqpData::Bool(true));
+        capabilities.insert("consumer_cancel_notify".to_string(), AmqpData::Bool(true));
+        capabilities.insert("connection.blocked".to_string(), AmqpData::Bool(true));
+        capabilities.insert("authentication_failure_close".to_string(), AmqpData::Bool(true));
+
+        let mut properties = HashMap::new();
+        properties.insert("product".to_string(), AmqpData::LongString(env!("CARGO_PKG_NAME").to_string()));
+        properties.insert("version".to_string(), AmqpData::LongString(env!("CARGO_PKG_VERSION").to_string()));
+        properties.insert("platform".to_string(), AmqpData::LongString("Rust".to_string()));
+        properties.insert("capabilities".to_string(), AmqpData::FieldTable(capabilities));
+        properties
+    }
+
+    fn client_properties(&self) -> HashMap<String, AmqpData> {
+        let mut properties = Self::default_client_properties();
+        properties.extend(self.client_properties.clone());
+        properties
+    }
+
+    fn notify(&self, state: AmqpConnectionState) {
+        if let Some(callback) = &self.on_state_change {
+            callback(state);
+        }
+    }
+}
+
 pub struct AmqpConnection {
-    ptr: Rc<AmqpConnectionInternal>,
+    shared: Rc<AmqpConnectionShared>,
+}
+
+// Automatic reconnect replaces broken connection in place and passes its own handle to
+// on_connected hook, so everything that outlives single connection is kept here
+struct AmqpConnectionShared {
+    ptr: RefCell<Rc<AmqpConnectionInternal>>,
+    params: AmqpConnectionParams,
+    on_error: Option<Rc<dyn Fn(AmqpConnectionError)>>,
+    on_blocked: Option<Rc<dyn Fn(String)>>,
+    on_unblocked: Option<Rc<dyn Fn()>>,
+    on_connected: Option<Rc<AmqpConnectedHook>>,
+}
+
+impl Debug for AmqpConnection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AmqpConnection")
+        .field("ptr", &self.shared.ptr.borrow())
+        .field("params", &self.shared.params)
+        .field("on_error", &self.shared.on_error.is_some())
+        .field("on_blocked", &self.shared.on_blocked.is_some())
+        .field("on_unblocked", &self.shared.on_unblocked.is_some())
+        .field("on_connected", &self.shared.on_connected.is_some())
+        .finish()
+    }
 }
 
 impl AmqpConnection {
-    pub async fn connect(params: AmqpConnectionParams) -> Result<AmqpConnection, AmqpConnectionError> {
-        let result: AmqpConnection = AmqpConnection { ptr: Rc::new(AmqpConnectionInternal::new()) };
-        result.ptr.connect(params, result.ptr.clone()).await?;
+    pub async fn connect(mut params: AmqpConnectionParams) -> Result<AmqpConnection, AmqpConnectionError> {
+        if matches!(params.max_frame_size, Some(size) if size < FRAME_MIN_SIZE) {
+            return Err(AmqpConnectionError::InvalidParameters);
+        }
+
+        let on_error: Option<Rc<dyn Fn(AmqpConnectionError)>> = params.on_error.take().map(Rc::from);
+        let on_blocked: Option<Rc<dyn Fn(String)>> = params.on_blocked.take().map(Rc::from);
+        let on_unblocked: Option<Rc<dyn Fn()>> = params.on_unblocked.take().map(Rc::from);
+        let on_connected = params.on_connected.take().map(Rc::new);
+        let ptr = Self::establish(&params, on_error.clone(), false).await?;
+
+        let shared = AmqpConnectionShared { ptr: RefCell::new(ptr), params, on_error, on_blocked, on_unblocked, on_connected };
+        let mut result = AmqpConnection { shared: Rc::new(shared) };
+        result.install_callbacks();
+        result.run_on_connected().await?;
 
         Ok(result)
     }
 
+    // Replaces broken connection with a new one, channels opened on the previous connection are
+    // no longer usable - on_connected hook is expected to reopen them
+    pub async fn reconnect(&mut self) -> Result<(), AmqpConnectionError> {
+        let previous = self.ptr();
+        previous.on_error.borrow_mut().take();
+        previous.on_lost.borrow_mut().take();
+        previous.mark_connection_closed(AmqpConnectionError::ConnectionClosed, false);
+
+        let ptr = Self::establish(&self.shared.params, self.shared.on_error.clone(), true).await?;
+        *self.shared.ptr.borrow_mut() = ptr;
+
+        self.install_callbacks();
+        self.run_on_connected().await
+    }
+
+    fn ptr(&self) -> Rc<AmqpConnectionInternal> {
+        self.shared.ptr.borrow().clone()
+    }
+
+    // With reconnect policy set, read or write error on established connection starts
+    // reconnecting in the background
+    fn install_callbacks(&self) {
+        let ptr = self.ptr();
+        *ptr.on_blocked.borrow_mut() = self.shared.on_blocked.clone();
+        *ptr.on_unblocked.borrow_mut() = self.shared.on_unblocked.clone();
+
+        if self.shared.params.reconnect.is_some() {
+            let shared = Rc::downgrade(&self.shared);
+            *ptr.on_lost.borrow_mut() = Some(Box::new(move |error| {
+                async_spawn(Self::reconnect_lost(shared.clone(), error)).detach();
+            }));
+        }
+    }
+
+    async fn reconnect_lost(shared: Weak<AmqpConnectionShared>, error: AmqpConnectionError) {
+        // handle held here keeps connection open until reconnect finishes, even if user drops theirs
+        let mut connection = match shared.upgrade() {
+            None => return,
+            Some(shared) => AmqpConnection { shared },
+        };
+
+        connection.shared.params.notify(AmqpConnectionState::Disconnected(error));
+        if let Err(error) = connection.reconnect().await {
+            eprintln!("AMQP reconnect failed: {}", error);
+        }
+    }
+
+    async fn establish(params: &AmqpConnectionParams, on_error: Option<Rc<dyn Fn(AmqpConnectionError)>>, reconnecting: bool) -> Result<Rc<AmqpConnectionInternal>, AmqpConnectionError> {
+        let policy = params.reconnect.unwrap_or_default();
+        let mut attempt = 0;
+
+        loop {
+            match (reconnecting, attempt) {
+                (false, 0) => params.notify(AmqpConnectionState::Connecting),
+                (_, attempt) => params.notify(AmqpConnectionState::Reconnecting(attempt)),
+            }
+
+            let ptr = Rc::new(AmqpConnectionInternal::new());
+            let result = ptr.connect(params, ptr.clone()).await;
+
+            match result {
+                Ok(_) => {
+                    *ptr.on_error.borrow_mut() = on_error;
+                    params.notify(AmqpConnectionState::Connected);
+                    return Ok(ptr);
+                },
+                Err(AmqpConnectionError::ConnectError(_) | AmqpConnectionError::ReadError(_)) if attempt < policy.max_retries => {
+                    attempt += 1;
+                    async_sleep(policy.backoff).await;
+                },
+                Err(error) => {
+                    params.notify(AmqpConnectionState::Disconnected(error.clone()));
+                    return Err(error);
+                },
+            }
+        }
+    }
+
+    async fn run_on_connected(&mut self) -> Result<(), AmqpConnectionError> {
+        let hook = self.shared.on_connected.clone();
+        match hook {
+            None => Ok(()),
+            Some(hook) => hook(self).await,
+        }
+    }
+
     pub fn is_alive(&self) -> bool {
-        self.ptr.is_connection_valid().is_ok()
+        self.ptr().is_connection_valid().is_ok()
+    }
+
+    // Set while broker refuses to read published messages, see on_blocked
+    pub fn is_blocked(&self) -> bool {
+        self.ptr().is_blocked()
+    }
+
+    pub fn server_properties(&self) -> AmqpServerProperties {
+        self.ptr().server_properties.borrow().clone()
     }
 
     pub async fn channel_open(&mut self) -> Result<AmqpChannel, AmqpConnectionError> {
-        self.ptr.is_connection_valid()?;
+        let ptr = self.ptr();
+        ptr.is_connection_valid()?;
 
-        let channel = AmqpChannel::new(self.ptr.clone());
-        let index = self.ptr.set_channel(&channel);
+        let channel = AmqpChannel::new(ptr.clone());
+        let index = ptr.set_channel(&channel)?;
         channel.ptr.number.set(index);
 
         let frame = AmqpFrame {
@@ -72,7 +346,7 @@ impl AmqpConnection {
             payload: AmqpFramePayload::Method(AmqpMethod::ChannelOpen()),
         };
 
-        self.ptr.writer_queue.send(Some(frame));
+        ptr.writer_queue.send(Some(frame));
         channel.ptr.wait_list.channel_open_ok.set(true);
         channel.ptr.rx.receive().await?;
 
@@ -80,7 +354,8 @@ impl AmqpConnection {
     }
 
     pub async fn close(self) {
-        if self.ptr.is_connection_valid().is_err() {
+        let ptr = self.ptr();
+        if ptr.is_connection_valid().is_err() {
             return;
         }
 
@@ -89,107 +364,107 @@ impl AmqpConnection {
             payload: AmqpFramePayload::Method(AmqpMethod::ConnectionClose(0, "shutdown".to_string(), 0, 0)),
         };
 
-        self.ptr.writer_queue.send(Some(frame));
-        self.ptr.signal.wait().await;
+        ptr.writer_queue.send(Some(frame));
+        ptr.signal.wait().await;
     }
 
+    // Returns (allocations, deallocations, hits) of write side buffer pool
     pub fn get_buffer_stats(&self) -> (u64, u64, u64) {
-        self.ptr.buffers.get_stats()
+        self.ptr().buffers.get_stats()
+    }
+
+    // Same as get_buffer_stats, for pool of buffers holding frames read from socket
+    pub fn get_read_buffer_stats(&self) -> (u64, u64, u64) {
+        self.ptr().read_buffers.get_stats()
     }
 
     pub fn set_buffers_capacity(&mut self, capacity: usize) {
-        self.ptr.buffers.change_capacity(capacity)
+        self.ptr().buffers.change_capacity(capacity)
+    }
+
+    // Number of socket writes issued by the writer since connection was established
+    pub fn get_write_count(&self) -> u64 {
+        self.ptr().write_count.get()
     }
 }
 
 impl Drop for AmqpConnection {
     fn drop(&mut self) {
-        self.ptr.mark_connection_closed(AmqpConnectionError::ConnectionClosed, false);
+        // reconnect task holds its own handle, connection is closed with the last one
+        if Rc::strong_count(&self.shared) == 1 {
+            self.ptr().mark_connection_closed(AmqpConnectionError::ConnectionClosed, false);
+        }
     }
 }
 
 struct AmqpConnectionReader {
-    fd: Rc<Socket>,
-    read_buffer: Vec<u8>,
-    read_offset: usize,
-    frame_buffer: Vec<u8>,
+    reader: AsyncBufReader<Rc<Socket>>,
     pub buffers: Rc<BufferManager>,
+    read_buffers: Rc<BufferManager>,
+    read_timeout: Option<Duration>,
+    frame_max: usize,
 }
 
 impl AmqpConnectionReader {
-    fn new(fd: Rc<Socket>, buffers: Rc<BufferManager>) -> Self {
-        Self { fd, read_buffer: Vec::with_capacity(4096), read_offset: 0, frame_buffer: Vec::with_capacity(4096), buffers }
+    fn new(fd: Rc<Socket>, buffers: Rc<BufferManager>, read_buffers: Rc<BufferManager>, scope: CancellationScope) -> Self {
+        Self { reader: AsyncBufReader::with_capacity(fd, 4096).scope(&scope), buffers, read_buffers, read_timeout: None, frame_max: 0 }
     }
 
-    fn change_frame_size(&mut self, size: usize) {
-        assert!(self.read_buffer.capacity() <= size);
-        self.read_buffer.reserve(size - self.read_buffer.capacity());
-        self.frame_buffer.reserve(size - self.frame_buffer.capacity());
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
     }
 
-    async fn fill_buffer(&mut self) -> Result<usize, AmqpConnectionError> {
-        if self.read_offset < self.read_buffer.len() {
-            return Ok(self.read_buffer.len() - self.read_offset);
-        }
-
-        self.read_offset = 0;
-        let result = async_read_into(&self.fd, std::mem::take(&mut self.read_buffer), None).await;
-        match result {
-            Err((error, _)) => return Err(AmqpConnectionError::ReadError(error)),
-            Ok(buffer) => self.read_buffer = buffer,
-        }
-
-        if self.read_buffer.is_empty() {
-            return Err(AmqpConnectionError::ConnectionClosed);
-        }
-
-        Ok(self.read_buffer.len())
+    // Read buffer grows only for frames that need it, see read_frame_remainder
+    fn change_frame_size(&mut self, size: usize) {
+        self.frame_max = size;
     }
 
-    async fn read_bytes(&mut self, mut target: &mut [u8]) -> Result<(), AmqpConnectionError> {
-        let mut target_size = target.len();
-
-        while target_size > 0 {
-            let bytes_available = self.fill_buffer().await?;
-
-            let to_copy = min(target_size, bytes_available);
-            target.clone_from_slice(&self.read_buffer[self.read_offset .. self.read_offset + to_copy]);
-            self.read_offset += to_copy;
-            target_size -= to_copy;
-
-            target = &mut target[to_copy..];
+    fn map_error(error: AsyncBufReadError) -> AmqpConnectionError {
+        match error {
+            AsyncBufReadError::ReadError(error) => AmqpConnectionError::ReadError(error),
+            AsyncBufReadError::UnexpectedEof => AmqpConnectionError::ConnectionClosed,
         }
+    }
 
-        Ok(())
+    async fn read_bytes(&mut self, target: &mut [u8]) -> Result<(), AmqpConnectionError> {
+        self.reader.read_exact(target).await.map_err(Self::map_error)
     }
 
     async fn read_u8(&mut self) -> Result<u8, AmqpConnectionError> {
-        let mut bytes: [u8; 1] = [0; 1];
-        self.read_bytes(&mut bytes).await?;
-
-        Ok(u8::from_be_bytes(bytes))
+        self.reader.read_u8().await.map_err(Self::map_error)
     }
 
     async fn read_u16(&mut self) -> Result<u16, AmqpConnectionError> {
-        let mut bytes: [u8; 2] = [0; 2];
-        self.read_bytes(&mut bytes).await?;
-
-        Ok(u16::from_be_bytes(bytes))
+        self.reader.read_u16_be().await.map_err(Self::map_error)
     }
 
     async fn read_u32(&mut self) -> Result<u32, AmqpConnectionError> {
-        let mut bytes: [u8; 4] = [0; 4];
-        self.read_bytes(&mut bytes).await?;
-
-        Ok(u32::from_be_bytes(bytes))
+        self.reader.read_u32_be().await.map_err(Self::map_error)
     }
 
     async fn read_frame(&mut self) -> Result<AmqpFrame, AmqpConnectionError> {
+        // waiting for next frame is unbounded, idle connection is detected by heartbeats
         let frame_type = self.read_u8().await?;
+
+        self.reader.set_timeout(self.read_timeout);
+        let result = self.read_frame_remainder(frame_type).await;
+        self.reader.set_timeout(None);
+
+        result
+    }
+
+    async fn read_frame_remainder(&mut self, frame_type: u8) -> Result<AmqpFrame, AmqpConnectionError> {
         let channel = self.read_u16().await?;
         let payload_size = self.read_u32().await? as usize;
 
-        let mut frame_buffer = std::mem::take(&mut self.frame_buffer);
+        // large frame is read with a few big reads, buffer is shrunk back once it is parsed
+        let frame_size = payload_size + FRAME_EXTRA_SIZE as usize;
+        if frame_size > self.reader.capacity() && frame_size <= self.frame_max {
+            self.reader.reserve_capacity(frame_size);
+        }
+
+        // frame payload is only needed until it is parsed, content is copied to write side pool buffer
+        let mut frame_buffer = self.read_buffers.get_buffer();
         reserve_buffer_size(&mut frame_buffer, payload_size);
 
         self.read_bytes(&mut frame_buffer).await?;
@@ -201,7 +476,11 @@ impl AmqpConnectionReader {
 
         let mut reader = AmqpFrameReader::new(&frame_buffer);
         let result = reader.read_frame(&self.buffers, frame_type, channel);
-        self.frame_buffer = frame_buffer;
+        self.read_buffers.put_buffer(frame_buffer);
+
+        if self.reader.capacity() > READ_BUFFER_RETAIN_SIZE && self.reader.buffered() <= READ_BUFFER_RETAIN_SIZE {
+            self.reader.shrink_capacity(READ_BUFFER_RETAIN_SIZE);
+        }
 
         match result {
             Ok(frame) => Ok(frame),
@@ -221,6 +500,7 @@ fn reserve_buffer_size(buffer: &mut Vec<u8>, size: usize) {
 pub(super) struct BufferManager {
     size: Cell<usize>,
     max_capacity: Cell<usize>,
+    max_buffer_size: usize,
     buffers: RefCell<VecDeque<Vec<u8>>>,
     allocations: Cell<u64>,
     deallocations: Cell<u64>,
@@ -232,16 +512,19 @@ impl Debug for BufferManager {
         f.debug_struct("AmqpConnectionInternal")
         .field("size", &self.size)
         .field("max_capacity", &self.max_capacity)
+        .field("max_buffer_size", &self.max_buffer_size)
         .field("buffers", &self.buffers.borrow().len())
         .finish()
     }
 }
 
 impl BufferManager {
-    fn new(size: usize, max_capacity: usize) -> Self {
+    // Returned buffers with capacity above max_buffer_size are released instead of pooled
+    fn new(size: usize, max_capacity: usize, max_buffer_size: usize) -> Self {
         BufferManager {
             size: Cell::new(size),
             max_capacity: Cell::new(max_capacity),
+            max_buffer_size,
             buffers: RefCell::new(VecDeque::new()),
             allocations: Cell::new(0),
             deallocations: Cell::new(0),
@@ -288,7 +571,7 @@ impl BufferManager {
     }
 
     pub(super) fn put_buffer(&self, mut buffer: Vec<u8>) {
-        if self.buffers.borrow().len() >= self.max_capacity.get() {
+        if self.buffers.borrow().len() >= self.max_capacity.get() || buffer.capacity() > self.max_buffer_size {
             self.deallocations.set(self.deallocations.get() + 1);
             return;
         }
@@ -300,16 +583,29 @@ impl BufferManager {
 
 struct AmqpConnectionWriter {
     fd: Rc<Socket>,
+    writer: AsyncBufWriter<Rc<Socket>>,
     queue: VecDeque<AmqpFrame>,
     buffers: Rc<BufferManager>,
+    max_coalesced_frames: usize,
 }
 
 impl AmqpConnectionWriter {
     fn new(fd: Rc<Socket>, buffers: Rc<BufferManager>) -> Self {
-        Self { fd, queue: VecDeque::new(), buffers }
+        Self {
+            writer: AsyncBufWriter::with_capacity(fd.clone(), 4096),
+            fd,
+            queue: VecDeque::new(),
+            buffers,
+            max_coalesced_frames: DEFAULT_MAX_COALESCED_FRAMES,
+        }
+    }
+
+    fn is_coalescing(&self) -> bool {
+        self.queue.len() < self.max_coalesced_frames
     }
 
     fn change_frame_size(&mut self, size: usize) {
+        self.writer.set_high_water_mark(size);
         self.buffers.change_frame_size(size)
     }
 
@@ -317,43 +613,48 @@ impl AmqpConnectionWriter {
         self.queue.push_back(frame);
     }
 
+    // All queued frames are coalesced, so they usually go out with a single write
     async fn flush_all(&mut self) -> Result<(), AmqpConnectionError> {
-        loop {
-            let frame = self.queue.pop_front();
-            match frame {
-                None => return Ok(()),
-                Some(frame) => self.write_frame(frame).await?,
-            }
+        while let Some(frame) = self.queue.pop_front() {
+            self.write_frame(frame).await?;
         }
+
+        self.writer.flush().await.map_err(AmqpConnectionError::WriteError)
     }
 
     async fn write_frame(&mut self, frame: AmqpFrame) -> Result<(), AmqpConnectionError> {
         let data = FrameWriter::write_frame(frame, self.buffers.as_ref());
-        let result = async_write(&self.fd, data, None).await;
-
-        match result {
-            Ok(buffer) => self.buffers.put_buffer(buffer),
-            Err((error, _)) => return Err(AmqpConnectionError::WriteError(error)),
-        }
+        let result = self.writer.write_all(&data).await;
+        self.buffers.put_buffer(data);
 
-        Ok(())
+        result.map_err(AmqpConnectionError::WriteError)
     }
 }
 
 pub(super) struct AmqpConnectionInternal {
     pub writer_queue: AsyncChannelTx<Option<AmqpFrame>>,
     pub max_frame_size: Cell<u32>,
+    pub max_message_size: Cell<usize>,
+    write_count: Cell<u64>,
     fd: Rc<Socket>,
     channels: RefCell<IndexedList<Rc<AmqpChannelInternals>>>,
     read_handler: Cell<TaskHandle<()>>,
     write_handler: Cell<TaskHandle<()>>,
     heartbeat_handler: Cell<TaskHandle<()>>,
+    read_scope: CancellationScope,
     signal: AsyncSignal,
     max_channels: Cell<u16>,
     heartbeat: Cell<u16>,
     last_error: RefCell<Option<AmqpConnectionError>>,
-    on_error: RefCell<Option<Box<dyn Fn(AmqpConnectionError)>>>,
+    on_error: RefCell<Option<Rc<dyn Fn(AmqpConnectionError)>>>,
+    on_blocked: RefCell<Option<Rc<dyn Fn(String)>>>,
+    on_unblocked: RefCell<Option<Rc<dyn Fn()>>>,
+    // called once reading from or writing to established connection fails, see install_callbacks
+    on_lost: RefCell<Option<Box<dyn Fn(AmqpConnectionError)>>>,
+    blocked: Cell<bool>,
+    server_properties: RefCell<AmqpServerProperties>,
     pub buffers: Rc<BufferManager>,
+    read_buffers: Rc<BufferManager>,
 }
 
 impl Debug for AmqpConnectionInternal {
@@ -363,6 +664,8 @@ impl Debug for AmqpConnectionInternal {
         .field("max_channels", &self.max_channels.get())
         .field("heartbeat", &self.heartbeat.get())
         .field("last_error", &self.last_error.borrow())
+        .field("blocked", &self.blocked.get())
+        .field("server_properties", &self.server_properties.borrow())
         .field("fd", &self.fd)
         .field("channels", &self.channels.borrow())
         .field("writer_queue", &self.writer_queue)
@@ -380,13 +683,23 @@ impl AmqpConnectionInternal {
             read_handler: Cell::new(TaskHandle::default()),
             write_handler: Cell::new(TaskHandle::default()),
             heartbeat_handler: Cell::new(TaskHandle::default()),
+            read_scope: CancellationScope::new(),
             signal: AsyncSignal::new(),
             max_channels: Cell::new(100),
             max_frame_size: Cell::new(4096),
+            max_message_size: Cell::new(DEFAULT_MAX_MESSAGE_SIZE),
+            write_count: Cell::new(0),
             heartbeat: Cell::new(0),
             last_error: RefCell::new(None),
             on_error: RefCell::new(None),
-            buffers: Rc::new(BufferManager::new(4096, 10)),
+            on_blocked: RefCell::new(None),
+            on_unblocked: RefCell::new(None),
+            on_lost: RefCell::new(None),
+            blocked: Cell::new(false),
+            server_properties: RefCell::new(AmqpServerProperties::default()),
+            buffers: Rc::new(BufferManager::new(4096, 10, usize::MAX)),
+            // frames are read one at a time, so single retained buffer is enough
+            read_buffers: Rc::new(BufferManager::new(4096, 1, READ_BUFFER_RETAIN_SIZE)),
         }
     }
 
@@ -398,8 +711,22 @@ impl AmqpConnectionInternal {
         }
     }
 
-    fn set_channel(&self, channel: &AmqpChannel) -> usize {
-        self.channels.borrow_mut().insert(channel.ptr.clone()) + 1
+    fn set_channel(&self, channel: &AmqpChannel) -> Result<usize, AmqpConnectionError> {
+        let mut channels = self.channels.borrow_mut();
+        let index = channels.insert(channel.ptr.clone());
+
+        // channel-max of 0 means no limit was negotiated
+        let max_channels = self.max_channels.get() as usize;
+        if max_channels > 0 && index + 1 > max_channels {
+            channels.remove(index);
+            return Err(AmqpConnectionError::TooManyChannels(max_channels as u16));
+        }
+
+        Ok(index + 1)
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.blocked.get()
     }
 
     pub fn clear_channel(&self, index: usize) {
@@ -418,7 +745,8 @@ impl AmqpConnectionInternal {
                 let result = channel.handle_frame(frame);
                 match result {
                     Ok(_) => result,
-                    Err(AmqpConnectionError::ChannelClosedByServer(_, _, _, _)) => {
+                    // channel level errors, connection stays usable
+                    Err(AmqpConnectionError::ChannelClosedByServer(_, _, _, _) | AmqpConnectionError::ChannelClosed) => {
                         close_channel = true;
                         Ok(())
                     },
@@ -450,6 +778,26 @@ impl AmqpConnectionInternal {
                 self.signal.signal();
                 Ok(())
             },
+            AmqpFramePayload::Method(AmqpMethod::ConnectionBlocked(reason)) => {
+                self.blocked.set(true);
+
+                let on_blocked = self.on_blocked.borrow().clone();
+                if let Some(callback) = on_blocked {
+                    callback(reason);
+                }
+
+                Ok(())
+            },
+            AmqpFramePayload::Method(AmqpMethod::ConnectionUnblocked()) => {
+                self.blocked.set(false);
+
+                let on_unblocked = self.on_unblocked.borrow().clone();
+                if let Some(callback) = on_unblocked {
+                    callback();
+                }
+
+                Ok(())
+            },
             AmqpFramePayload::Heartbeat() => Ok(()),
             _ => Err(AmqpConnectionError::ProtocolError("Unexpected connection frame")),
         }
@@ -471,24 +819,30 @@ impl AmqpConnectionInternal {
 
             self.writer_queue.send(None);
 
+            // pending read would otherwise be completed only after peer closes its side
+            self.read_scope.cancel_all();
+
             let channels = self.channels.borrow();
-            channels.iter().for_each(|channel| {
-                match channel {
-                    None => (),
-                    Some(channel) => channel.tx.send(Err(error.clone())),
-                }
+            channels.iter_indexed().for_each(|(_, channel)| {
+                channel.fail_pending_confirms(error.clone());
+                channel.fail_consumer_streams(error.clone());
+                channel.tx.send(Err(error.clone()));
             });
 
-            match &*self.on_error.borrow() {
+            let on_error = self.on_error.borrow().clone();
+            match on_error {
                 None => (),
                 Some(callback) => callback(error),
             }
         }
     }
 
-    async fn connect(&self, mut params: AmqpConnectionParams, self_ptr: Rc<AmqpConnectionInternal>) -> Result<(), AmqpConnectionError> {
+    async fn connect(&self, params: &AmqpConnectionParams, self_ptr: Rc<AmqpConnectionInternal>) -> Result<(), AmqpConnectionError> {
         let address = resolve_address(&params.address, Some(5672)).await?;
-        let connected = async_connect(&self.fd, address).await;
+        let connected = match params.connect_timeout {
+            None => async_connect(&self.fd, address).await,
+            Some(timeout) => async_connect_timeout(&self.fd, address, timeout).await,
+        };
         match connected {
             Ok(_) => (),
             Err(error) => return Err(AmqpConnectionError::ConnectError(error)),
@@ -500,42 +854,66 @@ impl AmqpConnectionInternal {
             Err((error, _)) => return Err(AmqpConnectionError::WriteError(error)),
         }
 
-        let mut reader = AmqpConnectionReader::new(self.fd.clone(), self.buffers.clone());
+        let mut reader = AmqpConnectionReader::new(self.fd.clone(), self.buffers.clone(), self.read_buffers.clone(), self.read_scope.clone());
         let mut writer = AmqpConnectionWriter::new(self.fd.clone(), self.buffers.clone());
 
-        let _ = reader.read_frame().await?;
+        let frame = reader.read_frame().await?;
+        let server_properties = match frame.payload {
+            AmqpFramePayload::Method(AmqpMethod::ConnectionStart(major, minor, properties, mechanisms, locales)) => {
+                AmqpServerProperties::new(major, minor, properties, &mechanisms, &locales)
+            },
+            _ => return Err(AmqpConnectionError::ProtocolError("connection.start frame expected")),
+        };
+
+        let auth = match &params.auth {
+            Some(auth) => auth.clone(),
+            None => AmqpAuth::Plain { username: params.username.clone(), password: params.password.clone() },
+        };
+
+        if !server_properties.has_mechanism(auth.mechanism()) {
+            return Err(AmqpConnectionError::AuthMechanismUnsupported(auth.mechanism(), server_properties.mechanisms.join(" ")));
+        }
 
-        let mut sasl = String::new();
-        sasl.push('\x00');
-        sasl.push_str(&params.username);
-        sasl.push('\x00');
-        sasl.push_str(&params.password);
+        *self.server_properties.borrow_mut() = server_properties;
 
+        // capabilities table is what makes broker send connection.blocked and basic.cancel
         let response = AmqpFrame {
             channel: 0,
-            payload: AmqpFramePayload::Method(AmqpMethod::ConnectionStartOk(HashMap::new(), "PLAIN".to_string(), sasl, String::new())),
+            payload: AmqpFramePayload::Method(AmqpMethod::ConnectionStartOk(params.client_properties(), auth.mechanism().to_string(), auth.response(), String::new())),
         };
 
         writer.enqueue_frame(response);
         writer.flush_all().await?;
 
+        let mut frame_max = 0;
         let frame = reader.read_frame().await?;
         match &frame.payload {
             AmqpFramePayload::Method(AmqpMethod::ConnectionTune(channels, frame, heartbeat)) => {
-                if *frame > 0 {
-                    reader.change_frame_size((*frame) as usize);
-                    writer.change_frame_size((*frame) as usize);
+                // 0 from server means it has no limit
+                frame_max = match (*frame, params.max_frame_size) {
+                    (0, requested) => requested.unwrap_or(0),
+                    (frame, Some(requested)) => std::cmp::min(frame, requested),
+                    (frame, None) => frame,
+                };
+
+                if frame_max > 0 {
+                    reader.change_frame_size(frame_max as usize);
+                    writer.change_frame_size(frame_max as usize);
 
                     // no real server is going to send value so small, but we want to
                     // prevent an underflow
-                    if *frame > FRAME_EXTRA_SIZE {
-                        self.max_frame_size.set((*frame) - FRAME_EXTRA_SIZE);
+                    if frame_max > FRAME_EXTRA_SIZE {
+                        self.max_frame_size.set(frame_max - FRAME_EXTRA_SIZE);
                     }
                 }
 
                 self.max_channels.set(*channels);
                 self.heartbeat.set(*heartbeat);
             },
+            // authentication_failure_close makes broker explain rejected credentials this way
+            AmqpFramePayload::Method(AmqpMethod::ConnectionClose(code, reason, class, method)) => {
+                return Err(AmqpConnectionError::ConnectionClosedByServer(*code, reason.clone(), *class, *method));
+            },
             _ => (),
         }
 
@@ -543,9 +921,15 @@ impl AmqpConnectionInternal {
             self.heartbeat.set(params.heartbeat);
         }
 
+        self.max_message_size.set(params.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE));
+        writer.max_coalesced_frames = params.max_coalesced_frames.unwrap_or(DEFAULT_MAX_COALESCED_FRAMES).max(1);
+
+        let heartbeat = self.heartbeat.get() as u64;
+        reader.set_read_timeout(params.read_timeout.or((heartbeat > 0).then(|| Duration::from_secs(heartbeat * 2))));
+
         let response = AmqpFrame {
             channel: 0,
-            payload: AmqpFramePayload::Method(AmqpMethod::ConnectionTuneOk(self.max_channels.get(), self.max_frame_size.get(), self.heartbeat.get())),
+            payload: AmqpFramePayload::Method(AmqpMethod::ConnectionTuneOk(self.max_channels.get(), frame_max, self.heartbeat.get())),
         };
 
         writer.enqueue_frame(response);
@@ -561,7 +945,6 @@ impl AmqpConnectionInternal {
 
         let _ = reader.read_frame().await?;
 
-        *self.on_error.borrow_mut() = params.on_error.take();
         self.start_io_handler(writer, self.writer_queue.rx(), reader, self_ptr);
         Ok(())
     }
@@ -570,6 +953,7 @@ impl AmqpConnectionInternal {
 
         let heartbeat = self.heartbeat.get();
         let heartbeat_writer = writer_channel.tx();
+        let write_connection = connection.clone();
 
         self.heartbeat_handler.set(async_spawn(async move {
             let interval = Duration::new(heartbeat as u64, 0);
@@ -604,9 +988,18 @@ impl AmqpConnectionInternal {
                             },
                         }
                     },
+                    // read cancelled because connection has been closed already
+                    Err(_) if connection.last_error.borrow().is_some() => break,
+                    // failed write shuts socket down, so it ends up here as well
                     Err(error) => {
                         eprintln!("Connection closed unexpectedly: {}", error);
-                        connection.mark_connection_closed(error, false);
+                        connection.mark_connection_closed(error.clone(), false);
+
+                        let on_lost = connection.on_lost.borrow_mut().take();
+                        if let Some(callback) = on_lost {
+                            callback(error);
+                        }
+
                         break;
                     },
                 }
@@ -619,24 +1012,41 @@ impl AmqpConnectionInternal {
 
         self.write_handler.set(async_spawn(async move {
             loop {
-                // TODO: enqueue more frames at once before sending
                 let frame = writer_channel.receive().await;
 
                 match frame {
                     Some(frame) => {
                         writer.enqueue_frame(frame);
+
+                        // frames queued in the meantime go out with the same write
+                        let mut closing = false;
+                        while writer.is_coalescing() {
+                            match writer_channel.try_receive() {
+                                Some(Some(frame)) => writer.enqueue_frame(frame),
+                                Some(None) => {
+                                    closing = true;
+                                    break;
+                                },
+                                None => break,
+                            }
+                        }
+
                         let result = writer.flush_all().await;
+                        write_connection.write_count.set(writer.writer.write_count());
 
                         // on write error shutdown socket, this should cause read_handler to return error
                         // and mark connection closed
-                        if result.is_err() {
-                            eprintln!("Connection write error");
-                            let _ = writer.fd.shutdown(true, true);
+                        if result.is_err() || closing {
+                            if result.is_err() {
+                                eprintln!("Connection write error");
+                            }
+
+                            let _ = async_shutdown(&writer.fd, SocketShutdown::Both).await;
                             break;
                         }
                     },
                     None => {
-                        let _ = writer.fd.shutdown(true, true);
+                        let _ = async_shutdown(&writer.fd, SocketShutdown::Both).await;
                         break;
                     }
                 }
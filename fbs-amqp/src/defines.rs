@@ -23,6 +23,8 @@ pub const AMQP_METHOD_CONNECTION_OPEN: u16      = 40;
 pub const AMQP_METHOD_CONNECTION_OPEN_OK: u16   = 41;
 pub const AMQP_METHOD_CONNECTION_CLOSE: u16     = 50;
 pub const AMQP_METHOD_CONNECTION_CLOSE_OK: u16  = 51;
+pub const AMQP_METHOD_CONNECTION_BLOCKED: u16   = 60;
+pub const AMQP_METHOD_CONNECTION_UNBLOCKED: u16 = 61;
 
 pub const AMQP_METHOD_CHANNEL_OPEN: u16         = 10;
 pub const AMQP_METHOD_CHANNEL_OPEN_OK: u16      = 11;
@@ -35,6 +37,10 @@ pub const AMQP_METHOD_EXCHANGE_DECLARE: u16     = 10;
 pub const AMQP_METHOD_EXCHANGE_DECLARE_OK: u16  = 11;
 pub const AMQP_METHOD_EXCHANGE_DELETE: u16      = 20;
 pub const AMQP_METHOD_EXCHANGE_DELETE_OK: u16   = 21;
+pub const AMQP_METHOD_EXCHANGE_BIND: u16        = 30;
+pub const AMQP_METHOD_EXCHANGE_BIND_OK: u16     = 31;
+pub const AMQP_METHOD_EXCHANGE_UNBIND: u16      = 40;
+pub const AMQP_METHOD_EXCHANGE_UNBIND_OK: u16   = 51;
 
 pub const AMQP_METHOD_QUEUE_DECLARE: u16        = 10;
 pub const AMQP_METHOD_QUEUE_DECLARE_OK: u16     = 11;
@@ -69,6 +75,13 @@ pub const AMQP_METHOD_BASIC_NACK: u16           = 120;
 pub const AMQP_METHOD_CONFIRM_SELECT: u16       = 10;
 pub const AMQP_METHOD_CONFIRM_SELECT_OK: u16    = 11;
 
+pub const AMQP_METHOD_TX_SELECT: u16            = 10;
+pub const AMQP_METHOD_TX_SELECT_OK: u16         = 11;
+pub const AMQP_METHOD_TX_COMMIT: u16            = 20;
+pub const AMQP_METHOD_TX_COMMIT_OK: u16         = 21;
+pub const AMQP_METHOD_TX_ROLLBACK: u16          = 30;
+pub const AMQP_METHOD_TX_ROLLBACK_OK: u16       = 31;
+
 pub const AMQP_BASIC_PROPERTY_CONTENT_TYPE_BIT: u8      = 15;
 pub const AMQP_BASIC_PROPERTY_CONTENT_ENCODING_BIT: u8  = 14;
 pub const AMQP_BASIC_PROPERTY_HEADERS_BIT: u8           = 13;
@@ -83,3 +96,5 @@ pub const AMQP_BASIC_PROPERTY_TYPE_BIT: u8              = 5;
 pub const AMQP_BASIC_PROPERTY_USER_ID_BIT: u8           = 4;
 pub const AMQP_BASIC_PROPERTY_APP_ID_BIT: u8            = 3;
 pub const AMQP_BASIC_PROPERTY_CLUSTER_ID_BIT: u8        = 2;
+
+pub const AMQP_REPLY_CONTENT_TOO_LARGE: u16      = 311;
@@ -40,6 +40,8 @@ pub enum AmqpMethod {
     ConnectionOpenOk(),
     ConnectionClose(u16, String, u16, u16),                                         // reply-code, reply-text, class-id, method-id
     ConnectionCloseOk(),
+    ConnectionBlocked(String),                                                      // reason
+    ConnectionUnblocked(),
     ChannelOpen(),
     ChannelOpenOk(),
     ChannelClose(u16, String, u16, u16),                                            // reply-code, reply-text, class-id, method-id
@@ -50,6 +52,10 @@ pub enum AmqpMethod {
     ExchangeDeclareOk(),
     ExchangeDelete(String, u8),                                                     // name, flags
     ExchangeDeleteOk(),
+    ExchangeBind(String, String, String, u8, HashMap<String, AmqpData>),            // destination, source, routing-key, flags, arguments
+    ExchangeBindOk(),
+    ExchangeUnbind(String, String, String, u8, HashMap<String, AmqpData>),          // destination, source, routing-key, flags, arguments
+    ExchangeUnbindOk(),
     QueueDeclare(String, u8, HashMap<String, AmqpData>),                            // name, flags, arguments
     QueueDeclareOk(String, i32, i32),                                               // name, messages, consumers
     QueueBind(String, String, String, u8, HashMap<String, AmqpData>),               // name, exchange, routing-key, flags, arguments
@@ -79,4 +85,10 @@ pub enum AmqpMethod {
     BasicNack(u64, u8),                                                             // delivery-tag, multiple, requeue
     ConfirmSelect(bool),                                                            // no-wait
     ConfirmSelectOk(),
+    TxSelect(),
+    TxSelectOk(),
+    TxCommit(),
+    TxCommitOk(),
+    TxRollback(),
+    TxRollbackOk(),
 }
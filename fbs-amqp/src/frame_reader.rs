@@ -129,6 +129,13 @@ impl<'buffer> AmqpFrameReader<'buffer> {
             (AMQP_CLASS_CONNECTION, AMQP_METHOD_CONNECTION_CLOSE_OK) => {
                 Ok(AmqpMethod::ConnectionCloseOk())
             },
+            (AMQP_CLASS_CONNECTION, AMQP_METHOD_CONNECTION_BLOCKED) => {
+                let reason = self.read_short_string()?;
+                Ok(AmqpMethod::ConnectionBlocked(reason))
+            },
+            (AMQP_CLASS_CONNECTION, AMQP_METHOD_CONNECTION_UNBLOCKED) => {
+                Ok(AmqpMethod::ConnectionUnblocked())
+            },
             (AMQP_CLASS_CHANNEL, AMQP_METHOD_CHANNEL_OPEN_OK) => {
                 let _ = self.read_long_string()?;   // deprecated arg
                 Ok(AmqpMethod::ChannelOpenOk())
@@ -158,6 +165,12 @@ impl<'buffer> AmqpFrameReader<'buffer> {
             (AMQP_CLASS_EXCHANGE, AMQP_METHOD_EXCHANGE_DELETE_OK) => {
                 Ok(AmqpMethod::ExchangeDeleteOk())
             },
+            (AMQP_CLASS_EXCHANGE, AMQP_METHOD_EXCHANGE_BIND_OK) => {
+                Ok(AmqpMethod::ExchangeBindOk())
+            },
+            (AMQP_CLASS_EXCHANGE, AMQP_METHOD_EXCHANGE_UNBIND_OK) => {
+                Ok(AmqpMethod::ExchangeUnbindOk())
+            },
             (AMQP_CLASS_QUEUE, AMQP_METHOD_QUEUE_DECLARE_OK) => {
                 let name = self.read_short_string()?;
                 let message_count = self.read_i32()?;
@@ -185,6 +198,11 @@ impl<'buffer> AmqpFrameReader<'buffer> {
                 let tag = self.read_short_string()?;
                 Ok(AmqpMethod::BasicConsumeOk(tag))
             },
+            (AMQP_CLASS_BASIC, AMQP_METHOD_BASIC_CANCEL) => {
+                let tag = self.read_short_string()?;
+                let flags = self.read_u8()?;
+                Ok(AmqpMethod::BasicCancel(tag, flags))
+            },
             (AMQP_CLASS_BASIC, AMQP_METHOD_BASIC_CANCEL_OK) => {
                 let tag = self.read_short_string()?;
                 Ok(AmqpMethod::BasicCancelOk(tag))
@@ -234,6 +252,15 @@ impl<'buffer> AmqpFrameReader<'buffer> {
             (AMQP_CLASS_CONFIRM, AMQP_METHOD_CONFIRM_SELECT_OK) => {
                 Ok(AmqpMethod::ConfirmSelectOk())
             },
+            (AMQP_CLASS_TX, AMQP_METHOD_TX_SELECT_OK) => {
+                Ok(AmqpMethod::TxSelectOk())
+            },
+            (AMQP_CLASS_TX, AMQP_METHOD_TX_COMMIT_OK) => {
+                Ok(AmqpMethod::TxCommitOk())
+            },
+            (AMQP_CLASS_TX, AMQP_METHOD_TX_ROLLBACK_OK) => {
+                Ok(AmqpMethod::TxRollbackOk())
+            },
             (_, _) => Err(AmqpFrameError::InvalidClassMethod(class_id, method_id))
         }
     }
@@ -399,6 +426,20 @@ impl<'buffer> AmqpFrameReader<'buffer> {
         Ok(String::from_utf8(buffer)?)
     }
 
+    fn read_byte_array(&mut self) -> Result<Vec<u8>, AmqpFrameError> {
+        let length = self.read_u32()? as usize;
+
+        // length comes from the wire, so check it before allocating
+        if length > self.bytes_available() {
+            return Err(AmqpFrameError::BufferTooShort);
+        }
+
+        let mut buffer = vec![0; length];
+        self.read_bytes(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
     fn bytes_available(&self) -> usize {
         self.data.len()
     }
@@ -443,17 +484,17 @@ impl<'buffer> AmqpFrameReader<'buffer> {
             b't' => Ok(AmqpData::Bool(self.read_u8()? > 0)),
             b'b' => Ok(AmqpData::I8(self.read_i8()?)),
             b'B' => Ok(AmqpData::U8(self.read_u8()?)),
-            b'U' => Ok(AmqpData::I16(self.read_i16()?)),
+            // RabbitMQ uses 's' and 'l' for signed short and long long, 'U' and 'L' are from the spec
+            b's' | b'U' => Ok(AmqpData::I16(self.read_i16()?)),
             b'u' => Ok(AmqpData::U16(self.read_u16()?)),
             b'I' => Ok(AmqpData::I32(self.read_i32()?)),
             b'i' => Ok(AmqpData::U32(self.read_u32()?)),
-            b'L' => Ok(AmqpData::I64(self.read_i64()?)),
-            b'l' => Ok(AmqpData::U64(self.read_u64()?)),
+            b'l' | b'L' => Ok(AmqpData::I64(self.read_i64()?)),
             b'f' => Ok(AmqpData::Float(self.read_f32()?)),
             b'd' => Ok(AmqpData::Double(self.read_f64()?)),
-            b'D' => Ok(AmqpData::Decimal(self.read_u8()?, self.read_u32()?)),
-            b's' => Ok(AmqpData::ShortString(self.read_short_string()?)),
+            b'D' => Ok(AmqpData::Decimal(self.read_u8()?, self.read_i32()?)),
             b'S' => Ok(AmqpData::LongString(self.read_long_string()?)),
+            b'x' => Ok(AmqpData::ByteArray(self.read_byte_array()?)),
             b'T' => Ok(AmqpData::Timestamp(self.read_u64()?)),
             b'V' => Ok(AmqpData::None),
             b'F' => Ok(AmqpData::FieldTable(self.read_table()?)),
@@ -463,3 +504,32 @@ impl<'buffer> AmqpFrameReader<'buffer> {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rabbitmq_table_test() {
+        // table encoded the way RabbitMQ sends it in x-death headers
+        let data = [
+            0x00, 0x00, 0x00, 0x41,
+            0x05, b'c', b'o', b'u', b'n', b't', b'l', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x06, b'r', b'e', b'a', b's', b'o', b'n', b'S', 0x00, 0x00, 0x00, 0x07, b'e', b'x', b'p', b'i', b'r', b'e', b'd',
+            0x05, b'd', b'e', b'l', b't', b'a', b's', 0xff, 0xfe,
+            0x04, b'p', b'o', b'r', b't', b'u', 0x16, 0x28,
+            0x04, b't', b'i', b'm', b'e', b'T', 0x00, 0x00, 0x00, 0x00, 0x65, 0x53, 0xf1, 0x00,
+        ];
+
+        let mut reader = AmqpFrameReader::new(&data);
+        let table = reader.read_table().unwrap();
+
+        assert_eq!(table.len(), 5);
+        assert!(matches!(table.get("count"), Some(AmqpData::I64(1))));
+        assert!(matches!(table.get("reason"), Some(AmqpData::LongString(value)) if value == "expired"));
+        assert!(matches!(table.get("delta"), Some(AmqpData::I16(-2))));
+        assert!(matches!(table.get("port"), Some(AmqpData::U16(5672))));
+        assert!(matches!(table.get("time"), Some(AmqpData::Timestamp(1700000000))));
+        assert_eq!(reader.bytes_available(), 0);
+    }
+}
@@ -251,6 +251,26 @@ impl FrameWriter {
                 write_short_string(target, &name);
                 write_u8(target, *flags);
             },
+            AmqpMethod::ExchangeBind(destination, source, routing_key, flags, arguments) => {
+                write_u16(target, AMQP_CLASS_EXCHANGE);
+                write_u16(target, AMQP_METHOD_EXCHANGE_BIND);
+                write_u16(target, 0);      // deprecated
+                write_short_string(target, destination);
+                write_short_string(target, source);
+                write_short_string(target, routing_key);
+                write_u8(target, *flags);
+                write_table(target, arguments);
+            },
+            AmqpMethod::ExchangeUnbind(destination, source, routing_key, flags, arguments) => {
+                write_u16(target, AMQP_CLASS_EXCHANGE);
+                write_u16(target, AMQP_METHOD_EXCHANGE_UNBIND);
+                write_u16(target, 0);      // deprecated
+                write_short_string(target, destination);
+                write_short_string(target, source);
+                write_short_string(target, routing_key);
+                write_u8(target, *flags);
+                write_table(target, arguments);
+            },
             AmqpMethod::QueueDeclare(name, flags, arguments) => {
                 write_u16(target, AMQP_CLASS_QUEUE);
                 write_u16(target, AMQP_METHOD_QUEUE_DECLARE);
@@ -314,6 +334,11 @@ impl FrameWriter {
                 write_short_string(target, tag);
                 write_u8(target, *flags);
             },
+            AmqpMethod::BasicCancelOk(tag) => {
+                write_u16(target, AMQP_CLASS_BASIC);
+                write_u16(target, AMQP_METHOD_BASIC_CANCEL_OK);
+                write_short_string(target, tag);
+            },
             AmqpMethod::BasicPublish(exchange, routing_key, flags) => {
                 write_u16(target, AMQP_CLASS_BASIC);
                 write_u16(target, AMQP_METHOD_BASIC_PUBLISH);
@@ -357,6 +382,18 @@ impl FrameWriter {
                 write_u16(target, AMQP_METHOD_CONFIRM_SELECT);
                 write_u8(target, (*no_wait) as u8);
             },
+            AmqpMethod::TxSelect() => {
+                write_u16(target, AMQP_CLASS_TX);
+                write_u16(target, AMQP_METHOD_TX_SELECT);
+            },
+            AmqpMethod::TxCommit() => {
+                write_u16(target, AMQP_CLASS_TX);
+                write_u16(target, AMQP_METHOD_TX_COMMIT);
+            },
+            AmqpMethod::TxRollback() => {
+                write_u16(target, AMQP_CLASS_TX);
+                write_u16(target, AMQP_METHOD_TX_ROLLBACK);
+            },
             _ => panic!("Attempting to write unsupported frame type"),
         }
     }
@@ -457,7 +494,7 @@ fn write_value(buffer: &mut Vec<u8>, value: &AmqpData) {
             write_u8(buffer, *value as u8);
         },
         AmqpData::I8(value) => {
-            write_u8(buffer, b't');
+            write_u8(buffer, b'b');
             write_i8(buffer, *value);
         },
         AmqpData::U8(value) => {
@@ -465,7 +502,7 @@ fn write_value(buffer: &mut Vec<u8>, value: &AmqpData) {
             write_u8(buffer, *value);
         },
         AmqpData::I16(value) => {
-            write_u8(buffer, b'U');
+            write_u8(buffer, b's');
             write_i16(buffer, *value);
         },
         AmqpData::U16(value) => {
@@ -481,12 +518,13 @@ fn write_value(buffer: &mut Vec<u8>, value: &AmqpData) {
             write_u32(buffer, *value);
         },
         AmqpData::I64(value) => {
-            write_u8(buffer, b'L');
+            write_u8(buffer, b'l');
             write_i64(buffer, *value);
         },
+        // RabbitMQ has no unsigned long long, values above i64::MAX wrap around
         AmqpData::U64(value) => {
             write_u8(buffer, b'l');
-            write_u64(buffer, *value);
+            write_i64(buffer, *value as i64);
         },
         AmqpData::Float(value) => {
             write_u8(buffer, b'f');
@@ -499,16 +537,22 @@ fn write_value(buffer: &mut Vec<u8>, value: &AmqpData) {
         AmqpData::Decimal(scale, value) => {
             write_u8(buffer, b'D');
             write_u8(buffer, *scale);
-            write_u32(buffer, *value);
+            write_i32(buffer, *value);
         },
+        // short strings are not a field table type, brokers expect long string instead
         AmqpData::ShortString(value) => {
-            write_u8(buffer, b's');
-            write_short_string(buffer, value);
+            write_u8(buffer, b'S');
+            write_long_string(buffer, value);
         },
         AmqpData::LongString(value) => {
             write_u8(buffer, b'S');
             write_long_string(buffer, value);
         },
+        AmqpData::ByteArray(value) => {
+            write_u8(buffer, b'x');
+            write_u32(buffer, value.len() as u32);
+            write_bytes(buffer, value);
+        },
         AmqpData::Timestamp(value) => {
             write_u8(buffer, b'T');
             write_u64(buffer, *value);
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::string::FromUtf8Error;
 use fbs_library::system_error::SystemError;
 use fbs_resolver::ResolveAddressError;
@@ -14,9 +16,12 @@ mod channel;
 pub type AmqpConsumer = Box<dyn Fn(u64, bool, String, String, &mut AmqpMessage)>;
 pub type AmqpConfirmAckCallback = Box<dyn Fn(u64, bool)>;
 pub type AmqpConfirmNackCallback = Box<dyn Fn(u64, AmqpNackFlags)>;
+pub type AmqpDelivery = (u64, bool, String, String, AmqpMessage);
+pub type AmqpStateCallback = Box<dyn Fn(AmqpConnectionState)>;
+pub type AmqpConnectedHook = Box<dyn for<'a> Fn(&'a mut AmqpConnection) -> Pin<Box<dyn Future<Output = Result<(), AmqpConnectionError>> + 'a>>>;
 
-pub use connection::{AmqpConnection, AmqpConnectionParams};
-pub use channel::{AmqpChannel, AmqpChannelPublisher};
+pub use connection::{AmqpAuth, AmqpConnection, AmqpConnectionParams, AmqpConnectionState, AmqpReconnectPolicy, AmqpServerProperties};
+pub use channel::{AmqpChannel, AmqpChannelPublisher, AmqpChannelPipeline, AmqpConsumerStream};
 
 #[derive(Error, Debug, Clone)]
 pub enum AmqpConnectionError {
@@ -42,8 +47,22 @@ pub enum AmqpConnectionError {
     ProtocolError(&'static str),
     #[error("Channel closed by server - {1}")]
     ChannelClosedByServer(u16, String, u16, u16),
+    #[error("Channel closed")]
+    ChannelClosed,
+    #[error("Channel limit of {0} reached")]
+    TooManyChannels(u16),
     #[error("Invalid parameters")]
     InvalidParameters,
+    #[error("Authentication mechanism {0} not offered by server (available: {1})")]
+    AuthMechanismUnsupported(&'static str, String),
+    #[error("Message {0} nacked by server")]
+    MessageNacked(u64),
+    #[error("Channel paused by server flow control")]
+    ChannelPaused,
+    #[error("Connection blocked by server")]
+    ConnectionBlocked,
+    #[error("Consumer {0} cancelled by server")]
+    ConsumerCancelled(String),
 }
 
 #[derive(Error, Debug, Clone)]
@@ -71,12 +90,13 @@ pub enum AmqpData {
     I32(i32),
     U32(u32),
     I64(i64),
-    U64(u64),
+    U64(u64),                   // written as signed, RabbitMQ has no unsigned 64-bit type
     Float(f32),
     Double(f64),
-    Decimal(u8, u32),
-    ShortString(String),
+    Decimal(u8, i32),           // scale and unscaled value, -12.34 is (2, -1234)
+    ShortString(String),        // written as long string, tables never contain short strings
     LongString(String),
+    ByteArray(Vec<u8>),
     FieldArray(Vec<AmqpData>),
     Timestamp(u64),
     FieldTable(HashMap<String, AmqpData>),
@@ -401,6 +421,8 @@ impl AmqpPublishFlags {
         self
     }
 
+    // Removed from RabbitMQ 3.0 and most other brokers, they close the channel when it is set.
+    // Publishing with this flag fails with InvalidParameters before anything is sent.
     pub fn immediate(mut self, value: bool) -> Self {
         if value {
             self.flags |= 1 << 1;
@@ -410,6 +432,10 @@ impl AmqpPublishFlags {
 
         self
     }
+
+    fn has_immediate(self) -> bool {
+        (self.flags & (1 << 1)) != 0
+    }
 }
 
 impl Into<u8> for AmqpPublishFlags {
@@ -447,6 +473,14 @@ impl AmqpNackFlags {
 
         self
     }
+
+    pub fn has_multiple(self) -> bool {
+        (self.flags & (1 << 0)) != 0
+    }
+
+    pub fn has_requeue(self) -> bool {
+        (self.flags & (1 << 1)) != 0
+    }
 }
 
 impl Into<u8> for AmqpNackFlags {
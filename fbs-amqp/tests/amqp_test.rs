@@ -1,6 +1,8 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::rc::Rc;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use fbs_amqp::*;
 use fbs_runtime::{async_run, async_sleep};
@@ -236,3 +238,1115 @@ fn get_test() {
 
     assert!(result.is_ok());
 }
+
+#[test]
+fn consume_stream_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        channel.declare_queue("test-queue-4".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-4".to_string(), false).await?;
+
+        let mut stream = channel.consume_stream("test-queue-4".to_string(), String::new(), AmqpConsumeFlags::new().no_ack(true)).await?;
+
+        publisher.publish("".to_string(), "test-queue-4".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content-1".as_bytes())?;
+        publisher.publish("".to_string(), "test-queue-4".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content-2".as_bytes())?;
+
+        let (_, redelivered, exchange, routing_key, message) = stream.next().await?;
+        assert_eq!(redelivered, false);
+        assert_eq!(exchange, "");
+        assert_eq!(routing_key, "test-queue-4");
+        assert_eq!(message.content.as_slice(), "test-content-1".as_bytes());
+
+        let (_, _, _, _, message) = stream.next().await?;
+        assert_eq!(message.content.as_slice(), "test-content-2".as_bytes());
+
+        stream.cancel().await?;
+
+        channel.delete_queue("test-queue-4".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn consume_stream_end_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let mut control = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-20".to_string(), AmqpQueueFlags::new()).await?;
+
+        // deleting queue makes server cancel the consumer
+        let mut stream = channel.consume_stream("test-queue-20".to_string(), String::new(), AmqpConsumeFlags::new().no_ack(true)).await?;
+        let waiter = fbs_runtime::async_spawn(async move { stream.next().await.map(|_| ()) });
+
+        control.delete_queue("test-queue-20".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        assert!(matches!(waiter.await, Err(AmqpConnectionError::ConsumerCancelled(_))));
+
+        // closing channel ends streams waiting on it
+        channel.declare_queue("test-queue-20".to_string(), AmqpQueueFlags::new()).await?;
+        let mut stream = channel.consume_stream("test-queue-20".to_string(), String::new(), AmqpConsumeFlags::new().no_ack(true)).await?;
+        let waiter = fbs_runtime::async_spawn(async move {
+            let first = stream.next().await.map(|_| ());
+            let second = stream.next().await.map(|_| ());
+            (first, second)
+        });
+
+        channel.close().await?;
+        let (first, second) = waiter.await;
+        assert!(matches!(first, Err(AmqpConnectionError::ChannelClosed)));
+        assert!(matches!(second, Err(AmqpConnectionError::ChannelClosed)));
+
+        control.delete_queue("test-queue-20".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        control.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn pipeline_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        channel.declare_exchange("test-exchange-5".to_string(), "direct".to_string(), AmqpExchangeFlags::new()).await?;
+
+        // every awaited method is a separate round-trip, so it needs its own write
+        let writes = amqp.get_write_count();
+        for i in 0..10 {
+            channel.declare_queue(format!("test-queue-5-{}", i), AmqpQueueFlags::new()).await?;
+            channel.bind_queue(format!("test-queue-5-{}", i), "test-exchange-5".to_string(), format!("test-key-{}", i), false).await?;
+        }
+        let sequential = amqp.get_write_count() - writes;
+
+        let mut pipeline = channel.pipeline();
+        for i in 0..10 {
+            pipeline = pipeline.delete_queue(format!("test-queue-5-{}", i), AmqpDeleteQueueFlags::new());
+        }
+        pipeline.execute().await?;
+
+        let writes = amqp.get_write_count();
+        let mut pipeline = channel.pipeline();
+        for i in 0..10 {
+            pipeline = pipeline
+                .declare_queue(format!("test-queue-5-{}", i), AmqpQueueFlags::new())
+                .bind_queue(format!("test-queue-5-{}", i), "test-exchange-5".to_string(), format!("test-key-{}", i), false);
+        }
+        pipeline.execute().await?;
+        let pipelined = amqp.get_write_count() - writes;
+
+        println!("sequential writes: {}, pipelined writes: {}", sequential, pipelined);
+        assert!(sequential >= 20);
+        assert!(pipelined <= 2);
+
+        for i in 0..10 {
+            channel.declare_queue(format!("test-queue-5-{}", i), AmqpQueueFlags::new().passive(true)).await?;
+        }
+
+        let mut pipeline = channel.pipeline();
+        for i in 0..10 {
+            pipeline = pipeline.delete_queue(format!("test-queue-5-{}", i), AmqpDeleteQueueFlags::new());
+        }
+        pipeline.delete_exchange("test-exchange-5".to_string(), AmqpDeleteExchangeFlags::new()).execute().await?;
+
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn tx_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-6".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-6".to_string(), false).await?;
+        channel.tx_select().await?;
+
+        channel.publish("".to_string(), "test-queue-6".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        channel.tx_rollback().await?;
+
+        let result = channel.get("test-queue-6".to_string(), true).await?;
+        assert!(result.is_none());
+
+        channel.publish("".to_string(), "test-queue-6".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        channel.tx_commit().await?;
+
+        let result = channel.get("test-queue-6".to_string(), true).await?;
+        assert!(result.is_some());
+
+        channel.delete_queue("test-queue-6".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn reconnect_policy_test() {
+    async_run(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost:5673".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.reconnect = Some(AmqpReconnectPolicy { max_retries: 2, backoff: Duration::from_millis(100) });
+
+        let retries = Rc::new(Cell::new(0));
+        let retries_copy = retries.clone();
+        params.on_state_change = Some(Box::new(move |state| {
+            if let AmqpConnectionState::Reconnecting(_) = state {
+                retries_copy.set(retries_copy.get() + 1);
+            }
+        }));
+
+        let connection = AmqpConnection::connect(params).await;
+
+        assert!(connection.is_err());
+        assert_eq!(retries.get(), 2);
+    });
+}
+
+#[test]
+fn reconnect_hook_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let counter = Rc::new(Cell::new(0));
+        let counter_copy = counter.clone();
+        params.on_connected = Some(Box::new(move |connection| {
+            let counter = counter_copy.clone();
+            Box::pin(async move {
+                let mut channel = connection.channel_open().await?;
+                channel.declare_queue("test-queue-7".to_string(), AmqpQueueFlags::new().auto_delete(true)).await?;
+                channel.close().await?;
+
+                counter.set(counter.get() + 1);
+                Ok(())
+            })
+        }));
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        assert_eq!(counter.get(), 1);
+
+        amqp.reconnect().await?;
+        assert_eq!(counter.get(), 2);
+        assert!(amqp.is_alive());
+
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+// Forwards connections to local broker, break_connections() cuts them as if network failed
+struct BrokerProxy {
+    port: u16,
+    connections: Arc<Mutex<Vec<std::net::TcpStream>>>,
+}
+
+impl BrokerProxy {
+    fn start() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(Mutex::new(vec![]));
+        let registry = connections.clone();
+
+        std::thread::spawn(move || {
+            for client in listener.incoming() {
+                let client = client.unwrap();
+                let broker = std::net::TcpStream::connect("127.0.0.1:5672").unwrap();
+                registry.lock().unwrap().push(client.try_clone().unwrap());
+
+                let directions = [(client.try_clone().unwrap(), broker.try_clone().unwrap()), (broker, client)];
+                for (mut from, mut to) in directions {
+                    std::thread::spawn(move || {
+                        let _ = std::io::copy(&mut from, &mut to);
+                        let _ = to.shutdown(std::net::Shutdown::Both);
+                    });
+                }
+            }
+        });
+
+        Self { port, connections }
+    }
+
+    fn break_connections(&self) {
+        for connection in self.connections.lock().unwrap().drain(..) {
+            let _ = connection.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+#[test]
+fn auto_reconnect_test() {
+    let proxy = BrokerProxy::start();
+
+    let result = async_run::<Result<(), AmqpConnectionError>>(async move {
+        let mut params = AmqpConnectionParams::default();
+        params.address = format!("127.0.0.1:{}", proxy.port);
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.reconnect = Some(AmqpReconnectPolicy { max_retries: 5, backoff: Duration::from_millis(100) });
+
+        let states = Rc::new(RefCell::new(vec![]));
+        let states_copy = states.clone();
+        params.on_state_change = Some(Box::new(move |state| {
+            states_copy.borrow_mut().push(state);
+        }));
+
+        let counter = Rc::new(Cell::new(0));
+        let counter_copy = counter.clone();
+        params.on_connected = Some(Box::new(move |connection| {
+            let counter = counter_copy.clone();
+            Box::pin(async move {
+                let channel = connection.channel_open().await?;
+                channel.close().await?;
+
+                counter.set(counter.get() + 1);
+                Ok(())
+            })
+        }));
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        assert_eq!(counter.get(), 1);
+        states.borrow_mut().clear();
+
+        proxy.break_connections();
+
+        // connection comes back on its own, on_connected hook runs again
+        let started = Instant::now();
+        while counter.get() < 2 && started.elapsed() < Duration::new(5, 0) {
+            async_sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(counter.get(), 2);
+        assert!(amqp.is_alive());
+        assert!(matches!(states.borrow().first(), Some(AmqpConnectionState::Disconnected(_))));
+        assert!(matches!(states.borrow().last(), Some(AmqpConnectionState::Connected)));
+
+        let channel = amqp.channel_open().await?;
+        channel.close().await?;
+
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn publish_chunks_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        channel.declare_queue("test-queue-8".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-8".to_string(), false).await?;
+
+        let header = "header-".as_bytes().to_vec();
+        let payload = vec![b'x'; 200000];
+        let footer = "-footer".as_bytes().to_vec();
+
+        let mut expected = header.clone();
+        expected.extend_from_slice(&payload);
+        expected.extend_from_slice(&footer);
+
+        publisher.publish_chunks("".to_string(), "test-queue-8".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), vec![header, payload, footer])?;
+        async_sleep(Duration::new(1, 0)).await;
+
+        let result = channel.get("test-queue-8".to_string(), true).await?;
+        match result {
+            None => panic!(),
+            Some((_, _, _, _, _, message)) => {
+                assert_eq!(message.content, expected);
+            },
+        }
+
+        channel.delete_queue("test-queue-8".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn publish_after_close_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        channel.close().await?;
+
+        let result = publisher.publish("".to_string(), "test-queue-9".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes());
+        assert!(matches!(result, Err(AmqpConnectionError::ChannelClosed)));
+
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn external_auth_not_offered_test() {
+    async_run(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.vhost = "/".to_string();
+        params.auth = Some(AmqpAuth::External);
+
+        let connection = AmqpConnection::connect(params).await;
+        assert!(matches!(connection, Err(AmqpConnectionError::AuthMechanismUnsupported("EXTERNAL", _))));
+    });
+}
+
+#[test]
+fn plain_auth_test() {
+    async_run(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.vhost = "/".to_string();
+        params.auth = Some(AmqpAuth::Plain { username: "guest".to_string(), password: "guest".to_string() });
+
+        let connection = AmqpConnection::connect(params).await;
+        assert!(connection.is_ok());
+    });
+}
+
+#[test]
+fn server_properties_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let connection = AmqpConnection::connect(params).await?;
+        let properties = connection.server_properties();
+
+        assert_eq!(properties.version, (0, 9));
+        assert!(properties.has_mechanism("PLAIN"));
+        assert!(properties.locales.contains(&"en_US".to_string()));
+        assert!(properties.properties.contains_key("product"));
+        assert!(properties.has_capability("publisher_confirms"));
+        assert!(!properties.has_capability("no_such_capability"));
+
+        connection.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn nack_all_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        channel.declare_queue("test-queue-10".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-10".to_string(), false).await?;
+
+        let mut stream = channel.consume_stream("test-queue-10".to_string(), String::new(), AmqpConsumeFlags::new()).await?;
+
+        for i in 0..3 {
+            publisher.publish("".to_string(), "test-queue-10".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), format!("test-content-{}", i).as_bytes())?;
+        }
+
+        let mut last_tag = 0;
+        for _ in 0..3 {
+            let (delivery_tag, redelivered, _, _, _) = stream.next().await?;
+            assert_eq!(redelivered, false);
+            last_tag = delivery_tag;
+        }
+
+        // all three are requeued and delivered again
+        channel.nack_all(last_tag, true);
+
+        for i in 0..3 {
+            let (delivery_tag, redelivered, _, _, message) = stream.next().await?;
+            assert_eq!(redelivered, true);
+            assert_eq!(message.content.as_slice(), format!("test-content-{}", i).as_bytes());
+            last_tag = delivery_tag;
+        }
+
+        // this time messages are discarded
+        channel.nack_all(last_tag, false);
+        stream.cancel().await?;
+
+        let (_, messages, _) = channel.declare_queue("test-queue-10".to_string(), AmqpQueueFlags::new().passive(true)).await?;
+        assert_eq!(messages, 0);
+
+        channel.delete_queue("test-queue-10".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn channel_max_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+
+        // open channels until negotiated limit is hit, connection must remain usable
+        let mut channels = vec![];
+        let limit = loop {
+            match amqp.channel_open().await {
+                Ok(channel) => channels.push(channel),
+                Err(AmqpConnectionError::TooManyChannels(limit)) => break limit,
+                Err(error) => return Err(error),
+            }
+        };
+
+        assert_eq!(channels.len(), limit as usize);
+        assert!(amqp.is_alive());
+
+        let channel = channels.pop().unwrap();
+        channel.close().await?;
+
+        let channel = amqp.channel_open().await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn immediate_publish_rejected_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        let result = publisher.publish("".to_string(), "test-queue-nonexisting".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new().immediate(true), "test-content".as_bytes());
+        assert!(matches!(result, Err(AmqpConnectionError::InvalidParameters)));
+
+        // nothing was sent, so channel stays usable
+        publisher.publish("".to_string(), "test-queue-nonexisting".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+
+        channel.close().await?;
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn return_unbound_exchange_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        // exchange exists, but nothing is bound to it, so mandatory message can't be routed anywhere
+        channel.declare_exchange("test-exchange-return".to_string(), "direct".to_string(), AmqpExchangeFlags::new()).await?;
+
+        let counter = Rc::new(Cell::new(0));
+        let counter_copy = counter.clone();
+
+        channel.set_on_return(Some(Box::new(move |code, reason, exchange, routing_key, message: &mut AmqpMessage| {
+            assert_eq!(code, 312);
+            assert_eq!(reason, "NO_ROUTE");
+            assert_eq!(exchange, "test-exchange-return");
+            assert_eq!(routing_key, "unbound-key");
+            assert_eq!(message.content.as_slice(), "test-content".as_bytes());
+            counter_copy.set(counter_copy.get() + 1);
+        })));
+
+        publisher.publish("test-exchange-return".to_string(), "unbound-key".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new().mandatory(true), "test-content".as_bytes())?;
+        publisher.publish("test-exchange-return".to_string(), "unbound-key".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+
+        async_sleep(Duration::new(1, 0)).await;
+
+        channel.delete_exchange("test-exchange-return".to_string(), AmqpDeleteExchangeFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        // only mandatory message is returned, other one is silently dropped by broker
+        assert_eq!(counter.get(), 1);
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn declare_queue_with_args_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        let mut arguments = HashMap::new();
+        arguments.insert("x-message-ttl".to_string(), AmqpData::I32(60000));
+        arguments.insert("x-max-length".to_string(), AmqpData::I32(10));
+        arguments.insert("x-dead-letter-exchange".to_string(), AmqpData::LongString("".to_string()));
+
+        channel.declare_queue_with_args("test-queue-11".to_string(), AmqpQueueFlags::new().exclusive(true), arguments.clone()).await?;
+
+        // same arguments are accepted on redeclare
+        channel.declare_queue_with_args("test-queue-11".to_string(), AmqpQueueFlags::new().exclusive(true), arguments).await?;
+        let (name, messages, _) = channel.declare_queue("test-queue-11".to_string(), AmqpQueueFlags::new().passive(true)).await?;
+        assert_eq!(name, "test-queue-11");
+        assert_eq!(messages, 0);
+
+        // different ttl is a precondition failure, broker closes the channel
+        let mut arguments = HashMap::new();
+        arguments.insert("x-message-ttl".to_string(), AmqpData::I32(1000));
+        let result = channel.declare_queue_with_args("test-queue-11".to_string(), AmqpQueueFlags::new().exclusive(true), arguments).await;
+        assert!(matches!(result, Err(AmqpConnectionError::ChannelClosedByServer(406, _, _, _))));
+
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn exchange_bind_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        channel.declare_exchange("test-exchange-source".to_string(), "fanout".to_string(), AmqpExchangeFlags::new()).await?;
+        channel.declare_exchange("test-exchange-destination".to_string(), "direct".to_string(), AmqpExchangeFlags::new()).await?;
+        channel.declare_queue("test-queue-12".to_string(), AmqpQueueFlags::new().exclusive(true)).await?;
+        channel.bind_queue("test-queue-12".to_string(), "test-exchange-destination".to_string(), "key".to_string(), false).await?;
+        channel.bind_exchange("test-exchange-destination".to_string(), "test-exchange-source".to_string(), "".to_string(), false).await?;
+
+        channel.publish("test-exchange-source".to_string(), "key".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        async_sleep(Duration::new(0, 200_000_000)).await;
+
+        let result = channel.get("test-queue-12".to_string(), true).await?;
+        assert!(result.is_some_and(|(_, _, _, _, _, message)| message.content == "test-content".as_bytes()));
+
+        channel.unbind_exchange("test-exchange-destination".to_string(), "test-exchange-source".to_string(), "".to_string(), false).await?;
+
+        channel.publish("test-exchange-source".to_string(), "key".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        async_sleep(Duration::new(0, 200_000_000)).await;
+
+        let result = channel.get("test-queue-12".to_string(), true).await?;
+        assert!(result.is_none());
+
+        channel.delete_exchange("test-exchange-source".to_string(), AmqpDeleteExchangeFlags::new()).await?;
+        channel.delete_exchange("test-exchange-destination".to_string(), AmqpDeleteExchangeFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn blocked_callbacks_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let blocked = Rc::new(Cell::new(0));
+        let blocked_copy = blocked.clone();
+
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.on_blocked = Some(Box::new(move |_| blocked_copy.set(blocked_copy.get() + 1)));
+        params.on_unblocked = Some(Box::new(|| ()));
+
+        let connection = AmqpConnection::connect(params).await?;
+
+        // broker without resource alarm never blocks, but has to understand the capability we advertised
+        assert!(connection.server_properties().has_capability("connection.blocked"));
+        assert!(!connection.is_blocked());
+        assert!(connection.is_alive());
+        assert_eq!(blocked.get(), 0);
+
+        connection.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn client_properties_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.client_properties.insert("connection_name".to_string(), AmqpData::LongString("client-properties-test".to_string()));
+        params.client_properties.insert("product".to_string(), AmqpData::LongString("test-product".to_string()));
+
+        let connection = AmqpConnection::connect(params).await?;
+        assert!(connection.is_alive());
+        connection.close().await;
+
+        let defaults = AmqpConnectionParams::default_client_properties();
+        assert!(matches!(defaults.get("capabilities"), Some(AmqpData::FieldTable(capabilities)) if capabilities.contains_key("consumer_cancel_notify")));
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn authentication_failure_test() {
+    async_run(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "wrong-password".to_string();
+        params.vhost = "/".to_string();
+
+        let result = AmqpConnection::connect(params).await;
+        assert!(matches!(result, Err(AmqpConnectionError::ConnectionClosedByServer(403, _, _, _))));
+    });
+}
+
+#[test]
+fn server_cancel_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let mut other_channel = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-13".to_string(), AmqpQueueFlags::new()).await?;
+
+        let cancelled = Rc::new(Cell::new(0));
+        let cancelled_copy = cancelled.clone();
+        channel.set_on_cancel(Some(Box::new(move |tag| {
+            assert_eq!(tag, "server-cancel-consumer");
+            cancelled_copy.set(cancelled_copy.get() + 1);
+        })));
+
+        channel.consume("test-queue-13".to_string(), "server-cancel-consumer".to_string(), Box::new(|_, _, _, _, _| ()), AmqpConsumeFlags::new().no_ack(true)).await?;
+
+        // deleting queue from elsewhere makes broker cancel the consumer
+        other_channel.delete_queue("test-queue-13".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        async_sleep(Duration::new(0, 200_000_000)).await;
+
+        assert_eq!(cancelled.get(), 1);
+
+        // channel stays usable after server side cancel
+        channel.declare_queue("test-queue-13".to_string(), AmqpQueueFlags::new()).await?;
+        channel.delete_queue("test-queue-13".to_string(), AmqpDeleteQueueFlags::new()).await?;
+
+        channel.close().await?;
+        other_channel.close().await?;
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn read_buffer_pool_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        channel.declare_queue("test-queue-14".to_string(), AmqpQueueFlags::new().exclusive(true)).await?;
+        channel.delete_queue("test-queue-14".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+
+        // small frames keep reusing the single read buffer
+        let (allocations, deallocations, hits) = amqp.get_read_buffer_stats();
+        assert_eq!(allocations, 1);
+        assert_eq!(deallocations, 0);
+        assert!(hits > 0);
+
+        amqp.close().await;
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn max_frame_size_test() {
+    async_run(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.max_frame_size = Some(1024);
+
+        let result = AmqpConnection::connect(params).await;
+        assert!(matches!(result, Err(AmqpConnectionError::InvalidParameters)));
+    });
+
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+        params.max_frame_size = Some(4096);
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        // content is split into many minimal frames both ways
+        let content = vec![b'x'; 20000];
+        let counter = Rc::new(Cell::new(0));
+        let counter_copy = counter.clone();
+
+        channel.set_on_return(Some(Box::new(move |_, _, _, _, message: &mut AmqpMessage| {
+            assert_eq!(message.content, vec![b'x'; 20000]);
+            counter_copy.set(counter_copy.get() + 1);
+        })));
+
+        publisher.publish("".to_string(), "test-queue-nonexisting".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new().mandatory(true), &content)?;
+
+        async_sleep(Duration::new(1, 0)).await;
+        channel.close().await?;
+        amqp.close().await;
+
+        assert_eq!(counter.get(), 1);
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn publish_awaitable_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        // confirm mode is required
+        let result = channel.publish_awaitable("".to_string(), "test-queue-15".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test".as_bytes()).await;
+        assert!(matches!(result, Err(AmqpConnectionError::InvalidParameters)));
+
+        let acked = Rc::new(Cell::new(0));
+        let acked_clone = acked.clone();
+        channel.confirm_select((Box::new(move |_, _| acked_clone.set(acked_clone.get() + 1)), Box::new(|_, _| ())), false).await?;
+
+        channel.declare_queue("test-queue-15".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-15".to_string(), false).await?;
+
+        for i in 0..3 {
+            channel.publish_awaitable("".to_string(), "test-queue-15".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), format!("test-content-{}", i).as_bytes()).await?;
+        }
+
+        assert!(acked.get() > 0);
+
+        let (_, messages, _) = channel.declare_queue("test-queue-15".to_string(), AmqpQueueFlags::new().passive(true)).await?;
+        assert_eq!(messages, 3);
+
+        channel.delete_queue("test-queue-15".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn decimal_header_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-16".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-16".to_string(), false).await?;
+
+        let mut headers = HashMap::new();
+        headers.insert("price".to_string(), AmqpData::Decimal(2, -1234));
+
+        let mut properties = AmqpBasicProperties::default();
+        properties.headers = Some(headers);
+
+        channel.publish("".to_string(), "test-queue-16".to_string(), properties, AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        async_sleep(Duration::new(1, 0)).await;
+
+        let result = channel.get("test-queue-16".to_string(), true).await?;
+        match result {
+            None => panic!(),
+            Some((_, _, _, _, _, message)) => {
+                let headers = message.properties.headers.unwrap();
+                assert!(matches!(headers.get("price"), Some(AmqpData::Decimal(2, -1234))));
+            },
+        }
+
+        channel.delete_queue("test-queue-16".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn byte_array_header_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-17".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-17".to_string(), false).await?;
+
+        let mut nested = HashMap::new();
+        nested.insert("payload".to_string(), AmqpData::ByteArray(vec![0, 1, 2, 255]));
+
+        let mut headers = HashMap::new();
+        headers.insert("nested".to_string(), AmqpData::FieldTable(nested));
+
+        let mut properties = AmqpBasicProperties::default();
+        properties.headers = Some(headers);
+
+        channel.publish("".to_string(), "test-queue-17".to_string(), properties, AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        async_sleep(Duration::new(1, 0)).await;
+
+        let result = channel.get("test-queue-17".to_string(), true).await?;
+        match result {
+            None => panic!(),
+            Some((_, _, _, _, _, message)) => {
+                let headers = message.properties.headers.unwrap();
+                match headers.get("nested") {
+                    Some(AmqpData::FieldTable(nested)) => {
+                        assert!(matches!(nested.get("payload"), Some(AmqpData::ByteArray(value)) if value == &vec![0, 1, 2, 255]));
+                    },
+                    other => panic!("unexpected header value {:?}", other),
+                }
+            },
+        }
+
+        channel.delete_queue("test-queue-17".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn get_wait_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+        let publisher = channel.publisher();
+
+        channel.declare_queue("test-queue-18".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-18".to_string(), false).await?;
+
+        let start = Instant::now();
+        let result = channel.get_wait("test-queue-18".to_string(), true, Duration::from_millis(300)).await?;
+        assert!(result.is_none());
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        fbs_runtime::async_spawn(async move {
+            async_sleep(Duration::from_millis(200)).await;
+            publisher.publish("".to_string(), "test-queue-18".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes()).unwrap();
+        }).detach();
+
+        let result = channel.get_wait("test-queue-18".to_string(), true, Duration::new(5, 0)).await?;
+        match result {
+            None => panic!(),
+            Some((_, _, _, _, _, message)) => assert_eq!(message.content.as_slice(), "test-content".as_bytes()),
+        }
+
+        channel.delete_queue("test-queue-18".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn write_coalescing_test() {
+    let result = async_run::<Result<(), AmqpConnectionError>>(async {
+        let mut params = AmqpConnectionParams::default();
+        params.address = "localhost".to_string();
+        params.username = "guest".to_string();
+        params.password = "guest".to_string();
+        params.vhost = "/".to_string();
+
+        let mut amqp = AmqpConnection::connect(params).await?;
+        let mut channel = amqp.channel_open().await?;
+
+        channel.declare_queue("test-queue-19".to_string(), AmqpQueueFlags::new().durable(true)).await?;
+        channel.purge_queue("test-queue-19".to_string(), false).await?;
+
+        // each publish queues method, header and body frame
+        let writes_before = amqp.get_write_count();
+        for _ in 0..100 {
+            channel.publish("".to_string(), "test-queue-19".to_string(), AmqpBasicProperties::default(), AmqpPublishFlags::new(), "test-content".as_bytes())?;
+        }
+
+        async_sleep(Duration::new(1, 0)).await;
+        let writes = amqp.get_write_count() - writes_before;
+        println!("writes for 300 frames: {}", writes);
+        assert!(writes <= 10);
+
+        channel.delete_queue("test-queue-19".to_string(), AmqpDeleteQueueFlags::new()).await?;
+        channel.close().await?;
+        amqp.close().await;
+
+        Ok(())
+    });
+
+    assert!(result.is_ok());
+}
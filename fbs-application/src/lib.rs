@@ -1,6 +1,7 @@
 use std::marker::PhantomData;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell, RefMut};
 use std::rc::Rc;
+use std::time::Duration;
 
 use fbs_library::update_cell;
 use fbs_runtime::*;
@@ -10,8 +11,53 @@ use fbs_executor::TaskHandle;
 use fbs_library::sigset::*;
 use fbs_library::signalfd::*;
 
+// Number of consecutive unhealthy pings before resource is reported as unhealthy
+const RESOURCE_FAILURE_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceHealth {
+    Healthy,
+    Unhealthy,
+    Dead,       // permanent failure, resource is not pinged anymore
+}
+
 pub trait ApplicationResource {
-    fn ping(&mut self) -> bool;
+    fn id(&self) -> &str;
+
+    fn ping(&mut self) -> ResourceHealth;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ResourceState {
+    reported: Option<ResourceHealth>,
+    failures: u32,
+}
+
+impl ResourceState {
+    // Returns new health when it should be reported
+    fn update(&mut self, health: ResourceHealth) -> Option<ResourceHealth> {
+        match health {
+            ResourceHealth::Healthy => self.failures = 0,
+            ResourceHealth::Unhealthy => {
+                self.failures += 1;
+                if self.failures < RESOURCE_FAILURE_THRESHOLD {
+                    return None;
+                }
+            },
+            ResourceHealth::Dead => (),
+        }
+
+        if self.reported == Some(health) {
+            return None;
+        }
+
+        self.reported = Some(health);
+        Some(health)
+    }
+
+    fn is_dead(&self) -> bool {
+        self.reported == Some(ResourceHealth::Dead)
+    }
 }
 
 pub enum EventProcessing {
@@ -25,11 +71,31 @@ pub trait ApplicationLogic : Sized + 'static {
 
     fn create(notifier: ApplicationStateNotifier) -> Result<Self, Self::Error>;
 
+    // Every signal from signal_mask is delivered here as SystemEvent::ApplicationSignal. SIGQUIT
+    // always stops the application, for other signals send SystemEvent::ApplicationQuit via notifier.
     fn handle_system_event(&mut self, event: SystemEvent);
 
-    async fn handle_app_event(&mut self, notifier: ApplicationStateNotifier, event: Self::Event) -> EventProcessing;
+    // Handlers of different events run concurrently, so state they change needs interior mutability.
+    // System events and resource pings wait until handlers in flight finish.
+    async fn handle_app_event(&self, notifier: ApplicationStateNotifier, event: Self::Event) -> EventProcessing;
 
     fn get_resources(&mut self) -> Vec<&mut dyn ApplicationResource>;
+
+    // Signals blocked for the process and read from signalfd, SIGQUIT should stay in the mask
+    fn signal_mask(&self) -> SignalSet {
+        let mut mask = SignalSet::empty();
+        mask.add(Signal::SIGINT);
+        mask.add(Signal::SIGQUIT);
+        mask.add(Signal::SIGHUP);
+        mask.add(Signal::SIGCHLD);
+
+        mask
+    }
+
+    // How long in-flight handle_app_event tasks are given to finish on quit, before being cancelled
+    fn shutdown_timeout(&self) -> Duration {
+        Duration::new(5, 0)
+    }
 }
 
 pub struct Application<T: ApplicationLogic> {
@@ -45,29 +111,28 @@ impl<T: ApplicationLogic> Application<T> {
         let state = Rc::new(ApplicationState::<T::Event>::new());
         let state_int = state.clone();
 
-        let mut app = Rc::new(T::create(state.create_notifier())?);
+        let app = Rc::new(RefCell::new(T::create(state.create_notifier())?));
 
         let notifier = state.create_notifier();
         notifier.send_system_event(SystemEvent::ApplicationInit);
 
+        let shutdown_timeout = app.borrow().shutdown_timeout();
+        let mask = app.borrow().signal_mask();
+
         async_run(async move {
             state.signal_proc.set(async_spawn(async move {
-                let mut mask = SignalSet::empty();
-                mask.add(Signal::SIGINT);
-                mask.add(Signal::SIGQUIT);
-                mask.add(Signal::SIGHUP);
-                mask.add(Signal::SIGCHLD);
-
                 set_process_signal_mask(SignalMask::Block, mask).unwrap();
 
                 let sigfd = SignalFd::new(mask, SignalFdFlags::new().close_on_exec(true).flags()).unwrap();
 
                 loop {
-                    let received = async_read_struct::<SignalFdInfo>(&sigfd, None).await;
+                    let received = async_read_signals(&sigfd).await;
                     match received {
                         Err(error) => panic!("Got error while reading from signalfd {}", error),
-                        Ok(info) => {
-                            notifier.send_system_event(SystemEvent::ApplicationSignal(info.signal()));
+                        Ok(infos) => {
+                            for info in infos {
+                                notifier.send_system_event(SystemEvent::ApplicationSignal(info.signal()));
+                            }
                         }
                     }
                 }
@@ -77,17 +142,13 @@ impl<T: ApplicationLogic> Application<T> {
                 loop {
                     state_int.has_event.wait().await;
 
-                    let mut resources = app.get_resources();
-                    resources.iter_mut().for_each(|r| {
-                        eprintln!("ping");
-                        r.ping();
-                    });
+                    let mut app_mut = state_int.borrow_app_mut(&app).await;
+                    state_int.ping_resources(app_mut.get_resources());
 
-                    drop(resources);
+                    if let Some(event) = state_int.internal_queue_rx.try_receive() {
+                        let running = state_int.handle_system_event(&event);
+                        app_mut.handle_system_event(event);
 
-                    if !state_int.internal_queue_rx.is_empty() {
-                        let event = state_int.internal_queue_rx.receive().await;
-                        let running = state_int.handle_system_event(event);
                         if !running {
                             break;
                         }
@@ -95,19 +156,31 @@ impl<T: ApplicationLogic> Application<T> {
                         continue;
                     }
 
-                    if !state_int.app_queue_rx.is_empty() {
-                        let event = state_int.app_queue_rx.receive().await;
+                    drop(app_mut);
 
+                    if let Some(event) = state_int.app_queue_rx.try_receive() {
                         let state = state_int.clone();
-                        async_spawn(async move {
-                            let a = app.handle_app_event(state.create_notifier(), event).await;
-
+                        let app = app.clone();
+                        let task = async_spawn(async move {
+                            {
+                                let app = app.borrow();
+                                let _ = app.handle_app_event(state.create_notifier(), event).await;
+                            }
+
+                            state.task_finished.signal();
                         });
 
+                        let mut tasks = state_int.tasks.borrow_mut();
+                        tasks.retain(|task| !task.is_completed());
+                        tasks.push(task);
+
                         continue;
                     }
                 }
 
+                state_int.accepting.set(false);
+                state_int.drain_tasks(shutdown_timeout).await;
+
                 update_cell(&state_int.signal_proc, |signal| { signal.cancel(); TaskHandle::default() });
             }));
         });
@@ -121,7 +194,8 @@ pub enum SystemEvent {
     ApplicationInit,
     ApplicationQuit,
     ApplicationSignal(Signal),
-    ResourceStateChanged,
+    ResourceActivity,                               // resource wants to be pinged
+    ResourceStateChanged(usize, ResourceHealth),    // index in get_resources, new health
 }
 
 #[derive(Clone)]
@@ -145,6 +219,10 @@ pub struct ApplicationState<T> {
     has_event: AsyncSignal,
     signal_proc: Cell<TaskHandle<()>>,
     main_proc: Cell<TaskHandle<()>>,
+    tasks: RefCell<Vec<TaskHandle<()>>>,
+    task_finished: AsyncSignal,
+    accepting: Cell<bool>,
+    resources: RefCell<Vec<ResourceState>>,
 }
 
 impl<T> ApplicationState<T> {
@@ -160,6 +238,10 @@ impl<T> ApplicationState<T> {
             has_event: AsyncSignal::new(),
             signal_proc: Cell::new(TaskHandle::default()),
             main_proc: Cell::new(TaskHandle::default()),
+            tasks: RefCell::new(Vec::new()),
+            task_finished: AsyncSignal::new(),
+            accepting: Cell::new(true),
+            resources: RefCell::new(Vec::new()),
         }
     }
 
@@ -170,7 +252,7 @@ impl<T> ApplicationState<T> {
         }
     }
 
-    fn handle_system_event(&self, event: SystemEvent) -> bool {
+    fn handle_system_event(&self, event: &SystemEvent) -> bool {
         eprintln!("System event - {:?}", event);
         match event {
             SystemEvent::ApplicationQuit => return false,
@@ -181,12 +263,84 @@ impl<T> ApplicationState<T> {
         true
     }
 
+    // Resources are pinged in order, failure of one doesn't affect pinging of the others
+    fn ping_resources(&self, mut resources: Vec<&mut dyn ApplicationResource>) {
+        let mut states = self.resources.borrow_mut();
+        states.resize(resources.len(), ResourceState::default());
+
+        resources.iter_mut().enumerate().for_each(|(index, resource)| {
+            if states[index].is_dead() {
+                return;
+            }
+
+            let health = resource.ping();
+            if let Some(health) = states[index].update(health) {
+                eprintln!("Resource {} ({}) is now {:?}", index, resource.id(), health);
+                self.internal_queue_tx.send(SystemEvent::ResourceStateChanged(index, health));
+                self.has_event.signal();
+            }
+        });
+    }
+
+    // App event handlers in flight hold shared borrow of the app, so mutable one waits for them
+    async fn borrow_app_mut<'a, A>(&self, app: &'a RefCell<A>) -> RefMut<'a, A> {
+        loop {
+            match app.try_borrow_mut() {
+                Ok(app) => return app,
+                Err(_) => self.task_finished.wait().await,
+            }
+        }
+    }
+
+    async fn drain_tasks(&self, timeout: Duration) {
+        let timed_out = Rc::new(Cell::new(false));
+        let timed_out_cpy = timed_out.clone();
+        let task_finished = self.task_finished.clone();
+
+        let timer = async_spawn(async move {
+            async_sleep(timeout).await;
+            timed_out_cpy.set(true);
+            task_finished.signal();
+        });
+
+        loop {
+            // borrow must not be held across suspension point
+            let pending = {
+                let mut tasks = self.tasks.borrow_mut();
+                tasks.retain(|task| !task.is_completed());
+                tasks.len()
+            };
+
+            if pending == 0 || timed_out.get() {
+                break;
+            }
+
+            self.task_finished.wait().await;
+        }
+
+        timer.cancel();
+
+        let tasks = std::mem::take(&mut *self.tasks.borrow_mut());
+        if !tasks.is_empty() {
+            eprintln!("Cancelling {} event handlers still running after {:?}", tasks.len(), timeout);
+        }
+
+        tasks.into_iter().for_each(|task| {
+            task.cancel();
+        });
+    }
+
     fn send_system_event(&mut self, event: SystemEvent) {
         self.internal_queue_tx.send(event);
         self.has_event.signal();
     }
 
     pub fn send_app_event(&mut self, event: T) {
+        // application is shutting down
+        if !self.accepting.get() {
+            return;
+        }
+
         self.app_queue_tx.send(event);
         self.has_event.signal();
     }
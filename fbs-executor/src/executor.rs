@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::rc::Rc;
 use std::task::{Context, Poll};
@@ -16,6 +17,7 @@ impl Debug for Executor {
             .field("ready", &self.ready.len())
             .field("waiting", &self.waiting.size())
             .field("channel", &self.channel.len())
+            .field("tasks", &self.tasks.borrow().len())
             .finish()
     }
 }
@@ -28,12 +30,14 @@ impl Executor {
             ready: VecDeque::with_capacity(10),
             waiting: IndexedList::new(),
             channel: rx,
+            tasks: Rc::new(RefCell::new(IndexedList::new())),
         }
     }
 
     pub fn get_frontend(&self) -> ExecutorFrontend {
         ExecutorFrontend {
-            channel: self.channel.tx()
+            channel: self.channel.tx(),
+            tasks: self.tasks.clone(),
         }
     }
 
@@ -42,6 +46,16 @@ impl Executor {
         }
     }
 
+    // Returns number of tasks actually polled
+    pub fn run_budget(&mut self, budget: usize) -> usize {
+        let mut executed = 0;
+        while executed < budget && self.run_once() {
+            executed += 1;
+        }
+
+        executed
+    }
+
     pub fn run_once(&mut self) -> bool {
         self.process_queue();
 
@@ -79,9 +93,15 @@ impl Executor {
         }
     }
 
+    fn unregister_task(&self, task: &TaskData) {
+        if let Some(index) = task.registry_index.take() {
+            self.tasks.borrow_mut().remove(index);
+        }
+    }
+
     fn process_task(&mut self, task: Rc<TaskData>) {
         match (task.is_executable.get(), task.future.take()) {
-            (false, _) => (),
+            (false, _) => self.unregister_task(&task),
             (true, None) => (),
             (true, Some(mut future)) => {
                 let waker = super::task_data::task_into_waker(Rc::into_raw(task.clone()));
@@ -97,6 +117,7 @@ impl Executor {
                     },
                     Poll::Ready(()) => {
                         task.is_executable.set(false);
+                        self.unregister_task(&task);
                         task.waiters.take().into_iter().for_each(|w| w.wake());
                     },
                 }
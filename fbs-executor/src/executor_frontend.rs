@@ -9,9 +9,32 @@ use super::ExecutorCmd;
 use super::TaskData;
 use super::TaskHandle;
 use super::ExecutorFrontend;
+use super::{TaskInfo, TaskState};
 
 impl ExecutorFrontend {
     pub fn spawn<T: 'static>(&self, future: impl Future<Output = T> + 'static) -> TaskHandle<T> {
+        self.spawn_task(None, future)
+    }
+
+    // Name is only used for debugging, see list_tasks
+    pub fn spawn_named<T: 'static>(&self, name: &str, future: impl Future<Output = T> + 'static) -> TaskHandle<T> {
+        self.spawn_task(Some(name.to_string()), future)
+    }
+
+    // Snapshot of all tasks that haven't finished yet
+    pub fn list_tasks(&self) -> Vec<TaskInfo> {
+        self.tasks.borrow().iter_indexed().filter_map(|(_, task)| task.upgrade()).map(|task| {
+            let state = match (task.is_executable.get(), task.wait_index.get()) {
+                (false, _) => TaskState::Completed,
+                (true, Some(_)) => TaskState::Waiting,
+                (true, None) => TaskState::Ready,
+            };
+
+            TaskInfo { name: task.name.clone(), state }
+        }).collect()
+    }
+
+    fn spawn_task<T: 'static>(&self, name: Option<String>, future: impl Future<Output = T> + 'static) -> TaskHandle<T> {
         let result_ptr = Rc::new(Cell::new(Option::<T>::None));
         let result_ptr_inner = result_ptr.clone();
         let future = Box::pin(async move {
@@ -24,8 +47,13 @@ impl ExecutorFrontend {
             wait_index: Cell::new(None),
             waiters: RefCell::new(Vec::with_capacity(1)),
             is_executable: Cell::new(true),
+            name,
+            registry_index: Cell::new(None),
         });
 
+        let index = self.tasks.borrow_mut().insert(Rc::downgrade(&task));
+        task.registry_index.set(Some(index));
+
         self.channel.send(ExecutorCmd::Schedule(task.clone()));
         TaskHandle {
             task: Some(task),
@@ -66,7 +94,10 @@ impl Future for Yield {
 
 #[cfg(test)]
 mod tests {
-    use crate::Executor;
+    use std::rc::Rc;
+    use std::cell::Cell;
+
+    use crate::{Executor, TaskInfo, TaskState};
 
     #[test]
     fn basic_async_test() {
@@ -104,4 +135,121 @@ mod tests {
         assert_eq!(handle2.is_completed(), true);
         assert_eq!(handle2.result(), Some(124));
     }
+
+    #[test]
+    fn list_tasks_test() {
+        let mut executor = Executor::new();
+        let frontend = executor.get_frontend();
+
+        let first = frontend.spawn_named("first", async {});
+        let second = frontend.spawn_named("second", first);
+        let _third = frontend.spawn(async {});
+
+        let names: Vec<_> = frontend.list_tasks().into_iter().map(|task| task.name).collect();
+        assert_eq!(names, vec![Some("first".to_string()), Some("second".to_string()), None]);
+
+        executor.run_all();
+        assert!(second.is_completed());
+        assert_eq!(frontend.list_tasks(), vec![]);
+
+        let waiting = frontend.spawn_named("waiting", std::future::pending::<()>());
+        executor.run_all();
+        assert_eq!(frontend.list_tasks(), vec![TaskInfo { name: Some("waiting".to_string()), state: TaskState::Waiting }]);
+
+        waiting.cancel();
+        assert_eq!(frontend.list_tasks()[0].state, TaskState::Completed);
+
+        executor.run_all();
+        assert_eq!(frontend.list_tasks(), vec![]);
+    }
+
+    #[test]
+    fn result_test() {
+        let mut executor = Executor::new();
+        let frontend = executor.get_frontend();
+
+        let handle = frontend.spawn(async { 5 });
+        assert_eq!(handle.result(), None);
+
+        executor.run_all();
+        assert_eq!(handle.result(), Some(5));
+        assert_eq!(handle.result(), None);
+        assert!(handle.is_completed());
+    }
+
+    #[test]
+    fn detach_test() {
+        let mut executor = Executor::new();
+        let frontend = executor.get_frontend();
+        let completed = Rc::new(Cell::new(false));
+        let completed_clone = completed.clone();
+
+        let yielder = executor.get_frontend();
+        frontend.spawn(async move {
+            yielder.yield_execution().await;
+            completed_clone.set(true);
+        }).detach();
+
+        executor.run_all();
+        assert!(completed.get());
+        assert_eq!(frontend.list_tasks(), vec![]);
+    }
+
+    struct DropFlag(Rc<Cell<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn drop_handle_test() {
+        let mut executor = Executor::new();
+        let frontend = executor.get_frontend();
+
+        let dropped = Rc::new(Cell::new(false));
+        let flag = DropFlag(dropped.clone());
+        let handle = frontend.spawn(async move {
+            let _flag = flag;
+            std::future::pending::<()>().await;
+        });
+
+        executor.run_all();
+        assert!(!dropped.get());
+        assert_eq!(frontend.list_tasks()[0].state, TaskState::Waiting);
+
+        // future is dropped right away, task is released on next executor run
+        drop(handle);
+        assert!(dropped.get());
+        executor.run_all();
+        assert_eq!(frontend.list_tasks(), vec![]);
+
+        let dropped = Rc::new(Cell::new(false));
+        let flag = DropFlag(dropped.clone());
+        frontend.spawn(async move {
+            let _flag = flag;
+            std::future::pending::<()>().await;
+        }).detach();
+
+        executor.run_all();
+        assert!(!dropped.get());
+        assert_eq!(frontend.list_tasks()[0].state, TaskState::Waiting);
+    }
+
+    #[test]
+    fn run_budget_test() {
+        let mut executor = Executor::new();
+        let frontend = executor.get_frontend();
+
+        let handles: Vec<_> = (0..5).map(|i| frontend.spawn(async move { i })).collect();
+
+        assert_eq!(executor.run_budget(3), 3);
+        assert!(executor.has_ready_tasks());
+        assert_eq!(handles.iter().filter(|handle| handle.is_completed()).count(), 3);
+
+        assert_eq!(executor.run_budget(3), 2);
+        assert!(!executor.has_ready_tasks());
+        assert!(handles.iter().all(|handle| handle.is_completed()));
+    }
 }
@@ -1,6 +1,6 @@
 use std::task::Waker;
 use std::cell::{RefCell, Cell};
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::pin::Pin;
 use std::collections::VecDeque;
 use std::future::Future;
@@ -20,14 +20,19 @@ enum ExecutorCmd {
     Wake(Waker),
 }
 
+// Tasks that haven't finished yet, shared with frontends so it can be inspected from inside a task
+type TaskRegistry = Rc<RefCell<IndexedList<Weak<TaskData>>>>;
+
 pub struct Executor {
     ready: VecDeque<Rc<TaskData>>,
     waiting: IndexedList<Rc<TaskData>>,
     channel: ChannelRx<ExecutorCmd>,
+    tasks: TaskRegistry,
 }
 
 pub struct ExecutorFrontend {
     channel: ChannelTx<ExecutorCmd>,
+    tasks: TaskRegistry,
 }
 
 pub struct TaskData {
@@ -36,8 +41,32 @@ pub struct TaskData {
     wait_index: Cell<Option<usize>>,
     waiters: RefCell<Vec<Waker>>,
     is_executable: Cell<bool>,
+    name: Option<String>,
+    registry_index: Cell<Option<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Ready,          // scheduled or currently running
+    Waiting,        // pending on a waker
+    Completed,      // cancelled, executor hasn't released it yet
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskInfo {
+    pub name: Option<String>,
+    pub state: TaskState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskCancelResult {
+    AlreadyCompleted,
+    Cancelled,     // future was dropped, along with any ops it was waiting on
 }
 
+// Owning handle of spawned task. Dropping it cancels the task and drops its future, which in turn
+// cancels all in-flight ops owned by it - so handles are kept around for as long as the task should
+// run. Detached (and default constructed) handles don't affect the task when dropped.
 pub struct TaskHandle<T> {
     task: Option<Rc<TaskData>>,
     result: Rc<Cell<Option<T>>>,
@@ -4,7 +4,7 @@ use std::task::{Context, Poll};
 use std::rc::Rc;
 use std::cell::Cell;
 
-use super::TaskHandle;
+use super::{TaskHandle, TaskCancelResult};
 
 impl<T> Future for TaskHandle<T> {
     type Output = T;
@@ -13,6 +13,7 @@ impl<T> Future for TaskHandle<T> {
 
         match (&self.task, maybe_value) {
             (Some(_), Some(value)) => Poll::Ready(value),
+            (Some(task), None) if !task.is_executable.get() => panic!("Polling task handle after its result was taken"),
             (Some(task), None) => {
                 task.waiters.borrow_mut().push(cx.waker().clone());
                 return Poll::Pending;
@@ -31,7 +32,7 @@ impl<T> Default for TaskHandle<T> {
 impl<T> Drop for TaskHandle<T> {
     fn drop(&mut self) {
         if !self.detached {
-            self.cancel_by_ref()
+            self.cancel_by_ref();
         }
     }
 }
@@ -44,35 +45,36 @@ impl<T> TaskHandle<T> {
         }
     }
 
+    // Moves result out of completed task without consuming the handle, so it may be checked
+    // periodically. None before completion and once result has been taken - await the handle
+    // to wait for pending task instead.
     pub fn result(&self) -> Option<T> {
         self.result.take()
     }
 
+    // Task keeps running until completion without the handle, its result is discarded
     pub fn detach(mut self) {
         self.detached = true
     }
 
-    pub fn cancel(self) {
-        match &self.task {
-            Some(task) => {
-                task.is_executable.set(false);
-                task.future.set(None);
-                task.channel.send(crate::ExecutorCmd::Schedule(task.clone()));
-            },
-            None => (),
-        }
+    pub fn cancel(mut self) -> TaskCancelResult {
+        self.cancel_by_ref()
     }
 
-    pub fn cancel_by_ref(&mut self) {
-        match &self.task {
-            Some(task) => {
+    pub fn cancel_by_ref(&mut self) -> TaskCancelResult {
+        let result = match &self.task {
+            Some(task) if task.is_executable.get() => {
                 task.is_executable.set(false);
+
+                // dropping future here auto-cancels all in-flight ops it owns
                 task.future.set(None);
                 task.channel.send(crate::ExecutorCmd::Schedule(task.clone()));
+                TaskCancelResult::Cancelled
             },
-            None => (),
-        }
+            _ => TaskCancelResult::AlreadyCompleted,
+        };
 
         self.task = None;
+        result
     }
 }
@@ -13,7 +13,7 @@ use std::fmt::{Debug, Formatter};
 
 use fbs_runtime::async_spawn;
 use fbs_runtime::async_utils::{async_channel_create, AsyncChannelRx, AsyncChannelTx, AsyncSignal};
-use fbs_runtime::{async_sleep_with_result, async_sleep_update, async_cancel, async_poll, async_poll_update};
+use fbs_runtime::{async_sleep_with_result, async_sleep_update, async_cancel, async_poll, async_poll_update, CancellationScope};
 
 use fbs_executor::TaskHandle;
 use fbs_library::poll::PollMask;
@@ -23,6 +23,8 @@ use libcurl_sys::*;
 
 const_cstr! {
     HTTP_METHOD_DELETE = "DELETE";
+    HTTP_REDIRECT_HTTPS_ONLY = "https";
+    HTTP_REDIRECT_ANY = "http,https";
 }
 
 #[derive(Error, Debug)]
@@ -37,6 +39,10 @@ pub enum HttpClientError {
     InputNullError(#[from] NulError),
     #[error("Request error")]
     RequestError(String),
+    #[error("Request cancelled")]
+    Cancelled,
+    #[error("Too many redirects")]
+    TooManyRedirects,
 }
 
 enum EasyOption<'opt> {
@@ -54,6 +60,14 @@ enum EasyOption<'opt> {
     Url(&'opt CStr),    // from curl doc: "The application does not have to keep the string around after setting this option."
     Headers(*mut curl_slist),
     FollowLocation(bool),
+    NoBody(bool),
+    Userpwd(&'opt CStr),    // copied by curl as well
+    HttpAuth(libc::c_ulong),
+    SslVerifyPeer(libc::c_long),
+    SslVerifyHost(libc::c_long),
+    CaInfo(&'opt CStr),     // copied by curl
+    MaxRedirs(libc::c_long),
+    RedirProtocols(&'opt CStr),
 }
 
 enum MultiOption {
@@ -69,16 +83,69 @@ pub enum HttpMethod {
     Post,
     Put,
     Delete,
+    Head,
+}
+
+#[derive(Clone)]
+pub enum HttpAuth {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+// Secrets are redacted, requests end up in logs
+impl Debug for HttpAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpAuth::Basic { username, .. } => f.debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"***")
+                .finish(),
+            HttpAuth::Bearer(_) => f.debug_tuple("Bearer").field(&"***").finish(),
+        }
+    }
+}
+
+// Default is full verification against system CA store
+#[derive(Debug, Clone)]
+pub struct HttpTlsConfig {
+    pub verify_peer: bool,
+    pub verify_host: bool,
+    pub ca_file: Option<String>,
+}
+
+impl Default for HttpTlsConfig {
+    fn default() -> Self {
+        Self { verify_peer: true, verify_host: true, ca_file: None }
+    }
+}
+
+impl HttpTlsConfig {
+    pub fn insecure() -> Self {
+        Self { verify_peer: false, verify_host: false, ca_file: None }
+    }
+
+    // Values for CURLOPT_SSL_VERIFYPEER and CURLOPT_SSL_VERIFYHOST. For host 2 is the only value
+    // that actually checks the name, 1 is treated the same way by curl
+    fn curl_verify_values(&self) -> (libc::c_long, libc::c_long) {
+        (self.verify_peer as libc::c_long, if self.verify_host { 2 } else { 0 })
+    }
 }
 
 pub struct HttpRequest {
     pub method: HttpMethod,
     pub url: String,
     pub headers: HashMap<String, String>,
+    pub auth: Option<HttpAuth>,
+    pub tls: HttpTlsConfig,
     pub follow_redirects: bool,
+    pub max_redirects: Option<i64>,
+    // Only matters for https urls, without it redirect to plain http fails
+    pub allow_redirect_to_http: bool,
     pub content: Vec<u8>,
     pub content_stream: Option<Box<dyn Fn(&mut [u8]) -> usize>>,
-    pub response_stream: Option<Box<dyn Fn(&[u8]) -> usize>>,
+    // Receives body chunks as they arrive, response_body stays empty then. Returning less
+    // than chunk length aborts the transfer.
+    pub response_stream: Option<Box<dyn FnMut(&[u8]) -> usize>>,
 }
 
 impl Debug for HttpRequest {
@@ -87,7 +154,11 @@ impl Debug for HttpRequest {
         .field("method", &self.method)
         .field("url", &self.url)
         .field("headers", &self.headers)
+        .field("auth", &self.auth)
+        .field("tls", &self.tls)
         .field("follow_redirects", &self.follow_redirects)
+        .field("max_redirects", &self.max_redirects)
+        .field("allow_redirect_to_http", &self.allow_redirect_to_http)
         .field("content", &self.content)
         .field("content_stream", &self.content_stream.is_some())
         .field("response_stream", &self.response_stream.is_some())
@@ -102,9 +173,19 @@ pub struct HttpResponseData {
     pub response_body: Vec<u8>,
 }
 
+impl HttpResponseData {
+    pub fn http_code(&self) -> i32 {
+        self.http_code
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+}
+
 impl HttpRequest {
     pub fn new() -> Self {
-        Self { method: HttpMethod::Get, url: String::new(), headers: HashMap::new(), follow_redirects: false, content: Vec::new(), content_stream: None, response_stream: None }
+        Self { method: HttpMethod::Get, url: String::new(), headers: HashMap::new(), auth: None, tls: HttpTlsConfig::default(), follow_redirects: false, max_redirects: None, allow_redirect_to_http: false, content: Vec::new(), content_stream: None, response_stream: None }
     }
 }
 
@@ -139,6 +220,15 @@ impl HttpResponse {
         self.ptr.borrow_mut().as_mut().set_completed(false);
     }
 
+    fn fail_request_with_code(&self, code: u32) {
+        self.ptr.borrow_mut().as_mut().set_error_code(code);
+        self.fail_request();
+    }
+
+    fn cancel_request(&self) {
+        self.ptr.borrow_mut().as_mut().set_cancelled();
+    }
+
     pub async fn wait_for_completion(self) -> Result<HttpResponseData, HttpClientError> {
         // clone is to avoid holding borrow across suspension point
         let waiter = self.ptr.borrow().as_ref().get_completion_waiter();
@@ -146,6 +236,16 @@ impl HttpResponse {
 
         self.ptr.borrow_mut().as_mut().get_result()
     }
+
+    // Results are returned in the same order as responses
+    pub async fn wait_all(responses: Vec<HttpResponse>) -> Vec<Result<HttpResponseData, HttpClientError>> {
+        let mut results = Vec::with_capacity(responses.len());
+        for response in responses {
+            results.push(response.wait_for_completion().await);
+        }
+
+        results
+    }
 }
 
 struct UploadBuffer {
@@ -165,7 +265,7 @@ impl Debug for UploadBuffer {
 }
 
 struct ResponseBuffer {
-    stream: Option<Box<dyn Fn(&[u8]) -> usize>>,
+    stream: Option<Box<dyn FnMut(&[u8]) -> usize>>,
     data: Vec<u8>,
 }
 
@@ -186,6 +286,8 @@ struct HttpResponseInner {
     url_cstring: CString,
     completion: AsyncSignal,
     error: Option<String>,
+    error_code: Option<u32>,
+    cancelled: bool,
     headers: *mut curl_slist,
     _pin: PhantomPinned,
 }
@@ -199,6 +301,8 @@ impl Debug for HttpResponseInner {
         .field("url_cstring", &self.url_cstring)
         .field("completion", &self.completion)
         .field("error", &self.error)
+        .field("error_code", &self.error_code)
+        .field("cancelled", &self.cancelled)
         .field("headers", &self.headers)
         .finish()
     }
@@ -233,6 +337,8 @@ impl HttpResponseInner {
                 completion: AsyncSignal::new(),
                 headers: std::ptr::null_mut(),
                 error: None,
+                error_code: None,
+                cancelled: false,
                 _pin: PhantomPinned,
             })
         }
@@ -294,7 +400,31 @@ impl HttpResponseInner {
             },
             EasyOption::Headers(ptr) => {
                 curl_easy_setopt(self.handle, CURLOPT_HTTPHEADER, ptr)
-            }
+            },
+            EasyOption::NoBody(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_NOBODY, value as libc::c_long)
+            },
+            EasyOption::Userpwd(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_USERPWD, value.as_ptr())
+            },
+            EasyOption::HttpAuth(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_HTTPAUTH, value)
+            },
+            EasyOption::SslVerifyPeer(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_SSL_VERIFYPEER, value)
+            },
+            EasyOption::SslVerifyHost(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_SSL_VERIFYHOST, value)
+            },
+            EasyOption::CaInfo(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_CAINFO, value.as_ptr())
+            },
+            EasyOption::MaxRedirs(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_MAXREDIRS, value)
+            },
+            EasyOption::RedirProtocols(value) => {
+                curl_easy_setopt(self.handle, CURLOPT_REDIR_PROTOCOLS_STR, value.as_ptr())
+            },
         };
 
         match error {
@@ -329,6 +459,11 @@ impl HttpResponseInner {
                 HttpMethod::Post => self.as_ref().set_option(EasyOption::HttpPost(true))?,
                 HttpMethod::Put => self.as_ref().set_option(EasyOption::Upload(true))?,
                 HttpMethod::Delete => self.as_ref().set_option(EasyOption::CustomRequest(Some(HTTP_METHOD_DELETE.as_cstr())))?,
+                HttpMethod::Head => {
+                    // HTTPGET clears upload flag set in init, NOBODY turns request into HEAD
+                    self.as_ref().set_option(EasyOption::HttpGet(true))?;
+                    self.as_ref().set_option(EasyOption::NoBody(true))?;
+                },
             };
 
             self.as_mut().get_unchecked_mut().data_to_send.data = std::mem::take(&mut request.content);
@@ -336,14 +471,27 @@ impl HttpResponseInner {
             self.as_mut().get_unchecked_mut().url_cstring = CString::new(request.url.clone())?;
             self.as_ref().set_option(EasyOption::Url(self.url_cstring.as_c_str()))?;
 
-            let headers = request.headers.iter().fold(std::ptr::null_mut(), |list, pair| {
-                let value = CString::new(format!("{}: {}", pair.0, pair.1));
+            let mut header_lines: Vec<String> = request.headers.iter().map(|pair| format!("{}: {}", pair.0, pair.1)).collect();
+            match &request.auth {
+                None => (),
+                Some(HttpAuth::Basic { username, password }) => {
+                    let userpwd = CString::new(format!("{}:{}", username, password))?;
+                    self.as_ref().set_option(EasyOption::Userpwd(userpwd.as_c_str()))?;
+                    self.as_ref().set_option(EasyOption::HttpAuth(CURLAUTH_BASIC as libc::c_ulong))?;
+                },
+                Some(HttpAuth::Bearer(token)) => {
+                    header_lines.push(format!("Authorization: Bearer {}", token));
+                },
+            };
+
+            let headers = header_lines.iter().fold(std::ptr::null_mut(), |list, line| {
+                let value = CString::new(line.as_str());
                 match value {
                     Ok(value) => {
                         curl_slist_append(list, value.as_ptr() as *const libc::c_char)
                     },
                     Err(_) => {
-                        eprintln!("NULL characters inside header name or value - {}: {}", pair.0, pair.1);
+                        eprintln!("NULL characters inside header name or value - {}", line);
                         list
                     }
                 }
@@ -355,6 +503,29 @@ impl HttpResponseInner {
 
             self.as_mut().get_unchecked_mut().headers = headers;
             self.as_ref().set_option(EasyOption::FollowLocation(request.follow_redirects))?;
+            if let Some(max_redirects) = request.max_redirects {
+                self.as_ref().set_option(EasyOption::MaxRedirs(max_redirects as libc::c_long))?;
+            }
+
+            let is_https = request.url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+            let protocols = match is_https && !request.allow_redirect_to_http {
+                true => HTTP_REDIRECT_HTTPS_ONLY.as_cstr(),
+                false => HTTP_REDIRECT_ANY.as_cstr(),
+            };
+
+            self.as_ref().set_option(EasyOption::RedirProtocols(protocols))?;
+
+            if !request.tls.verify_peer || !request.tls.verify_host {
+                eprintln!("TLS verification disabled for {} (peer: {}, host: {})", request.url, request.tls.verify_peer, request.tls.verify_host);
+            }
+
+            let (verify_peer, verify_host) = request.tls.curl_verify_values();
+            self.as_ref().set_option(EasyOption::SslVerifyPeer(verify_peer))?;
+            self.as_ref().set_option(EasyOption::SslVerifyHost(verify_host))?;
+            if let Some(ca_file) = &request.tls.ca_file {
+                let ca_file = CString::new(ca_file.as_str())?;
+                self.as_ref().set_option(EasyOption::CaInfo(ca_file.as_c_str()))?;
+            }
             Ok(())
         }
     }
@@ -369,11 +540,29 @@ impl HttpResponseInner {
         }
     }
 
+    fn set_error_code(mut self: Pin<&mut Self>, code: u32) {
+        unsafe {
+            self.as_mut().get_unchecked_mut().error_code = Some(code);
+        }
+    }
+
+    fn set_cancelled(mut self: Pin<&mut Self>) {
+        unsafe {
+            self.as_mut().get_unchecked_mut().cancelled = true;
+        }
+
+        self.completion.signal();
+    }
+
     fn get_completion_waiter(self: Pin<&Self>) -> AsyncSignal {
         self.completion.clone()
     }
 
     fn get_result(self: Pin<&mut Self>) -> Result<HttpResponseData, HttpClientError> {
+        if self.cancelled {
+            return Err(HttpClientError::Cancelled);
+        }
+
         match &self.as_ref().error {
             Some(error) => Err(self.as_ref().get_error_result(error)),
             None => Ok(self.get_ok_result()),
@@ -381,7 +570,10 @@ impl HttpResponseInner {
     }
 
     fn get_error_result(self: Pin<&Self>, error: &String) -> HttpClientError {
-        HttpClientError::RequestError(error.clone())
+        match self.error_code {
+            Some(CURLE_TOO_MANY_REDIRECTS) => HttpClientError::TooManyRedirects,
+            _ => HttpClientError::RequestError(error.clone()),
+        }
     }
 
     fn get_ok_result(mut self: Pin<&mut Self>) -> HttpResponseData {
@@ -495,7 +687,7 @@ impl SocketData {
 #[derive(Debug)]
 enum IOEvent {
     TimerFired,
-    FdReady(i32, bool, bool),
+    FdReady(i32, PollMask),
 }
 
 #[derive(Debug)]
@@ -506,12 +698,13 @@ struct HttpClientData {
     io_events_tx: AsyncChannelTx<IOEvent>,
     io_events_rx: AsyncChannelRx<IOEvent>,
     responses: Vec<HttpResponse>,
+    ops_scope: CancellationScope,
 }
 
 impl HttpClientData {
     fn new(multi_handle: *mut CURLM) -> Self {
         let (rx, tx) = async_channel_create();
-        Self { multi_handle, timer_epoch: 0, timer_op: None, io_events_rx: rx, io_events_tx: tx, responses: vec![] }
+        Self { multi_handle, timer_epoch: 0, timer_op: None, io_events_rx: rx, io_events_tx: tx, responses: vec![], ops_scope: CancellationScope::new() }
     }
 }
 
@@ -578,6 +771,15 @@ impl HttpClientDataPtr {
         self.ptr.borrow().multi_handle
     }
 
+    fn ops_scope(&self) -> CancellationScope {
+        self.ptr.borrow().ops_scope.clone()
+    }
+
+    // Poll and timer completions hold a reference to poller, so ops left in flight would keep it alive
+    fn cancel_ops(&self) {
+        self.ops_scope().cancel_all();
+    }
+
     unsafe fn complete_requests(&self) {
         loop {
             let mut msg_in_queue: i32 = 0;
@@ -601,9 +803,9 @@ impl HttpClientDataPtr {
 
                         curl_multi_remove_handle(inner.multi_handle, easy);
                     },
-                    (Some(idx), _) => {
+                    (Some(idx), code) => {
                         let response = inner.responses.remove(idx);
-                        response.fail_request();
+                        response.fail_request_with_code(code);
 
                         curl_multi_remove_handle(inner.multi_handle, easy);
                     },
@@ -618,6 +820,18 @@ impl HttpClientDataPtr {
             curl_multi_remove_handle(self.multi_handle(), r.easy_handle());
         });
     }
+
+    // Removing easy handle makes curl report its sockets with CURL_POLL_REMOVE, which cancels poll ops
+    unsafe fn cancel_all_requests(&self) {
+        self.take_all_responses().into_iter().for_each(|r| {
+            let code = curl_multi_remove_handle(self.multi_handle(), r.easy_handle());
+            if code != CURLM_OK {
+                eprintln!("Error in curl_multi_remove_handle: {}", curlm_code_to_error(code));
+            }
+
+            r.cancel_request();
+        });
+    }
 }
 
 struct HttpPinnedData {
@@ -679,14 +893,21 @@ impl HttpPinnedData {
                 loop {
                     let event = poller.wait_for_event().await;
                     match event {
-                        IOEvent::FdReady(fd, read, write) => {
+                        IOEvent::FdReady(fd, revents) => {
                             // println!("IOEvent::FdReady event");
-                            let mask = match (read, write) {
-                                (true, false)   => CURL_CSELECT_IN,
-                                (false, true)   => CURL_CSELECT_OUT,
-                                (true, true)    => CURL_CSELECT_IN | CURL_CSELECT_IN,
-                                (_, _) => panic!("IO event false/false"),
-                            };
+                            let mut mask = 0;
+                            // hangup is reported as readable, so curl reads remaining data and eof
+                            if revents.is_readable() || revents.is_hangup() {
+                                mask |= CURL_CSELECT_IN;
+                            }
+
+                            if revents.is_writable() {
+                                mask |= CURL_CSELECT_OUT;
+                            }
+
+                            if revents.is_error() {
+                                mask |= CURL_CSELECT_ERR;
+                            }
 
                             let mut running: i32 = 0;
                             let error = curl_multi_socket_action(multi_handle, fd, mask as i32, &mut running);
@@ -723,6 +944,28 @@ impl HttpPinnedData {
         Ok(response)
     }
 
+    pub fn execute_many(mut self: Pin<&mut Self>, requests: Vec<HttpRequest>) -> Result<Vec<HttpResponse>, HttpClientError> {
+        let mut responses = Vec::with_capacity(requests.len());
+        for mut request in requests {
+            let response = HttpResponse::new()?;
+            response.setup(&mut request)?;
+
+            self.poller.add_response(response.clone());
+            self.as_mut().attach(&response)?;
+            responses.push(response);
+        }
+
+        // single perform call starts all transfers
+        self.as_mut().perform()?;
+        Ok(responses)
+    }
+
+    pub fn cancel_all(self: Pin<&mut Self>) {
+        unsafe {
+            self.poller.cancel_all_requests();
+        }
+    }
+
     fn attach(self: Pin<&mut Self>, response: &HttpResponse) -> Result<(), HttpClientError> {
         unsafe {
             let code = curl_multi_add_handle(self.multi_handle, response.easy_handle());
@@ -762,10 +1005,15 @@ impl Drop for HttpPinnedData {
             if code != CURLM_OK {
                 eprintln!("Error in curl_multi_cleanup: {}", curlm_code_to_error(code));
             }
+
+            self.poller.cancel_ops();
         }
     }
 }
 
+// All requests of a client share one curl multi handle, which keeps finished connections open and
+// reuses them for following requests to the same host. Keeping one client around for a session,
+// instead of creating a new one per request, avoids repeated TCP and TLS handshakes.
 pub struct HttpClient {
     ptr: Pin<Box<HttpPinnedData>>,
 }
@@ -781,6 +1029,15 @@ impl HttpClient {
     pub fn execute(&mut self, request: HttpRequest) -> Result<HttpResponse, HttpClientError> {
         self.ptr.as_mut().execute(request)
     }
+
+    // Requests run concurrently, use HttpResponse::wait_all to collect results
+    pub fn execute_many(&mut self, requests: Vec<HttpRequest>) -> Result<Vec<HttpResponse>, HttpClientError> {
+        self.ptr.as_mut().execute_many(requests)
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.ptr.as_mut().cancel_all()
+    }
 }
 
 unsafe extern "C" fn socket_callback(_curl: *mut CURL, sockfd: curl_socket_t, what: libc::c_int, userp: *mut libc::c_void, sockp: *mut libc::c_void) -> libc::c_int {
@@ -870,7 +1127,7 @@ unsafe extern "C" fn write_proxy(ptr: *mut libc::c_char, size: libc::size_t, nme
     let data = std::slice::from_raw_parts(ptr as *const u8, size * nmemb);
     let buffer = &mut *(userdata as *mut ResponseBuffer);
 
-    match &buffer.stream {
+    match &mut buffer.stream {
         None => {
             buffer.data.extend_from_slice(data);
             size * nmemb
@@ -927,13 +1184,13 @@ unsafe fn poll_socket(poller: HttpClientDataPtr, socket: Rc<SocketData>, wanted:
             let poller_ptr = poller.clone();
 
             let socket_data = socket.clone();
-            let token = async_poll(&socket.fd(), wanted).schedule(move |result| {
+            let token = async_poll(&socket.fd(), wanted).scope(&poller.ops_scope()).schedule(move |result| {
                 if socket_data.is_dead() {
                     return;
                 }
 
                 match &result {
-                    Ok(mask) => poller_ptr.push_event(IOEvent::FdReady(socket_data.fd(), (mask & libc::POLLIN as i32) != 0, (mask & libc::POLLOUT as i32) != 0)),
+                    Ok(mask) => poller_ptr.push_event(IOEvent::FdReady(socket_data.fd(), *mask)),
                     Err(error) if error.cancelled() => (),
                     Err(error) => panic!("Poll operation for fd {} returned {}", socket_data.fd(), error),
                 };
@@ -968,7 +1225,7 @@ unsafe fn poll_socket(poller: HttpClientDataPtr, socket: Rc<SocketData>, wanted:
                         let wanted = socket_data.wanted();
                         poll_socket(poller.clone(), socket_data, wanted);
                     },
-                    Err(error) if error.errno() == libc::ENOENT => {
+                    Err(error) if error.not_found() => {
                         let wanted = socket_data.wanted();
                         poll_socket(poller.clone(), socket_data, wanted);
                     },
@@ -1006,7 +1263,7 @@ fn schedule_timeout(poller: HttpClientDataPtr, seconds: i64, nanoseconds: i64) {
         None => {
             // println!("schedule_timeout - new op {} {}", seconds, nanoseconds);
             let poller_ptr = poller.clone();
-            let token = async_sleep_with_result(Duration::new(seconds as u64, nanoseconds as u32)).schedule(move |result| {
+            let token = async_sleep_with_result(Duration::new(seconds as u64, nanoseconds as u32)).scope(&poller.ops_scope()).schedule(move |result| {
                 poller_ptr.clear_current_op();
                 if result.is_err() {
                     return;
@@ -1049,6 +1306,18 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn auth_debug_redacted_test() {
+        let auth = HttpAuth::Basic { username: "user".to_string(), password: "secret".to_string() };
+        let text = format!("{:?}", auth);
+        assert!(text.contains("user"));
+        assert!(!text.contains("secret"));
+
+        let mut request = HttpRequest::new();
+        request.auth = Some(HttpAuth::Bearer("token".to_string()));
+        assert!(!format!("{:?}", request).contains("token"));
+    }
+
     #[test]
     fn http_client_create() {
         let client = HttpClient::new();
@@ -1087,4 +1356,158 @@ mod tests {
             assert_eq!(r.is_err(), true);
         });
     }
+
+    #[test]
+    fn http_client_request_stream_chunks() {
+        async_run(async move {
+            let chunks = Rc::new(Cell::new(0));
+            let received = Rc::new(Cell::new(0));
+            let chunks_cpy = chunks.clone();
+            let received_cpy = received.clone();
+
+            let mut client = HttpClient::new().unwrap();
+            let mut request = HttpRequest::new();
+            request.url = String::from("http://httpbin.org/stream-bytes/262144?chunk_size=4096");
+            request.response_stream = Some(Box::new(move |data| {
+                chunks_cpy.set(chunks_cpy.get() + 1);
+                received_cpy.set(received_cpy.get() + data.len());
+                data.len()
+            }));
+
+            let response = client.execute(request).unwrap();
+            let r = response.wait_for_completion().await.unwrap();
+
+            assert_eq!(r.http_code(), 200);
+            assert!(r.response_body.is_empty());
+            assert_eq!(received.get(), 262144);
+            assert!(chunks.get() > 1);
+        });
+    }
+
+    #[test]
+    fn http_client_cancel_all() {
+        use std::time::Instant;
+
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+
+            let mut responses = vec![];
+            for _ in 0..3 {
+                let mut request = HttpRequest::new();
+                request.url = String::from("http://www.google.com/");
+                request.follow_redirects = true;
+
+                responses.push(client.execute(request).unwrap());
+            }
+
+            let started = Instant::now();
+            client.cancel_all();
+
+            for response in responses {
+                let r = response.wait_for_completion().await;
+                assert!(matches!(r, Err(HttpClientError::Cancelled)));
+            }
+
+            assert!(started.elapsed().as_secs() < 1);
+        });
+    }
+
+    #[test]
+    fn http_client_execute_many() {
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+
+            let requests = (0..4).map(|_| {
+                let mut request = HttpRequest::new();
+                request.url = String::from("http://www.google.com/robots.txt");
+                request.follow_redirects = true;
+                request
+            }).collect();
+
+            let responses = client.execute_many(requests).unwrap();
+            let results = HttpResponse::wait_all(responses).await;
+
+            assert_eq!(results.len(), 4);
+            assert!(results.iter().all(|r| r.as_ref().is_ok_and(|data| data.http_code() == 200)));
+        });
+    }
+
+    #[test]
+    fn http_client_head_request() {
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+            let mut request = HttpRequest::new();
+            request.method = HttpMethod::Head;
+            request.url = String::from("http://www.google.com/robots.txt");
+            request.follow_redirects = true;
+
+            let response = client.execute(request).unwrap();
+            let r = response.wait_for_completion().await.unwrap();
+
+            assert_eq!(r.http_code(), 200);
+            assert!(r.headers().keys().any(|key| key.eq_ignore_ascii_case("content-length")));
+            assert!(r.response_body.is_empty());
+        });
+    }
+
+    #[test]
+    fn http_client_auth() {
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+
+            let mut request = HttpRequest::new();
+            request.url = String::from("http://httpbin.org/basic-auth/user/secret");
+            request.auth = Some(HttpAuth::Basic { username: String::from("user"), password: String::from("secret") });
+            let basic = client.execute(request).unwrap();
+
+            let mut request = HttpRequest::new();
+            request.url = String::from("http://httpbin.org/bearer");
+            request.auth = Some(HttpAuth::Bearer(String::from("token")));
+            let bearer = client.execute(request).unwrap();
+
+            assert_eq!(basic.wait_for_completion().await.unwrap().http_code(), 200);
+            assert_eq!(bearer.wait_for_completion().await.unwrap().http_code(), 200);
+        });
+    }
+
+    // vendored curl is built without SSL, so only values passed to the easy handle are checked
+    #[test]
+    fn http_client_tls_config() {
+        assert_eq!(HttpTlsConfig::default().curl_verify_values(), (1, 2));
+        assert_eq!(HttpTlsConfig::insecure().curl_verify_values(), (0, 0));
+
+        let peer_only = HttpTlsConfig { verify_peer: true, verify_host: false, ca_file: None };
+        assert_eq!(peer_only.curl_verify_values(), (1, 0));
+
+        let host_only = HttpTlsConfig { verify_peer: false, verify_host: true, ca_file: None };
+        assert_eq!(host_only.curl_verify_values(), (0, 2));
+
+        // options are accepted by the easy handle
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+
+            let mut request = HttpRequest::new();
+            request.url = String::from("http://httpbin.org/get");
+            request.tls = HttpTlsConfig::insecure();
+            let response = client.execute(request).unwrap();
+
+            assert_eq!(response.wait_for_completion().await.unwrap().http_code(), 200);
+        });
+    }
+
+    #[test]
+    fn http_client_too_many_redirects() {
+        async_run(async move {
+            let mut client = HttpClient::new().unwrap();
+            let mut request = HttpRequest::new();
+            request.url = String::from("http://httpbin.org/redirect/5");
+            request.follow_redirects = true;
+            request.max_redirects = Some(2);
+
+            let response = client.execute(request).unwrap();
+            let r = response.wait_for_completion().await;
+
+            assert!(matches!(r, Err(HttpClientError::TooManyRedirects)));
+        });
+    }
 }
@@ -46,6 +46,7 @@ impl EventFdFlags {
     }
 }
 
+#[derive(Debug)]
 pub struct EventFd {
     fd: OwnedFd,
 }
@@ -67,7 +68,8 @@ impl EventFd {
             let result = libc::write(self.fd.as_raw_fd(), data.as_ptr() as *const libc::c_void, data.len());
             match result as i32 {
                 i if i == data.len() as i32  => true,
-                libc::EAGAIN => false,
+                // counter would overflow on non-blocking eventfd
+                -1 if SystemError::new_from_errno().would_block() => false,
                 _ => panic!("Unknown write result for eventdf = {}", result),
             }
         }
@@ -79,13 +79,46 @@ impl<T> IndexedList<T> {
         self.entries[index].as_mut()
     }
 
+    // Slots handed out by allocate count as used, even before value is inserted
     pub fn size(&self) -> usize {
         self.entries.len() - self.free_entries.len()
     }
 
+    pub fn len(&self) -> usize {
+        self.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    // Number of slots created so far, free ones are reused before list grows
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Yields every slot, None for free ones
     pub fn iter(&self) -> IndexedListIterator<T> {
         IndexedListIterator(0, self)
     }
+
+    pub fn iter_indexed(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.entries.iter().enumerate().filter_map(|(index, entry)| entry.as_ref().map(|value| (index, value)))
+    }
+
+    pub fn retain<F: FnMut(usize, &T) -> bool>(&mut self, mut predicate: F) {
+        for index in 0..self.entries.len() {
+            let keep = match &self.entries[index] {
+                None => true,
+                Some(value) => predicate(index, value),
+            };
+
+            if !keep {
+                self.entries[index] = None;
+                self.free_entries.push(index);
+            }
+        }
+    }
 }
 
 impl<T: Clone> IndexedList<T> {
@@ -110,12 +143,51 @@ impl<'list, T> Iterator for IndexedListIterator<'list, T> {
     type Item = Option<&'list T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.1.get(self.0) {
-            None => None,
-            Some(value) => {
-                self.0 += 1;
-                Some(Some(value))
-            },
+        // free slot in the middle must not end iteration
+        let entry = self.1.entries.get(self.0)?;
+        self.0 += 1;
+        Some(entry.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterate_with_holes() {
+        let mut list = IndexedList::new();
+        let first = list.insert(1);
+        let second = list.insert(2);
+        let third = list.insert(3);
+        list.remove(second);
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![Some(&1), None, Some(&3)]);
+        assert_eq!(list.iter_indexed().collect::<Vec<_>>(), vec![(first, &1), (third, &3)]);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.capacity(), 3);
+    }
+
+    #[test]
+    fn retain_frees_slots() {
+        let mut list = IndexedList::new();
+        for i in 0..6 {
+            list.insert(i);
+        }
+
+        list.retain(|_, value| value % 2 == 0);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.capacity(), 6);
+
+        // freed slots are reused before list grows
+        for i in 0..3 {
+            list.insert(i);
         }
+
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.capacity(), 6);
+
+        list.retain(|_, _| false);
+        assert!(list.is_empty());
     }
 }
@@ -12,6 +12,10 @@ pub mod indexed_list;
 pub mod poll;
 pub mod pipe;
 pub mod eventfd;
+pub mod process;
+
+#[cfg(feature = "serde")]
+mod serde_support;
 
 #[inline]
 pub fn update_cell<T: Default, F: FnOnce(T) -> T>(cell: &Cell<T>, f: F) {
@@ -11,21 +11,21 @@ impl OpenMode {
     }
 
     pub fn read_only(&mut self) -> &mut Self {
-        self.flags ^= !(libc::O_RDWR | libc::O_RDONLY | libc::O_WRONLY);
+        self.flags &= !libc::O_ACCMODE;
         self.flags |= libc::O_RDONLY;
 
         self
     }
 
     pub fn write_only(&mut self) -> &mut Self {
-        self.flags ^= !(libc::O_RDWR | libc::O_RDONLY | libc::O_WRONLY);
+        self.flags &= !libc::O_ACCMODE;
         self.flags |= libc::O_WRONLY;
 
         self
     }
 
     pub fn read_write(&mut self) -> &mut Self {
-        self.flags ^= !(libc::O_RDWR | libc::O_RDONLY | libc::O_WRONLY);
+        self.flags &= !libc::O_ACCMODE;
         self.flags |= libc::O_RDWR;
 
         self
@@ -33,9 +33,9 @@ impl OpenMode {
 
     pub fn append(&mut self, value: bool) -> &mut Self {
         if value {
-            self.flags |= libc::O_CREAT;
+            self.flags |= libc::O_APPEND;
         } else {
-            self.flags ^= !libc::O_CREAT;
+            self.flags &= !libc::O_APPEND;
         }
 
         self
@@ -45,7 +45,7 @@ impl OpenMode {
         if value {
             self.flags |= libc::O_CLOEXEC;
         } else {
-            self.flags ^= !libc::O_CLOEXEC;
+            self.flags &= !libc::O_CLOEXEC;
         }
 
         self
@@ -56,7 +56,7 @@ impl OpenMode {
             self.flags |= libc::O_CREAT;
             self.mode = mode;
         } else {
-            self.flags ^= !libc::O_CREAT;
+            self.flags &= !libc::O_CREAT;
         }
 
         self
@@ -66,7 +66,7 @@ impl OpenMode {
         if value {
             self.flags |= libc::O_EXCL;
         } else {
-            self.flags ^= !libc::O_EXCL;
+            self.flags &= !libc::O_EXCL;
         }
 
         self
@@ -76,7 +76,7 @@ impl OpenMode {
         if value {
             self.flags |= libc::O_DIRECT;
         } else {
-            self.flags ^= !libc::O_DIRECT;
+            self.flags &= !libc::O_DIRECT;
         }
 
         self
@@ -86,7 +86,7 @@ impl OpenMode {
         if value {
             self.flags |= libc::O_NONBLOCK;
         } else {
-            self.flags ^= !libc::O_NONBLOCK;
+            self.flags &= !libc::O_NONBLOCK;
         }
 
         self
@@ -96,7 +96,7 @@ impl OpenMode {
         if value {
             self.flags |= libc::O_TRUNC;
         } else {
-            self.flags ^= !libc::O_TRUNC;
+            self.flags &= !libc::O_TRUNC;
         }
 
         self
@@ -119,5 +119,57 @@ impl OpenMode {
     pub fn mode(&self) -> u32 {
         self.mode
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+
+    fn open_flags(path: &str, mode: &OpenMode) -> Result<(i32, i32), i32> {
+        let path = CString::new(path).unwrap();
+        let fd = unsafe { libc::open(path.as_ptr(), mode.flags(), mode.mode()) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().raw_os_error().unwrap());
+        }
+
+        let result = unsafe { (libc::fcntl(fd, libc::F_GETFL), libc::fcntl(fd, libc::F_GETFD)) };
+        unsafe { libc::close(fd) };
+
+        Ok(result)
+    }
 
+    #[test]
+    fn open_mode_flags_test() {
+        let path = format!("{}/fbs-open-mode-{}", std::env::temp_dir().display(), std::process::id());
+
+        let (flags, fd_flags) = open_flags(&path, OpenMode::new().create(true, 0o600).write_only().append(true).close_on_exec(true)).unwrap();
+        assert_eq!(flags & libc::O_ACCMODE, libc::O_WRONLY);
+        assert_ne!(flags & libc::O_APPEND, 0);
+        assert_ne!(fd_flags & libc::FD_CLOEXEC, 0);
+
+        let (flags, fd_flags) = open_flags(&path, OpenMode::new().read_only().non_blocking(true).close_on_exec(false)).unwrap();
+        assert_eq!(flags & libc::O_ACCMODE, libc::O_RDONLY);
+        assert_ne!(flags & libc::O_NONBLOCK, 0);
+        assert_eq!(flags & libc::O_APPEND, 0);
+        assert_eq!(fd_flags & libc::FD_CLOEXEC, 0);
+
+        // switching access mode or clearing flag leaves other flags intact
+        let mode = *OpenMode::new().append(true).non_blocking(true).write_only().read_write().append(false);
+        assert_eq!(mode.flags(), libc::O_RDWR | libc::O_NONBLOCK);
+
+        let (flags, _) = open_flags(&path, OpenMode::new().truncate(true)).unwrap();
+        assert_eq!(flags & libc::O_ACCMODE, libc::O_RDWR);
+
+        // tmpfs doesn't support direct IO
+        match open_flags(&path, OpenMode::new().direct(true)) {
+            Ok((flags, _)) => assert_ne!(flags & libc::O_DIRECT, 0),
+            Err(errno) => assert_eq!(errno, libc::EINVAL),
+        }
+
+        assert_eq!(open_flags(&path, OpenMode::new().create(true, 0o600).exists(true)), Err(libc::EEXIST));
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -11,7 +11,7 @@ impl PipeFlags {
         if value {
             self.flags |= libc::O_CLOEXEC;
         } else {
-            self.flags ^= !libc::O_CLOEXEC;
+            self.flags &= !libc::O_CLOEXEC;
         }
 
         *self
@@ -21,7 +21,7 @@ impl PipeFlags {
         if value {
             self.flags |= libc::O_DIRECT;
         } else {
-            self.flags ^= !libc::O_DIRECT;
+            self.flags &= !libc::O_DIRECT;
         }
 
         *self
@@ -31,7 +31,7 @@ impl PipeFlags {
         if value {
             self.flags |= libc::O_NONBLOCK;
         } else {
-            self.flags ^= !libc::O_NONBLOCK;
+            self.flags &= !libc::O_NONBLOCK;
         }
 
         *self
@@ -12,7 +12,7 @@ impl PollMask {
         if value {
             self.mask |= libc::POLLIN;
         } else {
-            self.mask ^= !libc::POLLIN;
+            self.mask &= !libc::POLLIN;
         }
 
         *self
@@ -22,11 +22,65 @@ impl PollMask {
         if value {
             self.mask |= libc::POLLOUT;
         } else {
-            self.mask ^= !libc::POLLOUT;
+            self.mask &= !libc::POLLOUT;
         }
 
         *self
     }
+
+    // Urgent (out-of-band) data
+    pub fn priority(&mut self, value: bool) -> Self {
+        if value {
+            self.mask |= libc::POLLPRI;
+        } else {
+            self.mask &= !libc::POLLPRI;
+        }
+
+        *self
+    }
+
+    // Peer shut down its writing side, reported before remaining data is read
+    pub fn rdhup(&mut self, value: bool) -> Self {
+        if value {
+            self.mask |= libc::POLLRDHUP;
+        } else {
+            self.mask &= !libc::POLLRDHUP;
+        }
+
+        *self
+    }
+
+    // Accessors for masks reported back by poll
+    pub fn is_readable(&self) -> bool {
+        self.mask & libc::POLLIN != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.mask & libc::POLLOUT != 0
+    }
+
+    pub fn is_priority(&self) -> bool {
+        self.mask & libc::POLLPRI != 0
+    }
+
+    pub fn is_rdhup(&self) -> bool {
+        self.mask & libc::POLLRDHUP != 0
+    }
+
+    // Error and hangup are always reported, there is no need to request them
+    pub fn is_error(&self) -> bool {
+        self.mask & libc::POLLERR != 0
+    }
+
+    pub fn is_hangup(&self) -> bool {
+        self.mask & libc::POLLHUP != 0
+    }
+}
+
+impl From<i32> for PollMask {
+    fn from(value: i32) -> Self {
+        PollMask { mask: value as i16 }
+    }
 }
 
 impl Into<i16> for PollMask {
@@ -41,6 +95,12 @@ impl Into<i32> for PollMask {
     }
 }
 
+impl From<u32> for PollMask {
+    fn from(value: u32) -> Self {
+        PollMask { mask: value as u16 as i16 }
+    }
+}
+
 impl Into<u16> for PollMask {
     fn into(self) -> u16 {
         self.mask as u16
@@ -49,6 +109,56 @@ impl Into<u16> for PollMask {
 
 impl Into<u32> for PollMask {
     fn into(self) -> u32 {
-        self.mask as u32
+        // no sign extension, poll bits only occupy lower 16 bits
+        self.mask as u16 as u32
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revents_test() {
+        let mask = PollMask::from((libc::POLLIN | libc::POLLHUP) as i32);
+        assert!(mask.is_readable());
+        assert!(mask.is_hangup());
+        assert!(!mask.is_writable());
+        assert!(!mask.is_error());
+
+        let mask = PollMask::default().write(true);
+        assert!(mask.is_writable());
+        assert!(!mask.is_readable());
+    }
+
+    #[test]
+    fn setters_test() {
+        let mut mask = PollMask::default().read(true).write(true).priority(true).rdhup(true);
+        assert!(mask.is_readable() && mask.is_writable() && mask.is_priority() && mask.is_rdhup());
+
+        mask.write(false);
+        mask.rdhup(false);
+        assert!(mask.is_readable() && mask.is_priority());
+        assert!(!mask.is_writable() && !mask.is_rdhup());
+
+        // setters modify the receiver in place and also return the updated value
+        mask.read(false);
+        mask.priority(false);
+        assert!(mask.empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn conversions_round_trip_test() {
+        let mask = PollMask::default().read(true).write(true).priority(true).rdhup(true);
+        let raw = (libc::POLLIN | libc::POLLOUT | libc::POLLPRI | libc::POLLRDHUP) as i32;
+
+        let as_i32: i32 = mask.into();
+        let as_u32: u32 = mask.into();
+        assert_eq!(as_i32, raw);
+        assert_eq!(as_u32, raw as u32);
+        assert_eq!(PollMask::from(as_i32), mask);
+        assert_eq!(PollMask::from(as_u32), mask);
+
+        let revents = PollMask::from((libc::POLLERR | libc::POLLHUP) as i32);
+        assert!(revents.is_error() && revents.is_hangup());
+    }
+}
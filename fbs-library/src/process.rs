@@ -0,0 +1,46 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildExitStatus {
+    Exited(i32),                // exit code
+    Killed(i32),                // signal number
+    Dumped(i32),                // signal number, core was dumped
+    Other(i32, i32),            // si_code, si_status - stopped/continued children, only with WSTOPPED/WCONTINUED
+}
+
+impl ChildExitStatus {
+    pub fn from_siginfo(info: &libc::siginfo_t) -> Self {
+        Self::from_code(info.si_code, unsafe { info.si_status() })
+    }
+
+    // si_code and si_status pair, as reported by waitid or signalfd
+    pub fn from_code(code: i32, status: i32) -> Self {
+        match code {
+            libc::CLD_EXITED => Self::Exited(status),
+            libc::CLD_KILLED => Self::Killed(status),
+            libc::CLD_DUMPED => Self::Dumped(status),
+            code => Self::Other(code, status),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        *self == Self::Exited(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::zombie_processes)]   // child is reaped by waitid
+    fn child_exit_status_test() {
+        let child = std::process::Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::waitid(libc::P_PID, child.id(), &mut info, libc::WEXITED) };
+        assert_eq!(result, 0);
+
+        let status = ChildExitStatus::from_siginfo(&info);
+        assert_eq!(status, ChildExitStatus::Exited(3));
+        assert!(!status.success());
+    }
+}
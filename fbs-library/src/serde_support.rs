@@ -0,0 +1,63 @@
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+
+use super::ip_address::IpAddress;
+use super::socket_address::SocketIpAddress;
+
+// Both types use the same text form as to_text/from_text, port is mandatory for SocketIpAddress
+impl Serialize for IpAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_text())
+    }
+}
+
+impl<'de> Deserialize<'de> for IpAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        IpAddress::from_text(&text).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for SocketIpAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_text())
+    }
+}
+
+impl<'de> Deserialize<'de> for SocketIpAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        SocketIpAddress::from_text(&text, None).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_address_round_trip() {
+        for text in ["127.0.0.1", "2001:db8::8a2e:370:7334"] {
+            let address = IpAddress::from_text(text).unwrap();
+            let json = serde_json::to_string(&address).unwrap();
+            assert_eq!(json, format!("\"{}\"", text));
+            assert_eq!(serde_json::from_str::<IpAddress>(&json).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn socket_address_round_trip() {
+        for text in ["127.0.0.1:2404", "[2001:db8::8a2e:370:7334]:2404"] {
+            let address = SocketIpAddress::from_text(text, None).unwrap();
+            let json = serde_json::to_string(&address).unwrap();
+            assert_eq!(json, format!("\"{}\"", text));
+            assert_eq!(serde_json::from_str::<SocketIpAddress>(&json).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn socket_address_invalid() {
+        assert!(serde_json::from_str::<SocketIpAddress>("\"127.0.0.1\"").is_err());
+        assert!(serde_json::from_str::<IpAddress>("\"not an address\"").is_err());
+    }
+}
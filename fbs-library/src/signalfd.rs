@@ -1,8 +1,12 @@
-use std::mem::MaybeUninit;
+use std::mem::{MaybeUninit, size_of};
 use std::os::fd::{OwnedFd, FromRawFd, AsRawFd, IntoRawFd, RawFd};
 use super::sigset::{SignalSet, Signal};
+use super::process::ChildExitStatus;
 use super::system_error::SystemError;
 
+// Buffer size for reading up to 16 pending signals at once
+pub const SIGNALFD_READ_BATCH_SIZE: usize = 16 * size_of::<libc::signalfd_siginfo>();
+
 #[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct SignalFdInfo(libc::signalfd_siginfo);
@@ -15,6 +19,36 @@ impl SignalFdInfo {
     pub fn signal(&self) -> Signal {
         unsafe { std::mem::transmute(self.0.ssi_signo as i32) }
     }
+
+    // Sender pid, for SIGCHLD it is the child that changed state
+    pub fn pid(&self) -> libc::pid_t {
+        self.0.ssi_pid as libc::pid_t
+    }
+
+    pub fn uid(&self) -> libc::uid_t {
+        self.0.ssi_uid
+    }
+
+    // si_code, for SIGCHLD one of CLD_* values
+    pub fn code(&self) -> i32 {
+        self.0.ssi_code
+    }
+
+    // For SIGCHLD exit code or signal number, depending on code()
+    pub fn status(&self) -> i32 {
+        self.0.ssi_status
+    }
+
+    pub fn child_exit_status(&self) -> ChildExitStatus {
+        ChildExitStatus::from_code(self.code(), self.status())
+    }
+
+    // Read from signalfd always returns whole structs, but data is not guaranteed to be aligned
+    pub fn from_bytes(data: &[u8]) -> Vec<SignalFdInfo> {
+        data.chunks_exact(size_of::<libc::signalfd_siginfo>())
+            .map(|chunk| Self(unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const libc::signalfd_siginfo) }))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -31,7 +65,7 @@ impl SignalFdFlags {
         if value {
             self.flags |= libc::SFD_CLOEXEC;
         } else {
-            self.flags ^= !libc::SFD_CLOEXEC;
+            self.flags &= !libc::SFD_CLOEXEC;
         }
 
         self
@@ -41,7 +75,7 @@ impl SignalFdFlags {
         if value {
             self.flags |= libc::SFD_NONBLOCK;
         } else {
-            self.flags ^= !libc::SFD_NONBLOCK;
+            self.flags &= !libc::SFD_NONBLOCK;
         }
 
         self
@@ -81,6 +115,19 @@ impl SignalFd {
         }
     }
 
+    // Single read returns all pending signals that fit into batch, so burst of signals is handled
+    // at once. Non-blocking signalfd with nothing pending returns empty vector.
+    pub fn read_all(&self) -> Result<Vec<SignalFdInfo>, SystemError> {
+        let mut buffer = vec![0u8; SIGNALFD_READ_BATCH_SIZE];
+        let result = unsafe { libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+        if result < 0 {
+            let error = SystemError::new_from_errno();
+            return if error.would_block() { Ok(vec![]) } else { Err(error) };
+        }
+
+        Ok(SignalFdInfo::from_bytes(&buffer[..result as usize]))
+    }
+
     pub fn set_signal_mask(&mut self, mask: SignalSet) -> Result<(), SystemError> {
         unsafe {
             let fd = libc::signalfd(self.fd.as_raw_fd(), mask.as_ptr(), 0);
@@ -107,7 +154,7 @@ impl IntoRawFd for SignalFd {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sigset::SignalSet;
+    use crate::sigset::{SignalSet, SignalMask, set_process_signal_mask};
 
     #[test]
     fn signalfd_create() {
@@ -141,4 +188,75 @@ mod tests {
         assert_eq!(signalfd.non_blocking(), true);
         assert_eq!(signalfd.close_on_exec(), true);
     }
+
+    #[test]
+    fn signalfd_read_all() {
+        let mut mask = SignalSet::empty();
+        mask.add(Signal::SIGUSR1);
+        mask.add(Signal::SIGUSR2);
+        mask.add(Signal::SIGWINCH);
+
+        // mask applies to test thread only, raise() targets the same thread
+        set_process_signal_mask(SignalMask::Block, mask).unwrap();
+        let signalfd = SignalFd::new(mask, SignalFdFlags::new().close_on_exec(true).non_blocking(true).flags()).unwrap();
+
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+            libc::raise(libc::SIGUSR2);
+            libc::raise(libc::SIGWINCH);
+        }
+
+        let mut signals: Vec<i32> = signalfd.read_all().unwrap().into_iter().map(|info| info.signal() as i32).collect();
+        signals.sort();
+        assert_eq!(signals, vec![libc::SIGUSR1, libc::SIGUSR2, libc::SIGWINCH]);
+
+        assert_eq!(signalfd.read_all().unwrap().len(), 0);
+        set_process_signal_mask(SignalMask::Unblock, mask).unwrap();
+    }
+
+    // Runs in forked child, which is single threaded - SIGCHLD blocked there cannot be
+    // delivered to other thread of test harness
+    fn sigchld_info_check() -> i32 {
+        let mut mask = SignalSet::empty();
+        mask.add(Signal::SIGCHLD);
+        if set_process_signal_mask(SignalMask::Block, mask).is_err() {
+            return 1;
+        }
+
+        let Ok(signalfd) = SignalFd::new(mask, SignalFdFlags::new().close_on_exec(true).flags()) else {
+            return 2;
+        };
+
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe { libc::_exit(7) };
+        }
+
+        let Ok(infos) = signalfd.read_all() else {
+            return 3;
+        };
+
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED) };
+
+        match infos.first() {
+            Some(info) if info.pid() == pid && info.uid() == unsafe { libc::getuid() }
+                && info.code() == libc::CLD_EXITED && info.child_exit_status() == ChildExitStatus::Exited(7) => 0,
+            _ => 4,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::zombie_processes)]   // child is reaped by waitid
+    fn signalfd_sigchld_info() {
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe { libc::_exit(sigchld_info_check()) };
+        }
+
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED) };
+        assert_eq!(result, 0);
+        assert_eq!(ChildExitStatus::from_siginfo(&info), ChildExitStatus::Exited(0));
+    }
 }
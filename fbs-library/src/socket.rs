@@ -1,25 +1,38 @@
 use std::mem::size_of;
 use std::os::fd::{OwnedFd, FromRawFd, AsRawFd, RawFd, IntoRawFd};
 use std::io::Error;
+use std::time::Duration;
 
-use super::socket_address::SocketIpAddress;
+use super::socket_address::{SocketIpAddress, SocketAddressBinary};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum SocketError {
     #[error("System error")]
     SystemError(#[from] std::io::Error),
+    #[error("Operation not permitted, CAP_NET_RAW is required")]
+    PermissionDenied,
 }
 
 
 #[repr(i32)]
 pub enum SocketDomain {
     Inet    = libc::AF_INET,
+    Inet6   = libc::AF_INET6,
 }
 
 #[repr(i32)]
 pub enum SocketType {
     Stream  = libc::SOCK_STREAM,
+    Datagram = libc::SOCK_DGRAM,
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy)]
+pub enum SocketShutdown {
+    Read    = libc::SHUT_RD,
+    Write   = libc::SHUT_WR,
+    Both    = libc::SHUT_RDWR,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -36,7 +49,7 @@ impl SocketFlags {
         if value {
             self.flags |= libc::SOCK_CLOEXEC;
         } else {
-            self.flags ^= !libc::SOCK_CLOEXEC;
+            self.flags &= !libc::SOCK_CLOEXEC;
         }
 
         self
@@ -46,7 +59,7 @@ impl SocketFlags {
         if value {
             self.flags |= libc::SOCK_NONBLOCK;
         } else {
-            self.flags ^= !libc::SOCK_NONBLOCK;
+            self.flags &= !libc::SOCK_NONBLOCK;
         }
 
         self
@@ -59,6 +72,16 @@ impl SocketFlags {
 
 pub enum SocketOptions {
     ReuseAddr(bool),
+    KeepAlive(bool),
+    KeepIdle(u32),          // seconds of idle time before first probe
+    KeepInterval(u32),      // seconds between probes
+    KeepCount(u32),         // unanswered probes before connection is dropped
+    // Inet6 sockets only. With false, socket bound to "::" accepts IPv4 peers as well, they show
+    // up as IPv4-mapped addresses (::ffff:a.b.c.d). Must be set before bind.
+    V6Only(bool),
+    // Restricts traffic to given interface, empty name removes binding. Needs CAP_NET_RAW unless
+    // kernel allows unprivileged binding (5.7+, socket not yet bound to other device).
+    BindToDevice(String),
 }
 
 #[derive(Debug)]
@@ -77,14 +100,103 @@ impl Socket {
         }
     }
 
-    pub fn listen(&self, address: &SocketIpAddress, backlog: i32) -> Result<(), SocketError> {
+    // Enough for datagram sockets, stream sockets should use listen instead
+    pub fn bind(&self, address: &SocketIpAddress) -> Result<(), SocketError> {
         let binary = address.to_binary();
         unsafe {
             let error = libc::bind(self.fd.as_raw_fd(), binary.sockaddr_ptr(), binary.length() as u32);
             if error != 0 {
                 return Err(SocketError::SystemError(Error::last_os_error()));
             }
+        }
+
+        Ok(())
+    }
+
+    pub fn listen(&self, address: &SocketIpAddress, backlog: i32) -> Result<(), SocketError> {
+        self.bind(address)?;
+        self.listen_bound(backlog)
+    }
+
+    // For restarts, where previous listener may still hold the address for a moment. Bind is
+    // retried on EADDRINUSE, delay starts at backoff and doubles after every attempt. Blocks
+    // calling thread while waiting, last error is returned once retries are exhausted.
+    pub fn listen_with_retry(&self, address: &SocketIpAddress, backlog: i32, retries: u32, backoff: Duration) -> Result<(), SocketError> {
+        let mut delay = backoff;
+        let mut attempt = 0;
+
+        loop {
+            match self.bind(address) {
+                Ok(()) => break,
+                Err(SocketError::SystemError(error)) if error.raw_os_error() == Some(libc::EADDRINUSE) && attempt < retries => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                },
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.listen_bound(backlog)
+    }
+
+    // Synchronous counterpart of async_connect, for setup code running outside of the runtime.
+    // Blocks calling thread, on non-blocking socket fails with EINPROGRESS instead.
+    pub fn connect_blocking(&self, address: &SocketIpAddress) -> Result<(), SocketError> {
+        let binary = address.to_binary();
+        unsafe {
+            let error = libc::connect(self.fd.as_raw_fd(), binary.sockaddr_ptr(), binary.length() as u32);
+            if error != 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Address the socket is bound to, useful to learn the port picked by kernel after binding to port 0
+    pub fn local_address(&self) -> Result<SocketIpAddress, SocketError> {
+        let mut binary = SocketAddressBinary::default();
+        let mut length = size_of::<SocketAddressBinary>() as libc::socklen_t;
+        unsafe {
+            let error = libc::getsockname(self.fd.as_raw_fd(), binary.sockaddr_ptr_mut(), &mut length);
+            if error != 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+        }
+
+        binary.to_socket_address().ok_or_else(|| SocketError::SystemError(Error::from_raw_os_error(libc::EAFNOSUPPORT)))
+    }
+
+    // Reactor ops don't depend on O_NONBLOCK, so socket prepared in blocking mode can be
+    // passed to async code as it is
+    pub fn set_nonblocking(&self, value: bool) -> Result<(), SocketError> {
+        unsafe {
+            let flags = libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFL);
+            if flags < 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+
+            let flags = if value { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+            if libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFL, flags) < 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+        }
+
+        Ok(())
+    }
 
+    pub fn is_nonblocking(&self) -> Result<bool, SocketError> {
+        let flags = unsafe { libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFL) };
+        if flags < 0 {
+            return Err(SocketError::SystemError(Error::last_os_error()));
+        }
+
+        Ok(flags & libc::O_NONBLOCK != 0)
+    }
+
+    fn listen_bound(&self, backlog: i32) -> Result<(), SocketError> {
+        unsafe {
             let error = libc::listen(self.fd.as_raw_fd(), backlog);
             if error != 0 {
                 return Err(SocketError::SystemError(Error::last_os_error()));
@@ -96,20 +208,79 @@ impl Socket {
 
     pub fn set_option(&self, option: SocketOptions) -> Result<(), SocketError> {
         match option {
-            SocketOptions::ReuseAddr(value) => {
-                unsafe {
-                    let value: libc::c_int = value as libc::c_int;
-                    let error = libc::setsockopt(self.as_raw_fd(), libc::SOL_SOCKET, libc::SO_REUSEADDR, &value as *const i32 as *const libc::c_void, size_of::<libc::c_int>() as u32);
-                    if error != 0 {
-                        return Err(SocketError::SystemError(Error::last_os_error()));
-                    }
-                }
+            SocketOptions::ReuseAddr(value) => self.set_int_option(libc::SOL_SOCKET, libc::SO_REUSEADDR, value as libc::c_int),
+            SocketOptions::KeepAlive(value) => self.set_int_option(libc::SOL_SOCKET, libc::SO_KEEPALIVE, value as libc::c_int),
+            SocketOptions::KeepIdle(value) => self.set_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, value as libc::c_int),
+            SocketOptions::KeepInterval(value) => self.set_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, value as libc::c_int),
+            SocketOptions::KeepCount(value) => self.set_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPCNT, value as libc::c_int),
+            SocketOptions::V6Only(value) => self.set_int_option(libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, value as libc::c_int),
+            SocketOptions::BindToDevice(device) => self.set_bytes_option(libc::SOL_SOCKET, libc::SO_BINDTODEVICE, device.as_bytes()),
+        }
+    }
+
+    // Enables keepalive probes, so dead peer is detected even if connection stays idle
+    pub fn set_keepalive(&self, idle: u32, interval: u32, count: u32) -> Result<(), SocketError> {
+        self.set_option(SocketOptions::KeepAlive(true))?;
+        self.set_option(SocketOptions::KeepIdle(idle))?;
+        self.set_option(SocketOptions::KeepInterval(interval))?;
+        self.set_option(SocketOptions::KeepCount(count))
+    }
+
+    fn set_int_option(&self, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> Result<(), SocketError> {
+        unsafe {
+            let error = libc::setsockopt(self.as_raw_fd(), level, name, &value as *const i32 as *const libc::c_void, size_of::<libc::c_int>() as u32);
+            if error != 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_bytes_option(&self, level: libc::c_int, name: libc::c_int, value: &[u8]) -> Result<(), SocketError> {
+        unsafe {
+            let error = libc::setsockopt(self.as_raw_fd(), level, name, value.as_ptr() as *const libc::c_void, value.len() as libc::socklen_t);
+            if error != 0 {
+                let error = Error::last_os_error();
+                return match error.raw_os_error() {
+                    Some(libc::EPERM) => Err(SocketError::PermissionDenied),
+                    _ => Err(SocketError::SystemError(error)),
+                };
             }
         }
 
         Ok(())
     }
 
+    #[cfg(test)]
+    fn get_bytes_option(&self, level: libc::c_int, name: libc::c_int) -> Result<Vec<u8>, SocketError> {
+        unsafe {
+            let mut value = vec![0u8; 64];
+            let mut length = value.len() as libc::socklen_t;
+            let error = libc::getsockopt(self.as_raw_fd(), level, name, value.as_mut_ptr() as *mut libc::c_void, &mut length);
+            if error != 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+
+            value.truncate(length as usize);
+            Ok(value)
+        }
+    }
+
+    #[cfg(test)]
+    fn get_int_option(&self, level: libc::c_int, name: libc::c_int) -> Result<libc::c_int, SocketError> {
+        unsafe {
+            let mut value: libc::c_int = 0;
+            let mut length = size_of::<libc::c_int>() as libc::socklen_t;
+            let error = libc::getsockopt(self.as_raw_fd(), level, name, &mut value as *mut i32 as *mut libc::c_void, &mut length);
+            if error != 0 {
+                return Err(SocketError::SystemError(Error::last_os_error()));
+            }
+
+            Ok(value)
+        }
+    }
+
     pub fn shutdown(&self, read_end: bool, write_end: bool) -> Result<(), SocketError> {
         unsafe {
             let mut how = 0;
@@ -143,4 +314,133 @@ impl FromRawFd for Socket {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
         Self { fd: OwnedFd::from_raw_fd(fd) }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keepalive_test() {
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        assert_eq!(socket.get_int_option(libc::SOL_SOCKET, libc::SO_KEEPALIVE).unwrap(), 0);
+
+        socket.set_keepalive(30, 5, 4).unwrap();
+
+        assert_eq!(socket.get_int_option(libc::SOL_SOCKET, libc::SO_KEEPALIVE).unwrap(), 1);
+        assert_eq!(socket.get_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPIDLE).unwrap(), 30);
+        assert_eq!(socket.get_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL).unwrap(), 5);
+        assert_eq!(socket.get_int_option(libc::IPPROTO_TCP, libc::TCP_KEEPCNT).unwrap(), 4);
+    }
+
+    #[test]
+    fn bind_datagram_test() {
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Datagram, SocketFlags::new().close_on_exec(true).flags());
+        let address = SocketIpAddress::from_text("127.0.0.1:0", None).unwrap();
+        assert!(socket.bind(&address).is_ok());
+
+        // kernel picked the port
+        let bound = socket.local_address().unwrap();
+        assert_eq!(bound.address(), address.address());
+        assert_ne!(bound.port(), 0);
+
+        // listen is not supported for datagram sockets
+        let other = Socket::new(SocketDomain::Inet, SocketType::Datagram, SocketFlags::new().close_on_exec(true).flags());
+        assert!(other.listen(&address, 10).is_err());
+    }
+
+    #[test]
+    fn v6only_test() {
+        let socket = Socket::new(SocketDomain::Inet6, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+
+        socket.set_option(SocketOptions::V6Only(true)).unwrap();
+        assert_eq!(socket.get_int_option(libc::IPPROTO_IPV6, libc::IPV6_V6ONLY).unwrap(), 1);
+
+        socket.set_option(SocketOptions::V6Only(false)).unwrap();
+        assert_eq!(socket.get_int_option(libc::IPPROTO_IPV6, libc::IPV6_V6ONLY).unwrap(), 0);
+
+        // dual stack listener
+        let address = SocketIpAddress::from_text("[::]:0", None).unwrap();
+        assert!(socket.listen(&address, 10).is_ok());
+
+        // option is not available for IPv4 sockets
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        assert!(socket.set_option(SocketOptions::V6Only(false)).is_err());
+    }
+
+    #[test]
+    fn listen_with_retry_test() {
+        let holder = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        holder.listen(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap(), 10).unwrap();
+        let address = holder.local_address().unwrap();
+
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        let result = socket.listen_with_retry(&address, 10, 2, Duration::from_millis(1));
+        assert!(matches!(result, Err(SocketError::SystemError(error)) if error.raw_os_error() == Some(libc::EADDRINUSE)));
+
+        // address is released while retries are still going on
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            drop(holder);
+        });
+
+        assert!(socket.listen_with_retry(&address, 10, 10, Duration::from_millis(5)).is_ok());
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn keepalive_invalid_value_test() {
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        assert!(socket.set_keepalive(0, 5, 4).is_err());
+    }
+
+    #[test]
+    fn connect_blocking_test() {
+        let listener = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        listener.set_option(SocketOptions::ReuseAddr(true)).unwrap();
+        listener.listen(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap(), 10).unwrap();
+        let address = listener.local_address().unwrap();
+
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).non_blocking(true).flags());
+        assert!(socket.is_nonblocking().unwrap());
+
+        socket.set_nonblocking(false).unwrap();
+        assert!(!socket.is_nonblocking().unwrap());
+
+        // backlog accepts connection even though nobody calls accept
+        socket.connect_blocking(&address).unwrap();
+
+        socket.set_nonblocking(true).unwrap();
+        assert!(socket.is_nonblocking().unwrap());
+    }
+
+    #[test]
+    fn socket_flags_test() {
+        let flags = SocketFlags::new().close_on_exec(true).non_blocking(true).non_blocking(false).flags();
+        assert_eq!(flags, libc::SOCK_CLOEXEC);
+    }
+
+    #[test]
+    fn bind_to_device_test() {
+        let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+
+        match socket.set_option(SocketOptions::BindToDevice("lo".to_string())) {
+            // unprivileged test run on older kernel
+            Err(SocketError::PermissionDenied) => return,
+            result => result.unwrap(),
+        }
+
+        // kernel reports name with terminating null byte
+        let device = socket.get_bytes_option(libc::SOL_SOCKET, libc::SO_BINDTODEVICE).unwrap();
+        assert_eq!(device.split(|byte| *byte == 0).next().unwrap(), b"lo");
+
+        let address = SocketIpAddress::from_text("127.0.0.1:0", None).unwrap();
+        assert!(socket.listen(&address, 10).is_ok());
+
+        socket.set_option(SocketOptions::BindToDevice(String::new())).unwrap();
+        assert_eq!(socket.get_bytes_option(libc::SOL_SOCKET, libc::SO_BINDTODEVICE).unwrap().len(), 0);
+
+        let result = socket.set_option(SocketOptions::BindToDevice("no-such-device".to_string()));
+        assert!(matches!(result, Err(SocketError::SystemError(_))));
+    }
+}
@@ -5,7 +5,7 @@ use super::ip_address::*;
 
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SocketIpAddress {
     address: IpAddress,
     port: u16,
@@ -39,6 +39,16 @@ impl SocketAddressBinary {
         }
     }
 
+    pub fn to_socket_address(&self) -> Option<SocketIpAddress> {
+        unsafe {
+            match self.generic.sa_family as i32 {
+                libc::AF_INET => Some(SocketIpAddress::from_sockaddr_in(&self.ipv4)),
+                libc::AF_INET6 => Some(SocketIpAddress::from_sockaddr_in6(&self.ipv6)),
+                _ => None
+            }
+        }
+    }
+
     #[inline]
     pub fn length(&self) -> usize {
         unsafe {
@@ -50,7 +50,48 @@ impl SystemError {
         }
     }
 
+    // Non-blocking fd has nothing to read, or no room to write
+    #[inline]
+    pub fn would_block(&self) -> bool {
+        // same value on Linux, but not guaranteed elsewhere
+        self.0 == libc::EAGAIN || self.0 == libc::EWOULDBLOCK
+    }
+
+    #[inline]
+    pub fn interrupted(&self) -> bool {
+        self.0 == libc::EINTR
+    }
+
+    #[inline]
+    pub fn connection_reset(&self) -> bool {
+        self.0 == libc::ECONNRESET
+    }
+
+    #[inline]
+    pub fn not_found(&self) -> bool {
+        self.0 == libc::ENOENT
+    }
+
     pub fn errno(&self) -> i32 {
         self.0
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifiers_test() {
+        assert!(SystemError::new(libc::EAGAIN).would_block());
+        assert!(SystemError::new(libc::EWOULDBLOCK).would_block());
+        assert!(SystemError::new(libc::EINTR).interrupted());
+        assert!(SystemError::new(libc::ECONNRESET).connection_reset());
+        assert!(SystemError::new(libc::ENOENT).not_found());
+        assert!(!SystemError::new(libc::ENOENT).would_block());
+
+        let result = unsafe { libc::close(-1) };
+        assert_eq!(result, -1);
+        assert_eq!(SystemError::new_from_errno(), SystemError::new(libc::EBADF));
+    }
 }
\ No newline at end of file
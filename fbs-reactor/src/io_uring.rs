@@ -11,12 +11,23 @@ use fbs_library::system_error::SystemError;
 pub struct IoUringParams {
     pub sq_entries: u32,
     pub cq_entries: u32,
+    // Kernel thread polls submission queue, so submitting doesn't need a syscall while it is awake.
+    // Value is thread idle time in milliseconds, after which it sleeps until woken by next submit.
+    // Needs CAP_SYS_NICE before Linux 5.11 (CAP_SYS_ADMIN before 5.6).
+    pub sq_poll_idle: Option<u32>,
+}
+
+impl Default for IoUringParams {
+    fn default() -> Self {
+        Self { sq_entries: 16, cq_entries: 64, sq_poll_idle: None }
+    }
 }
 
 pub struct IoUring {
     ring: io_uring,
     created: bool,
     probe: *mut io_uring_probe,
+    sq_poll: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -56,6 +67,8 @@ pub enum IoUringError {
     SubmitError(SystemError),
     #[error("cqe wait error")]
     WaitError(SystemError),
+    #[error("resource register error")]
+    RegisterError(SystemError),
 }
 
 impl Drop for IoUring {
@@ -78,12 +91,18 @@ impl IoUring {
                 },
                 created: false,
                 probe: std::ptr::null_mut(),
+                sq_poll: params.sq_poll_idle.is_some(),
             };
 
             let mut raw_params: io_uring_params = mem::zeroed();
             raw_params.cq_entries = params.cq_entries;
             raw_params.flags = IORING_SETUP_CQSIZE | IORING_SETUP_CLAMP;
 
+            if let Some(idle) = params.sq_poll_idle {
+                raw_params.flags |= IORING_SETUP_SQPOLL;
+                raw_params.sq_thread_idle = idle;
+            }
+
             let errno = io_uring_queue_init_params(params.sq_entries, &mut result.ring, &mut raw_params);
             match -errno {
                 0 => {},
@@ -107,10 +126,16 @@ impl IoUring {
         unsafe { io_uring_opcode_supported(self.probe, opcode as libc::c_int) > 0 }
     }
 
+    pub fn is_sq_polling(&self) -> bool {
+        self.sq_poll
+    }
+
     pub fn sq_space_left(&self) -> u32 {
         unsafe { io_uring_sq_space_left(&self.ring) }
     }
 
+    // With SQPOLL liburing only publishes new tail and enters the kernel just when polling thread
+    // has set IORING_SQ_NEED_WAKEUP, so calling it is cheap in both modes
     pub fn submit(&mut self) -> Result<i32, IoUringError> {
         unsafe {
             let result = io_uring_submit(&mut self.ring);
@@ -122,6 +147,23 @@ impl IoUring {
         }
     }
 
+    // Set when CQ ring was full and kernel had to keep completions on its overflow list
+    pub fn cq_has_overflow(&self) -> bool {
+        unsafe { io_uring_cq_has_overflow(&self.ring) }
+    }
+
+    // Flushes overflowed completions into CQ ring, as much as there's space for
+    pub fn get_events(&mut self) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_get_events(&mut self.ring);
+            match -result {
+                0 => Ok(()),
+                libc::EAGAIN | libc::EBUSY | libc::EINTR => Err(IoUringError::TryAgain),
+                _ => Err(IoUringError::WaitError(SystemError::new(-result))),
+            }
+        }
+    }
+
     pub fn get_sqe(&mut self) -> Option<IoUringSQEPtr> {
         unsafe {
             let ptr = io_uring_get_sqe(&mut self.ring);
@@ -156,6 +198,56 @@ impl IoUring {
         }
     }
 
+    pub fn register_buffers(&mut self, buffers: &[libc::iovec]) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_register_buffers(&mut self.ring, buffers.as_ptr(), buffers.len() as u32);
+            match result {
+                0 => Ok(()),
+                errno => Err(IoUringError::RegisterError(SystemError::new(-errno))),
+            }
+        }
+    }
+
+    pub fn unregister_buffers(&mut self) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_unregister_buffers(&mut self.ring);
+            match result {
+                0 => Ok(()),
+                errno => Err(IoUringError::RegisterError(SystemError::new(-errno))),
+            }
+        }
+    }
+
+    pub fn register_files(&mut self, files: &[i32]) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_register_files(&mut self.ring, files.as_ptr(), files.len() as u32);
+            match result {
+                0 => Ok(()),
+                errno => Err(IoUringError::RegisterError(SystemError::new(-errno))),
+            }
+        }
+    }
+
+    pub fn update_files(&mut self, offset: u32, files: &[i32]) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_register_files_update(&mut self.ring, offset, files.as_ptr(), files.len() as u32);
+            match result {
+                errno if errno < 0 => Err(IoUringError::RegisterError(SystemError::new(-errno))),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    pub fn unregister_files(&mut self) -> Result<(), IoUringError> {
+        unsafe {
+            let result = io_uring_unregister_files(&mut self.ring);
+            match result {
+                0 => Ok(()),
+                errno => Err(IoUringError::RegisterError(SystemError::new(-errno))),
+            }
+        }
+    }
+
     pub fn cqe_seen(&mut self, entry: IoUringCQEPtr) {
         unsafe {
             io_uring_cqe_seen(&mut self.ring, entry.cqe)
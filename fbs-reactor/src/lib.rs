@@ -1,7 +1,9 @@
-use std::os::fd::IntoRawFd;
+use std::os::fd::{IntoRawFd, RawFd};
 use std::{ffi::CString, mem::ManuallyDrop};
 use std::time::Duration;
 use std::alloc::Layout;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::fmt::{Debug, Formatter};
 
 use liburing_sys::*;
@@ -10,26 +12,35 @@ use thiserror::Error;
 use fbs_library::socket_address::{SocketIpAddress, SocketAddressBinary};
 use fbs_library::poll::PollMask;
 
-pub use io_uring::IoUringCQE;
+pub use io_uring::{IoUringCQE, IoUringError, IoUringParams, IoUringCreateError};
 
 mod io_uring;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReactorError {
     #[error("io_uring has no more SQEs available")]
     NoSQEAvailable,
+    #[error("io_uring completion queue overflowed")]
+    CompletionOverflow,
 }
 
 const CQE_CANCEL_CQE: u64 = u64::MAX;
 const CQE_TIMEOUT_CQE: u64 = u64::MAX - 1;
 const CQE_INVALID: u64 = u64::MAX - 2;
 
+// op table is never shrunk below this size
+const OPS_TABLE_MIN_SIZE: usize = 64;
+
 pub type OpCompletion = Option<Box<dyn FnOnce(IoUringCQE, ReactorOpParameters)>>;
+pub type OpMultishotCompletion = Option<Box<dyn FnMut(IoUringCQE)>>;
 
 pub struct IOUringReq {
     pub op: IOUringOp,
     pub completion: OpCompletion,
+    pub multishot: OpMultishotCompletion,   // called for every CQE flagged with IORING_CQE_F_MORE, final one goes to completion
     pub timeout: Option<Duration>,
+    pub fixed_file: bool,       // fd of the op is an index into registered files table
+    pub drain: bool,            // op starts only after all previously submitted ops complete
 }
 
 #[non_exhaustive]
@@ -45,6 +56,20 @@ impl IOUringOpType {
     pub const ACCEPT: u32 = io_uring_op_IORING_OP_ACCEPT;
     pub const CONNECT: u32 = io_uring_op_IORING_OP_CONNECT;
     pub const TIMEOUT: u32 = io_uring_op_IORING_OP_TIMEOUT;
+    pub const FSYNC: u32 = io_uring_op_IORING_OP_FSYNC;
+    pub const RENAME: u32 = io_uring_op_IORING_OP_RENAMEAT;
+    pub const READ_FIXED: u32 = io_uring_op_IORING_OP_READ_FIXED;
+    pub const WRITE_FIXED: u32 = io_uring_op_IORING_OP_WRITE_FIXED;
+    pub const SPLICE: u32 = io_uring_op_IORING_OP_SPLICE;
+    pub const SHUTDOWN: u32 = io_uring_op_IORING_OP_SHUTDOWN;
+    pub const POLL: u32 = io_uring_op_IORING_OP_POLL_ADD;
+    pub const FALLOCATE: u32 = io_uring_op_IORING_OP_FALLOCATE;
+    pub const FADVISE: u32 = io_uring_op_IORING_OP_FADVISE;
+    pub const MADVISE: u32 = io_uring_op_IORING_OP_MADVISE;
+    pub const WAITID: u32 = io_uring_op_IORING_OP_WAITID;
+    pub const RECVMSG: u32 = io_uring_op_IORING_OP_RECVMSG;
+    pub const SENDMSG: u32 = io_uring_op_IORING_OP_SENDMSG;
+    pub const TEE: u32 = io_uring_op_IORING_OP_TEE;
 }
 
 pub struct Buffer {
@@ -75,9 +100,14 @@ impl Buffer {
         self.capacity
     }
 
+    // Vec without capacity holds dangling pointer, which was never allocated
+    fn owns_allocation(&self) -> bool {
+        !self.ptr.is_null() && self.layout.size() > 0
+    }
+
     fn clear(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
+            if self.owns_allocation() {
                 std::alloc::dealloc(self.ptr, self.layout)
             }
 
@@ -114,7 +144,8 @@ impl Buffer {
             ptr: buffer.as_mut_ptr() as *mut u8,
             size: buffer.len() * std::mem::size_of::<T>(),
             capacity: buffer.capacity() * std::mem::size_of::<T>(),
-            layout: Layout::new::<T>(),
+            // must match allocation made by Vec, as it is used to free buffer which isn't converted back
+            layout: Layout::array::<T>(buffer.capacity()).expect("Invalid Vec layout"),
         }
     }
 
@@ -133,6 +164,12 @@ impl Buffer {
         assert!(self.size % std::mem::size_of::<T>() == 0);
         assert!(self.capacity % std::mem::size_of::<T>() == 0);
 
+        // struct buffers are allocated with alignment of the struct, Vec would free them with its own layout
+        if Layout::array::<T>(self.capacity / std::mem::size_of::<T>()).ok() != Some(self.layout) {
+            let data = unsafe { std::slice::from_raw_parts(self.ptr as *const T, bytes / std::mem::size_of::<T>()) };
+            return data.to_vec();
+        }
+
         let result = unsafe { Vec::from_raw_parts(self.ptr as *mut T, bytes / std::mem::size_of::<T>(), self.capacity / std::mem::size_of::<T>()) };
 
         // self.ptr is still set but ownership is transfered to return value, so need to clear it to avoid double free in a destructor
@@ -144,7 +181,7 @@ impl Buffer {
 impl Drop for Buffer {
     fn drop(&mut self) {
         unsafe {
-            if !self.ptr.is_null() {
+            if self.owns_allocation() {
                 std::alloc::dealloc(self.ptr, self.layout)
             }
         }
@@ -192,33 +229,66 @@ pub enum IOUringOp {
 
     Nop(),
     Close(MaybeFd),                    // fd
-    Open(CString, i32, u32),           // path, flags, mode
+    Open(i32, CString, i32, u32),      // dirfd, path, flags, mode
     Read(i32, Buffer, Option<u64>),    // fd, buffer, offset
     Write(i32, Buffer, Option<u64>),   // fd, buffer, offset
     Socket(i32, i32, i32),
     Accept(i32, i32),
+    AcceptFrom(i32, i32),              // fd, flags - peer address is stored in op parameters
     Connect(i32, SocketIpAddress),
     Sleep(Duration),
     Cancel(u64, usize),
     SleepUpdate((u64, usize), Duration),
     Poll(i32, PollMask),
     PollUpdate((u64, usize), PollMask),
+    Fsync(i32, bool),                  // fd, data only
+    Rename(CString, CString),          // old path, new path
+    ReadFixed(i32, u16, u32, Option<u64>),     // fd, registered buffer index, length, offset
+    WriteFixed(i32, u16, u32, Option<u64>),    // fd, registered buffer index, length, offset
+    Splice(i32, Option<i64>, i32, Option<i64>, u32, u32),  // fd in, offset in, fd out, offset out, length, flags
+    Shutdown(i32, i32),                // fd, how
+    PollMultishot(i32, PollMask),      // fd, mask
+    Fallocate(i32, i32, u64, u64),     // fd, mode, offset, length
+    Fadvise(i32, u64, u64, i32),       // fd, offset, length, advice
+    Madvise(usize, u64, i32),          // address, length, advice
+    WaitId(u32, u32, i32),             // idtype, id, options - child info is stored in op parameters
+    RecvFrom(i32, Buffer, i32),        // fd, buffer, flags - peer address is stored in op parameters
+    SendTo(i32, Buffer, SocketIpAddress, i32),     // fd, buffer, destination, flags
+    Tee(i32, i32, u32, u32),           // fd in, fd out, length, flags
 }
 
 #[derive(Default)]
 pub struct ReactorOpParameters {
     timeout: __kernel_timespec,
     path: CString,
+    target_path: CString,
     address: SocketAddressBinary,
+    address_length: libc::socklen_t,
+    siginfo: Option<Box<libc::siginfo_t>>,
+    message: Option<Box<(libc::msghdr, libc::iovec)>>,
     pub buffer: Buffer,
+    pub timed_out: bool,               // op was cancelled by its linked timeout, not by cancel request
 }
 
 impl ReactorOpParameters {
+    pub fn address(&self) -> &SocketAddressBinary {
+        &self.address
+    }
+
+    pub fn siginfo(&self) -> Option<&libc::siginfo_t> {
+        self.siginfo.as_deref()
+    }
+
     fn reset(&mut self) {
         self.timeout = unsafe { std::mem::zeroed() };
         self.address = SocketAddressBinary::default();
+        self.address_length = 0;
+        self.siginfo = None;
+        self.message = None;
         self.buffer.clear();
         self.path = CString::default();
+        self.target_path = CString::default();
+        self.timed_out = false;
     }
 }
 
@@ -230,22 +300,34 @@ enum OpState {
 
 struct ReactorOp {
     state: OpState,
+    multishot: OpMultishotCompletion,
     parameters: ReactorOpParameters,
     seq: u64,
+    has_timeout: bool,
+    cancel_requested: bool,
+    chain_failed: Option<Rc<Cell<bool>>>,  // shared by links of one chain, set once any of them fails
 }
 
 impl ReactorOp {
     fn new(seq: u64) -> Self {
         ReactorOp {
             state: OpState::Unscheduled(),
+            multishot: None,
             parameters: ReactorOpParameters::default(),
             seq,
+            has_timeout: false,
+            cancel_requested: false,
+            chain_failed: None,
         }
     }
 
     fn reset(&mut self) {
         self.state = OpState::Unscheduled();
+        self.multishot = None;
         self.parameters.reset();
+        self.has_timeout = false;
+        self.cancel_requested = false;
+        self.chain_failed = None;
     }
 }
 
@@ -258,6 +340,12 @@ impl ReactorOpPtr {
         ReactorOpPtr { ptr: Box::new(ReactorOp::new(seq)) }
     }
 
+    fn notify_op(&mut self, cqe: IoUringCQE) {
+        if let Some(multishot) = &mut self.ptr.multishot {
+            multishot(cqe);
+        }
+    }
+
     fn complete_op(&mut self, cqe: IoUringCQE, params: ReactorOpParameters) {
         let completion = std::mem::replace(&mut self.ptr.state, OpState::Completed());
         if let OpState::Scheduled(Some(completion)) = completion {
@@ -274,6 +362,17 @@ impl ReactorOpPtr {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReactorStats {
+    pub in_flight: u32,
+    pub ops_table_size: usize,
+    pub ops_table_capacity: usize,
+    pub ops_free_entries: usize,
+    pub rop_cache_size: usize,
+    pub cq_overflows: u64,
+    pub last_error: Option<ReactorError>,
+}
+
 pub struct Reactor {
     ring: IoUring,
     ops: Vec<Option<ReactorOpPtr>>,
@@ -282,6 +381,10 @@ pub struct Reactor {
     uncommited: u32,
     rop_cache: Vec<ReactorOpPtr>,
     seq: u64,
+    registered_buffers: Vec<libc::iovec>,
+    registered_files: usize,
+    cq_overflows: u64,
+    last_error: Option<ReactorError>,
 }
 
 impl Debug for Reactor {
@@ -293,18 +396,90 @@ impl Debug for Reactor {
             .field("uncommited", &self.uncommited)
             .field("rop_cache", &self.rop_cache.len())
             .field("seq", &self.seq)
+            .field("registered_buffers", &self.registered_buffers.len())
+            .field("registered_files", &self.registered_files)
+            .field("cq_overflows", &self.cq_overflows)
+            .field("last_error", &self.last_error)
             .finish()
     }
 }
 
 impl Reactor {
     pub fn new() -> Result<Self, IoUringCreateError> {
-        let params = IoUringParams {
-            sq_entries: 16,
-            cq_entries: 64,
+        Self::with_params(IoUringParams::default())
+    }
+
+    // When SQPOLL is requested, but kernel doesn't support it or process lacks privileges, reactor
+    // falls back to regular submission, is_sq_polling() tells which mode is active
+    pub fn with_params(params: IoUringParams) -> Result<Self, IoUringCreateError> {
+        let ring = match IoUring::new(params) {
+            Err(IoUringCreateError::InsufficientPrivileges | IoUringCreateError::InvalidArguments) if params.sq_poll_idle.is_some() => {
+                IoUring::new(IoUringParams { sq_poll_idle: None, ..params })?
+            },
+            result => result?,
         };
 
-        Ok(Reactor { ring: IoUring::new(params)?, ops: vec![], ops_free_entries: vec![], in_flight: 0, uncommited: 0, rop_cache: vec![], seq: 0 })
+        Ok(Reactor { ring, ops: vec![], ops_free_entries: vec![], in_flight: 0, uncommited: 0, rop_cache: vec![], seq: 0, registered_buffers: vec![], registered_files: 0, cq_overflows: 0, last_error: None })
+    }
+
+    pub fn is_sq_polling(&self) -> bool {
+        self.ring.is_sq_polling()
+    }
+
+    // Registered buffers are pinned by the kernel and referenced by index from ReadFixed/WriteFixed.
+    // Caller must keep them alive and unmoved until unregister_buffers is called or reactor is dropped,
+    // to resize a buffer it has to be unregistered and registered again.
+    pub unsafe fn register_buffers(&mut self, buffers: &[&mut [u8]]) -> Result<(), IoUringError> {
+        let iovecs: Vec<libc::iovec> = buffers.iter().map(|buffer| libc::iovec {
+            iov_base: buffer.as_ptr() as *mut libc::c_void,
+            iov_len: buffer.len() as _,
+        }).collect();
+
+        self.ring.register_buffers(&iovecs)?;
+        self.registered_buffers = iovecs;
+        Ok(())
+    }
+
+    pub fn unregister_buffers(&mut self) -> Result<(), IoUringError> {
+        if self.registered_buffers.is_empty() {
+            return Ok(());
+        }
+
+        self.ring.unregister_buffers()?;
+        self.registered_buffers.clear();
+        Ok(())
+    }
+
+    // Fixed files are referenced by index in the table instead of fd, which saves fd refcounting
+    // on each op. update_files replaces entries in place, e.g. after reconnecting a socket.
+    // Kernel refuses second table with EBUSY, so previous one is unregistered first.
+    pub fn register_files(&mut self, files: &[RawFd]) -> Result<(), IoUringError> {
+        self.unregister_files()?;
+        self.ring.register_files(files)?;
+        self.registered_files = files.len();
+        Ok(())
+    }
+
+    pub fn update_files(&mut self, offset: u32, files: &[RawFd]) -> Result<(), IoUringError> {
+        self.ring.update_files(offset, files)
+    }
+
+    pub fn unregister_files(&mut self) -> Result<(), IoUringError> {
+        if self.registered_files == 0 {
+            return Ok(());
+        }
+
+        self.ring.unregister_files()?;
+        self.registered_files = 0;
+        Ok(())
+    }
+
+    // Unknown index gives null address, for which kernel completes op with EFAULT
+    fn registered_buffer(&self, index: u16) -> *mut libc::c_void {
+        match self.registered_buffers.get(index as usize) {
+            None => std::ptr::null_mut(),
+            Some(iovec) => iovec.iov_base as *mut libc::c_void,
+        }
     }
 
     pub fn is_supported(&self, opcode: u32) -> bool {
@@ -348,6 +523,10 @@ impl Reactor {
         self.submit().expect("Error on submit");
     }
 
+    pub fn is_op_in_flight(&self, token: (u64, usize)) -> bool {
+        self.cancel_token_is_valid(token.0, token.1)
+    }
+
     fn cancel_token_is_valid(&self, seq: u64, index: usize) -> bool {
         if self.ops.len() <= index {
             return false;
@@ -363,6 +542,10 @@ impl Reactor {
     }
 
     fn enqueue_cancel(&mut self, index: usize) {
+        if let Some(Some(rop)) = self.ops.get_mut(index) {
+            rop.ptr.cancel_requested = true;
+        }
+
         let sqe = self.get_sqe().expect("Can't get SQE from io_uring");
 
         unsafe {
@@ -385,6 +568,7 @@ impl Reactor {
 
         self.in_flight += ops_count;
 
+        let chain_failed = Rc::new(Cell::new(false));
         ops.into_iter().enumerate().for_each(|(op_index, req)| {
             let op_index = op_index as u32;
             let sqe = self.get_sqe().expect("Can't get SQE from io_uring");
@@ -402,10 +586,10 @@ impl Reactor {
                     IOUringOp::Close(ref mut fd) => {
                         io_uring_prep_close(sqe.ptr, fd.take_fd());
                     },
-                    IOUringOp::Open(path, flags, mode) => {
+                    IOUringOp::Open(dirfd, path, flags, mode) => {
                         parameters.path = path;
 
-                        io_uring_prep_openat(sqe.ptr, libc::AT_FDCWD, parameters.path.as_ptr(), flags, mode);
+                        io_uring_prep_openat(sqe.ptr, dirfd, parameters.path.as_ptr(), flags, mode);
                     },
                     IOUringOp::Read(fd, buffer, offset) => {
                         parameters.buffer = buffer;
@@ -423,6 +607,11 @@ impl Reactor {
                     IOUringOp::Accept(fd, flags) => {
                         io_uring_prep_accept(sqe.ptr, fd, std::ptr::null_mut(), std::ptr::null_mut(), flags);
                     },
+                    IOUringOp::AcceptFrom(fd, flags) => {
+                        parameters.address_length = std::mem::size_of::<SocketAddressBinary>() as libc::socklen_t;
+
+                        io_uring_prep_accept(sqe.ptr, fd, parameters.address.sockaddr_ptr_mut(), &mut parameters.address_length, flags);
+                    },
                     IOUringOp::Connect(fd, address) => {
                         parameters.address = address.to_binary();
 
@@ -466,16 +655,104 @@ impl Reactor {
 
                         io_uring_prep_poll_update(sqe.ptr, user_data, 0, mask.into(), IORING_POLL_UPDATE_EVENTS);
                     },
+                    IOUringOp::Fsync(fd, data_only) => {
+                        let flags = match data_only {
+                            true => IORING_FSYNC_DATASYNC,
+                            false => 0,
+                        };
+
+                        io_uring_prep_fsync(sqe.ptr, fd, flags);
+                    },
+                    IOUringOp::Rename(path, target_path) => {
+                        parameters.path = path;
+                        parameters.target_path = target_path;
+
+                        io_uring_prep_renameat(sqe.ptr, libc::AT_FDCWD, parameters.path.as_ptr(), libc::AT_FDCWD, parameters.target_path.as_ptr(), 0);
+                    },
+                    IOUringOp::ReadFixed(fd, buffer_index, length, offset) => {
+                        let buffer = self.registered_buffer(buffer_index);
+
+                        io_uring_prep_read_fixed(sqe.ptr, fd, buffer, length, offset.unwrap_or(u64::MAX), buffer_index as i32);
+                    },
+                    IOUringOp::WriteFixed(fd, buffer_index, length, offset) => {
+                        let buffer = self.registered_buffer(buffer_index);
+
+                        io_uring_prep_write_fixed(sqe.ptr, fd, buffer, length, offset.unwrap_or(u64::MAX), buffer_index as i32);
+                    },
+                    IOUringOp::Splice(fd_in, offset_in, fd_out, offset_out, length, flags) => {
+                        // -1 means current file position, required for pipe end
+                        io_uring_prep_splice(sqe.ptr, fd_in, offset_in.unwrap_or(-1), fd_out, offset_out.unwrap_or(-1), length, flags);
+                    },
+                    IOUringOp::Shutdown(fd, how) => {
+                        io_uring_prep_shutdown(sqe.ptr, fd, how);
+                    },
+                    IOUringOp::PollMultishot(fd, mask) => {
+                        io_uring_prep_poll_multishot(sqe.ptr, fd, mask.into());
+                    },
+                    IOUringOp::Fallocate(fd, mode, offset, length) => {
+                        io_uring_prep_fallocate(sqe.ptr, fd, mode, offset, length);
+                    },
+                    IOUringOp::Fadvise(fd, offset, length, advice) => {
+                        io_uring_prep_fadvise(sqe.ptr, fd, offset, length as _, advice);
+                    },
+                    IOUringOp::Madvise(address, length, advice) => {
+                        io_uring_prep_madvise(sqe.ptr, address as *mut libc::c_void, length as _, advice);
+                    },
+                    IOUringOp::WaitId(idtype, id, options) => {
+                        // boxed, so kernel pointer stays valid even if parameters are moved
+                        let siginfo = parameters.siginfo.insert(Box::new(std::mem::zeroed()));
+
+                        io_uring_prep_waitid(sqe.ptr, idtype as _, id as _, (siginfo.as_mut() as *mut libc::siginfo_t).cast(), options, 0);
+                    },
+                    IOUringOp::RecvFrom(fd, buffer, flags) => {
+                        parameters.buffer = buffer;
+
+                        let message: &mut (libc::msghdr, libc::iovec) = parameters.message.insert(Box::new(std::mem::zeroed()));
+                        message.1.iov_base = parameters.buffer.as_mut_ptr() as *mut libc::c_void;
+                        message.1.iov_len = parameters.buffer.capacity();
+                        message.0.msg_name = parameters.address.sockaddr_ptr_mut() as *mut libc::c_void;
+                        message.0.msg_namelen = std::mem::size_of::<SocketAddressBinary>() as libc::socklen_t;
+                        message.0.msg_iov = &mut message.1;
+                        message.0.msg_iovlen = 1;
+
+                        io_uring_prep_recvmsg(sqe.ptr, fd, (&mut message.0 as *mut libc::msghdr).cast(), flags as u32);
+                    },
+                    IOUringOp::SendTo(fd, buffer, destination, flags) => {
+                        parameters.buffer = buffer;
+                        parameters.address = destination.to_binary();
+
+                        let message: &mut (libc::msghdr, libc::iovec) = parameters.message.insert(Box::new(std::mem::zeroed()));
+                        message.1.iov_base = parameters.buffer.as_ptr() as *mut libc::c_void;
+                        message.1.iov_len = parameters.buffer.size();
+                        message.0.msg_name = parameters.address.sockaddr_ptr() as *mut libc::c_void;
+                        message.0.msg_namelen = parameters.address.length() as libc::socklen_t;
+                        message.0.msg_iov = &mut message.1;
+                        message.0.msg_iovlen = 1;
+
+                        io_uring_prep_sendmsg(sqe.ptr, fd, (&message.0 as *const libc::msghdr).cast(), flags as u32);
+                    },
+                    IOUringOp::Tee(fd_in, fd_out, length, flags) => {
+                        io_uring_prep_tee(sqe.ptr, fd_in, fd_out, length, flags);
+                    },
                     IOUringOp::InProgress(_) => panic!("op already scheduled"),
                 }
 
                 rop.ptr.state = OpState::Scheduled(req.completion.take());
+                rop.ptr.multishot = req.multishot.take();
 
                 let mut flags = 0;
                 if op_index != ops_count - 1 || req.timeout.is_some() {
                     flags |= IOSQE_IO_LINK;
                 }
 
+                if req.fixed_file {
+                    flags |= IOSQE_FIXED_FILE;
+                }
+
+                if req.drain {
+                    flags |= IOSQE_IO_DRAIN;
+                }
+
                 io_uring_sqe_set_data64(sqe.ptr, index as u64);
                 io_uring_sqe_set_flags(sqe.ptr, flags);
 
@@ -484,6 +761,11 @@ impl Reactor {
                 }
             }
 
+            rop.ptr.has_timeout = req.timeout.is_some();
+            if ops_count > 1 {
+                rop.ptr.chain_failed = Some(chain_failed.clone());
+            }
+
             self.ops[index] = Some(rop);
         });
 
@@ -515,6 +797,48 @@ impl Reactor {
         self.in_flight
     }
 
+    pub fn stats(&self) -> ReactorStats {
+        ReactorStats {
+            in_flight: self.in_flight,
+            ops_table_size: self.ops.len(),
+            ops_table_capacity: self.ops.capacity(),
+            ops_free_entries: self.ops_free_entries.len(),
+            rop_cache_size: self.rop_cache.len(),
+            cq_overflows: self.cq_overflows,
+            last_error: self.last_error,
+        }
+    }
+
+    // Resets error reported in stats, so next overflow can be told apart from the previous one
+    pub fn clear_last_error(&mut self) {
+        self.last_error = None;
+    }
+
+    // Releases memory retained after load spike. Only unused tail of the table can be dropped,
+    // indexes of in-flight ops are stored in submitted SQEs and can't be moved.
+    fn compact_ops(&mut self) {
+        if self.ops.len() <= OPS_TABLE_MIN_SIZE || (self.in_flight as usize) * 4 > self.ops.len() {
+            return;
+        }
+
+        let size_before = self.ops.len();
+        while let Some(None) = self.ops.last() {
+            self.ops.pop();
+        }
+
+        let size = self.ops.len();
+        if size == size_before {
+            return;
+        }
+
+        let capacity = std::cmp::max(size, OPS_TABLE_MIN_SIZE);
+        self.ops_free_entries.retain(|index| *index < size);
+        self.ops_free_entries.shrink_to(capacity);
+        self.ops.shrink_to(capacity);
+        self.rop_cache.truncate(capacity);
+        self.rop_cache.shrink_to(capacity);
+    }
+
     fn get_sqe(&mut self) -> Result<IoUringSQEPtr, ReactorError> {
         let result = self.ring.get_sqe().ok_or_else(|| ReactorError::NoSQEAvailable);
         if result.is_ok() {
@@ -527,6 +851,11 @@ impl Reactor {
     pub fn submit(&mut self) -> Result<i32, IoUringError> {
         let mut result = 0;
 
+        // older kernels refuse to submit with EBUSY until overflowed completions are reaped
+        if self.ring.cq_has_overflow() {
+            self.process_completed_ops()?;
+        }
+
         if self.uncommited > 0 {
             result = self.ring.submit()?;
             self.uncommited = 0;
@@ -537,26 +866,56 @@ impl Reactor {
 
     pub fn process_ops(&mut self) -> Result<bool, IoUringError> {
         if self.in_flight == 0 {
+            self.compact_ops();
             return Ok(false);
         }
 
-        let handled = self.process_completed_ops();
+        let handled = self.process_completed_ops()?;
         if !handled {
             self.submit()?;
             self.wait_for_completion()?;
         }
 
+        self.compact_ops();
         Ok(true)
     }
 
-    fn process_completed_ops(&mut self) -> bool {
+    // Same as process_ops, but never blocks waiting for completions. Returns true when any
+    // completion was handled.
+    pub fn poll_ops(&mut self) -> Result<bool, IoUringError> {
+        if self.in_flight == 0 {
+            return Ok(false);
+        }
+
+        self.submit()?;
+        self.process_completed_ops()
+    }
+
+    fn process_completed_ops(&mut self) -> Result<bool, IoUringError> {
         let mut handled = false;
-        while let Some(cqe) = self.ring.peek_cqe() {
-            self.process_cqe(cqe);
-            handled = true;
+        let mut overflowed = false;
+        loop {
+            while let Some(cqe) = self.ring.peek_cqe() {
+                self.process_cqe(cqe);
+                handled = true;
+            }
+
+            // ring has been drained, so there's room for completions kernel kept aside
+            if !self.ring.cq_has_overflow() {
+                break;
+            }
+
+            // overflow may take several passes to drain, it's still counted once
+            if !overflowed {
+                overflowed = true;
+                self.cq_overflows += 1;
+                self.last_error = Some(ReactorError::CompletionOverflow);
+            }
+
+            self.ring.get_events()?;
         }
 
-        handled
+        Ok(handled)
     }
 
     fn process_cqe(&mut self, cqe: IoUringCQEPtr) {
@@ -567,6 +926,11 @@ impl Reactor {
             CQE_TIMEOUT_CQE => (),
             CQE_CANCEL_CQE => (),
             CQE_INVALID => (),
+            // op stays armed, so its slot can't be released yet
+            index if cqe.get_flags() & IORING_CQE_F_MORE != 0 => {
+                let rop = self.ops[index as usize].as_mut().expect("io_uring returned multishot op with incorrect index");
+                rop.notify_op(cqe.copy_from());
+            },
             index => {
                 let index = index as usize;
                 let mut rop = self.ops[index].take().expect("io_uring returned completed op with incorrect index");
@@ -574,8 +938,20 @@ impl Reactor {
                 self.in_flight -= 1;
                 self.ops_free_entries.push(index);
 
-                let params = std::mem::take(&mut rop.ptr.parameters);
-                rop.complete_op(cqe.copy_from(), params);
+                let cqe_data = cqe.copy_from();
+                let mut params = std::mem::take(&mut rop.ptr.parameters);
+
+                // Linked timeout completes target op with ECANCELED, same as explicit cancel does. Links
+                // complete in chain order, so link cancelled because earlier one failed sees the flag set.
+                let chain_failed = rop.ptr.chain_failed.take();
+                let earlier_link_failed = chain_failed.as_ref().is_some_and(|failed| failed.get());
+                params.timed_out = cqe_data.result == -libc::ECANCELED && rop.ptr.has_timeout && !rop.ptr.cancel_requested && !earlier_link_failed;
+
+                if let (Some(failed), true) = (chain_failed, cqe_data.result < 0) {
+                    failed.set(true);
+                }
+
+                rop.complete_op(cqe_data, params);
                 self.retire_rop(rop);
             },
         }
@@ -590,3 +966,57 @@ impl Reactor {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run under Miri to verify allocation bookkeeping: cargo +nightly miri test -p fbs-reactor buffer
+    #[test]
+    fn buffer_from_vec_drop_test() {
+        let mut data: Vec<u32> = Vec::with_capacity(17);
+        data.extend_from_slice(&[1, 2, 3]);
+
+        let buffer = Buffer::from_vec(data);
+        assert_eq!(buffer.size(), 3 * std::mem::size_of::<u32>());
+        assert_eq!(buffer.capacity(), 17 * std::mem::size_of::<u32>());
+        drop(buffer);
+
+        let mut buffer = Buffer::from_vec::<u32>(Vec::new());
+        buffer.clear();
+        drop(buffer);
+    }
+
+    #[test]
+    fn buffer_from_vec_round_trip_test() {
+        let data: Vec<u32> = vec![0xDEADBEEF, 0x01020304];
+        let buffer = Buffer::from_vec(data);
+
+        let data = unsafe { buffer.to_vec::<u32>(2 * std::mem::size_of::<u32>()) };
+        assert_eq!(data, vec![0xDEADBEEF, 0x01020304]);
+    }
+    #[test]
+    fn sq_poll_test() {
+        // works in both modes, unprivileged run falls back to regular submission
+        let mut reactor = Reactor::with_params(IoUringParams { sq_poll_idle: Some(10), ..IoUringParams::default() }).unwrap();
+
+        let result = std::rc::Rc::new(std::cell::Cell::new(None));
+        let result_clone = result.clone();
+
+        let mut req = IOUringReq {
+            op: IOUringOp::Nop(),
+            completion: Some(Box::new(move |cqe, _| result_clone.set(Some(cqe.result)))),
+            multishot: None,
+            timeout: None,
+            fixed_file: false,
+            drain: false,
+        };
+
+        reactor.schedule_linked2(std::slice::from_mut(&mut &mut req));
+        while result.get().is_none() {
+            assert!(reactor.process_ops().unwrap());
+        }
+
+        assert_eq!(result.get(), Some(0));
+        assert_eq!(reactor.pending_ops(), 0);
+    }
+}
@@ -1,5 +1,4 @@
 #![allow(nonstandard_style)]
-use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::task::{Context, Poll};
 use std::pin::Pin;
@@ -7,12 +6,16 @@ use std::future::Future;
 use std::mem::MaybeUninit;
 use std::sync::{Arc, Mutex};
 use std::num::ParseIntError;
+use std::time::Duration;
 
 use fbs_library::eventfd::{EventFd, EventFdFlags};
 use fbs_library::ip_address::IpAddress;
+use fbs_library::socket::{Socket, SocketDomain, SocketType, SocketFlags};
 use fbs_library::socket_address::SocketIpAddress;
+use fbs_library::system_error::SystemError;
 
-use fbs_runtime::{AsyncReadStruct, async_read_struct};
+use fbs_runtime::{AsyncReadStruct, async_read_struct, async_connect_timeout, async_sleep, async_spawn};
+use fbs_runtime::async_utils::async_channel_create;
 
 use libc::{timespec, addrinfo, sigval, SIGEV_THREAD};
 use libc::pthread_attr_t;
@@ -20,6 +23,11 @@ use thiserror::Error;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+mod nameserver;
+
+// Delay before next address is tried while previous attempts are still in progress, RFC 8305
+const CONNECT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 fn gai_code_to_error(code: libc::c_int) -> String {
     unsafe { CStr::from_ptr(gai_strerror(code)).to_string_lossy().into_owned() }
 }
@@ -85,7 +93,11 @@ impl DnsResult {
 
 pub struct DnsQuery {
     domain: String,
-    internal: Arc<Mutex<GaiInnerData>>,
+    flags: DnsQueryFlags,
+    // created on first poll, so queries sent directly to nameserver don't allocate eventfd
+    internal: Option<Arc<Mutex<GaiInnerData>>>,
+    server: Option<SocketIpAddress>,
+    server_query: Option<Pin<Box<dyn Future<Output = Result<DnsResult, ResolverError>>>>>,
 }
 
 #[repr(C)]
@@ -119,8 +131,14 @@ impl GaiInnerData {
         }
     }
 
+    // Order returned by getaddrinfo is preserved, it is already sorted by preference (RFC 6724)
     fn get_result(&mut self) -> Result<DnsResult, ResolverError> {
-        let mut result: HashSet<IpAddress> = HashSet::new();
+        let mut result: Vec<IpAddress> = vec![];
+        let mut insert = |address: IpAddress| {
+            if !result.contains(&address) {
+                result.push(address);
+            }
+        };
 
         unsafe {
             let mut ptr = self.0.ar_result;
@@ -139,13 +157,13 @@ impl GaiInnerData {
                     libc::AF_INET => {
                         if self.3.return_ipv4 {
                             let inet4_ptr = (*ptr).ai_addr as *mut libc::sockaddr_in;
-                            result.insert(IpAddress::from_inet4(&(*inet4_ptr).sin_addr));
+                            insert(IpAddress::from_inet4(&(*inet4_ptr).sin_addr));
                         }
                     },
                     libc::AF_INET6 => {
                         if self.3.return_ipv6 {
                             let inet6_ptr = (*ptr).ai_addr as *mut libc::sockaddr_in6;
-                            result.insert(IpAddress::from_inet6(&(*inet6_ptr).sin6_addr));
+                            insert(IpAddress::from_inet6(&(*inet6_ptr).sin6_addr));
                         }
                     },
                     _ => (),
@@ -162,7 +180,7 @@ impl GaiInnerData {
             return Err(ResolverError::NoRecord);
         }
 
-        Ok(DnsResult { addresses: result.into_iter().collect() })
+        Ok(DnsResult { addresses: result })
     }
 }
 
@@ -184,9 +202,18 @@ impl DnsQuery {
     pub fn new(domain: String, flags: DnsQueryFlags) -> Self {
         Self {
             domain,
-            internal: Arc::new(Mutex::new(GaiInnerData::new(flags))),
+            flags,
+            internal: None,
+            server: None,
+            server_query: None,
         }
     }
+
+    // Bypasses system resolver configuration and queries given nameserver directly over UDP
+    pub fn with_server(mut self, server: SocketIpAddress) -> Self {
+        self.server = Some(server);
+        self
+    }
 }
 
 impl Default for DnsQuery {
@@ -198,9 +225,23 @@ impl Default for DnsQuery {
 impl Future for DnsQuery {
     type Output = Result<DnsResult, ResolverError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(server) = self.server {
+            if self.server_query.is_none() {
+                let query = nameserver::query_nameserver(self.domain.clone(), self.flags, server);
+                self.server_query = Some(Box::pin(query));
+            }
+
+            return self.server_query.as_mut().unwrap().as_mut().poll(cx);
+        }
+
+        if self.internal.is_none() {
+            self.internal = Some(Arc::new(Mutex::new(GaiInnerData::new(self.flags))));
+        }
+
         let inner = self.as_ref().get_ref();
-        let mut gai_data = inner.internal.lock().unwrap();
+        let internal = inner.internal.as_ref().unwrap();
+        let mut gai_data = internal.lock().unwrap();
 
         if !gai_data.is_filled() {
             gai_data.fill(inner);
@@ -210,7 +251,7 @@ impl Future for DnsQuery {
                 handler.sigev_notify = SIGEV_THREAD;
                 handler._sigev_un._sigev_thread._function = Some(sigev_notifier);
                 handler._sigev_un._sigev_thread._attribute = std::ptr::null_mut();
-                handler.sigev_value.sival_ptr = Arc::into_raw(self.internal.clone()) as *mut libc::c_void;
+                handler.sigev_value.sival_ptr = Arc::into_raw(internal.clone()) as *mut libc::c_void;
 
                 let mut entries = &mut gai_data.0 as *mut gaicb;
                 let result = getaddrinfo_a(GAI_NOWAIT as i32, &mut entries as *mut *mut gaicb, 1, &mut handler);
@@ -277,6 +318,111 @@ pub async fn resolve_address(address: &str, default_port: Option<u16>) -> Result
     Ok(SocketIpAddress::from_ip_address(result.one_record(), port))
 }
 
+#[derive(Error, Debug, Clone)]
+pub enum ConnectResolvedError {
+    #[error("Resolver error")]
+    ResolverError(#[from] ResolverError),
+    #[error("Connect error - {0}")]
+    ConnectError(SystemError),     // error of the last failed attempt
+}
+
+enum ConnectEvent {
+    Connected(Socket),
+    Failed(SystemError),
+    AttemptDelay(usize),           // index of attempt which was started together with the delay
+}
+
+// Alternates address families, starting with the preferred one
+fn interleave_families(addresses: Vec<IpAddress>) -> Vec<IpAddress> {
+    let first_is_ipv6 = addresses.first().is_some_and(|address| address.is_ipv6());
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addresses.into_iter().partition(|address| address.is_ipv6() == first_is_ipv6);
+    preferred.reverse();
+    other.reverse();
+
+    let mut result = vec![];
+    while let Some(address) = preferred.pop() {
+        result.push(address);
+        if let Some(address) = other.pop() {
+            result.push(address);
+        }
+    }
+
+    result.extend(other.into_iter().rev());
+    result
+}
+
+// Address silently dropping SYNs would otherwise keep attempt pending until kernel gives up (minutes),
+// expired timeout is reported as ETIMEDOUT and counts as failed attempt
+async fn connect_attempt(address: SocketIpAddress, timeout: Duration) -> Result<Socket, SystemError> {
+    let domain = match address.address().is_ipv4() {
+        true => SocketDomain::Inet,
+        false => SocketDomain::Inet6,
+    };
+
+    let socket = Socket::new(domain, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+    async_connect_timeout(&socket, address, timeout).await.map(|_| socket)
+}
+
+// Resolves both A and AAAA records and connects to them Happy Eyeballs style (RFC 8305): next
+// address is tried when previous attempt fails or doesn't finish within 250ms, first established
+// connection wins and remaining attempts are cancelled. Each attempt is bounded by attempt_timeout.
+pub async fn async_connect_resolved(host: &str, port: u16, attempt_timeout: Duration) -> Result<Socket, ConnectResolvedError> {
+    let addresses = match IpAddress::from_text(host) {
+        Ok(address) => vec![address],
+        Err(_) => {
+            let query = DnsQuery::new(host.to_string(), DnsQueryFlags::default().return_ipv4(true).return_ipv6(true));
+            interleave_families(query.await?.all_record())
+        },
+    };
+
+    let (events, tx) = async_channel_create();
+
+    // dropping handles cancels attempts and delays which are still in flight
+    let mut tasks = vec![];
+    let mut next = 0;
+    let mut pending = 0;
+    let mut start_next = true;
+    let mut last_error = SystemError::new(libc::ECONNREFUSED);
+
+    loop {
+        if start_next && next < addresses.len() {
+            let address = SocketIpAddress::from_ip_address(addresses[next], port);
+            let attempt_tx = tx.clone();
+            tasks.push(async_spawn(async move {
+                match connect_attempt(address, attempt_timeout).await {
+                    Ok(socket) => attempt_tx.send(ConnectEvent::Connected(socket)),
+                    Err(error) => attempt_tx.send(ConnectEvent::Failed(error)),
+                }
+            }));
+
+            let delay_tx = tx.clone();
+            let attempt = next;
+            tasks.push(async_spawn(async move {
+                async_sleep(CONNECT_ATTEMPT_DELAY).await;
+                delay_tx.send(ConnectEvent::AttemptDelay(attempt));
+            }));
+
+            next += 1;
+            pending += 1;
+        }
+
+        if pending == 0 {
+            return Err(ConnectResolvedError::ConnectError(last_error));
+        }
+
+        start_next = match events.receive().await {
+            ConnectEvent::Connected(socket) => return Ok(socket),
+            ConnectEvent::Failed(error) => {
+                pending -= 1;
+                last_error = error;
+                true
+            },
+            // stale delay, next attempt was already started because previous one failed
+            ConnectEvent::AttemptDelay(attempt) => attempt + 1 == next,
+        };
+    }
+}
+
 #[cfg(test)]
 mod test {
     use fbs_runtime::async_run;
@@ -315,6 +461,20 @@ mod test {
         });
     }
 
+    // needs access to public nameserver
+    #[test]
+    #[ignore]
+    fn async_resolver_with_server_test() {
+        async_run(async {
+            let server = SocketIpAddress::from_text("8.8.8.8:53", None).unwrap();
+            let query = DnsQuery::new("google.com".to_string(), DnsQueryFlags::default()).with_server(server);
+            let result = query.await;
+
+            let result = result.unwrap();
+            assert!(result.all_record().iter().all(|address| address.is_ipv4()));
+        });
+    }
+
     #[test]
     fn resolve_address_test1() {
         async_run(async {
@@ -352,4 +512,40 @@ mod test {
             assert!(address.is_err());
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn interleave_families_test() {
+        let v4a = IpAddress::from_text("10.0.0.1").unwrap();
+        let v4b = IpAddress::from_text("10.0.0.2").unwrap();
+        let v6a = IpAddress::from_text("fd00::1").unwrap();
+        let v6b = IpAddress::from_text("fd00::2").unwrap();
+        let v6c = IpAddress::from_text("fd00::3").unwrap();
+
+        assert_eq!(interleave_families(vec![v6a, v6b, v6c, v4a, v4b]), vec![v6a, v4a, v6b, v4b, v6c]);
+        assert_eq!(interleave_families(vec![v4a, v4b, v6a]), vec![v4a, v6a, v4b]);
+    }
+
+    #[test]
+    fn async_connect_resolved_test() {
+        use fbs_library::socket::SocketOptions;
+
+        // listening on IPv4 only, so attempt to ::1 (if localhost has it) fails and IPv4 one wins
+        let listener = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        listener.set_option(SocketOptions::ReuseAddr(true)).unwrap();
+        listener.listen(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap(), 10).unwrap();
+        let port = listener.local_address().unwrap().port();
+
+        // bound but not listening, so connection is refused
+        let closed = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+        closed.bind(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap()).unwrap();
+        let closed_port = closed.local_address().unwrap().port();
+
+        async_run(async move {
+            let socket = async_connect_resolved("localhost", port, Duration::new(1, 0)).await;
+            assert!(socket.is_ok());
+
+            let socket = async_connect_resolved("127.0.0.1", closed_port, Duration::new(1, 0)).await;
+            assert!(matches!(socket, Err(ConnectResolvedError::ConnectError(error)) if error.errno() == libc::ECONNREFUSED));
+        });
+    }
+}
@@ -0,0 +1,344 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fbs_library::ip_address::IpAddress;
+use fbs_library::socket::{Socket, SocketDomain, SocketType, SocketFlags};
+use fbs_library::socket_address::SocketIpAddress;
+use fbs_runtime::{async_connect, async_write, async_read_into, async_close, AsyncBufReader, AsyncBufReadError};
+
+use super::{DnsQueryFlags, DnsResult, ResolverError};
+
+const DNS_HEADER_SIZE: usize = 12;
+const DNS_MAX_UDP_SIZE: usize = 512;
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_CLASS_IN: u16 = 1;
+
+const DNS_FLAG_RESPONSE: u16 = 1 << 15;
+const DNS_FLAG_TRUNCATED: u16 = 1 << 9;
+const DNS_FLAG_RECURSION_DESIRED: u16 = 1 << 8;
+const DNS_RCODE_MASK: u16 = 0x000F;
+
+const DNS_RCODE_SERVER_FAILURE: u16 = 2;
+const DNS_RCODE_NAME_ERROR: u16 = 3;
+
+pub(super) async fn query_nameserver(domain: String, flags: DnsQueryFlags, server: SocketIpAddress) -> Result<DnsResult, ResolverError> {
+    let mut addresses = vec![];
+
+    if flags.return_ipv4 {
+        addresses.append(&mut query_record(&domain, DNS_TYPE_A, server).await?);
+    }
+
+    if flags.return_ipv6 {
+        addresses.append(&mut query_record(&domain, DNS_TYPE_AAAA, server).await?);
+    }
+
+    if addresses.is_empty() {
+        return Err(ResolverError::NoRecord);
+    }
+
+    Ok(DnsResult { addresses })
+}
+
+async fn query_record(domain: &str, record_type: u16, server: SocketIpAddress) -> Result<Vec<IpAddress>, ResolverError> {
+    let id = query_id();
+    let request = build_query(id, domain, record_type)?;
+
+    let socket = server_socket(server, SocketType::Datagram);
+    let result = exchange_udp(&socket, server, request.clone()).await;
+    async_close(socket).await;
+
+    let response = result?;
+    if !is_truncated(id, &response) {
+        return parse_response(id, record_type, &response);
+    }
+
+    // answer didn't fit into UDP datagram, query is repeated over TCP to get it whole
+    let socket = server_socket(server, SocketType::Stream);
+    let result = exchange_tcp(&socket, server, request).await;
+    async_close(socket).await;
+
+    parse_response(id, record_type, &result?)
+}
+
+fn server_socket(server: SocketIpAddress, socket_type: SocketType) -> Socket {
+    let domain = match server.address().is_ipv4() {
+        true => SocketDomain::Inet,
+        false => SocketDomain::Inet6,
+    };
+
+    Socket::new(domain, socket_type, SocketFlags::new().close_on_exec(true).flags())
+}
+
+async fn send_request(socket: &Socket, server: SocketIpAddress, request: Vec<u8>) -> Result<(), ResolverError> {
+    if let Err(error) = async_connect(socket, server).await {
+        return Err(ResolverError::InternalError(error.errno(), "connect to nameserver failed".to_string()));
+    }
+
+    if let Err((error, _)) = async_write(socket, request, None).await {
+        return Err(ResolverError::InternalError(error.errno(), "write to nameserver failed".to_string()));
+    }
+
+    Ok(())
+}
+
+async fn exchange_udp(socket: &Socket, server: SocketIpAddress, request: Vec<u8>) -> Result<Vec<u8>, ResolverError> {
+    send_request(socket, server, request).await?;
+
+    let mut buffer = Vec::with_capacity(DNS_MAX_UDP_SIZE);
+    buffer.resize(DNS_MAX_UDP_SIZE, 0);
+
+    match async_read_into(socket, buffer, None).timeout(DNS_TIMEOUT).await {
+        Ok(buffer) => Ok(buffer),
+        Err((error, _)) if error.cancelled() || error.timed_out() => Err(ResolverError::TemporaryError),
+        Err((error, _)) => Err(ResolverError::InternalError(error.errno(), "read from nameserver failed".to_string())),
+    }
+}
+
+// Over TCP both query and response are prefixed with two byte length (RFC 1035, 4.2.2)
+async fn exchange_tcp(socket: &Socket, server: SocketIpAddress, request: Vec<u8>) -> Result<Vec<u8>, ResolverError> {
+    let mut message = Vec::with_capacity(request.len() + 2);
+    message.extend_from_slice(&(request.len() as u16).to_be_bytes());
+    message.extend_from_slice(&request);
+
+    send_request(socket, server, message).await?;
+
+    let mut reader = AsyncBufReader::new(socket);
+    reader.set_timeout(Some(DNS_TIMEOUT));
+
+    let result = match reader.read_u16_be().await {
+        Ok(length) => {
+            let mut buffer = vec![0; length as usize];
+            reader.read_exact(&mut buffer).await.map(|_| buffer)
+        },
+        Err(error) => Err(error),
+    };
+
+    match result {
+        Ok(buffer) => Ok(buffer),
+        Err(AsyncBufReadError::ReadError(error)) if error.cancelled() || error.timed_out() => Err(ResolverError::TemporaryError),
+        Err(AsyncBufReadError::ReadError(error)) => Err(ResolverError::InternalError(error.errno(), "read from nameserver failed".to_string())),
+        Err(AsyncBufReadError::UnexpectedEof) => Err(ResolverError::InternalError(0, "truncated nameserver response".to_string())),
+    }
+}
+
+// Random id makes forged responses harder to get accepted
+fn query_id() -> u16 {
+    let mut id = [0u8; 2];
+    let result = unsafe { libc::getrandom(id.as_mut_ptr() as *mut libc::c_void, id.len(), 0) };
+    if result == id.len() as isize {
+        return u16::from_ne_bytes(id);
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (now.subsec_nanos() ^ std::process::id()) as u16
+}
+
+// Response to some other query is left for parse_response to reject
+fn is_truncated(id: u16, data: &[u8]) -> bool {
+    match (read_u16(data, 0), read_u16(data, 2)) {
+        (Ok(response_id), Ok(flags)) => response_id == id && flags & DNS_FLAG_TRUNCATED != 0,
+        _ => false,
+    }
+}
+
+fn build_query(id: u16, domain: &str, record_type: u16) -> Result<Vec<u8>, ResolverError> {
+    let mut query = Vec::with_capacity(DNS_MAX_UDP_SIZE);
+
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&DNS_FLAG_RECURSION_DESIRED.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());  // questions
+    query.extend_from_slice(&0u16.to_be_bytes());  // answers
+    query.extend_from_slice(&0u16.to_be_bytes());  // authority records
+    query.extend_from_slice(&0u16.to_be_bytes());  // additional records
+
+    for label in domain.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(ResolverError::InvalidParameters);
+        }
+
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+
+    Ok(query)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ResolverError> {
+    match data.get(offset..offset + 2) {
+        None => Err(ResolverError::InternalError(0, "truncated nameserver response".to_string())),
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+    }
+}
+
+fn skip_name(data: &[u8], mut offset: usize) -> Result<usize, ResolverError> {
+    loop {
+        let length = match data.get(offset) {
+            None => return Err(ResolverError::InternalError(0, "truncated nameserver response".to_string())),
+            Some(length) => *length as usize,
+        };
+
+        match length {
+            0 => return Ok(offset + 1),
+            // compression pointer always terminates the name
+            length if length & 0xC0 == 0xC0 => return Ok(offset + 2),
+            length => offset += length + 1,
+        }
+    }
+}
+
+fn parse_response(id: u16, record_type: u16, data: &[u8]) -> Result<Vec<IpAddress>, ResolverError> {
+    if data.len() < DNS_HEADER_SIZE || read_u16(data, 0)? != id {
+        return Err(ResolverError::InternalError(0, "invalid nameserver response".to_string()));
+    }
+
+    let flags = read_u16(data, 2)?;
+    if flags & DNS_FLAG_RESPONSE == 0 {
+        return Err(ResolverError::InternalError(0, "invalid nameserver response".to_string()));
+    }
+
+    match flags & DNS_RCODE_MASK {
+        0 => (),
+        DNS_RCODE_SERVER_FAILURE => return Err(ResolverError::TemporaryError),
+        DNS_RCODE_NAME_ERROR => return Err(ResolverError::NoRecord),
+        _ => return Err(ResolverError::PermanentError),
+    }
+
+    let questions = read_u16(data, 4)?;
+    let answers = read_u16(data, 6)?;
+
+    let mut offset = DNS_HEADER_SIZE;
+    for _ in 0..questions {
+        offset = skip_name(data, offset)? + 4;
+    }
+
+    let mut result = vec![];
+    for _ in 0..answers {
+        offset = skip_name(data, offset)?;
+
+        let answer_type = read_u16(data, offset)?;
+        let answer_class = read_u16(data, offset + 2)?;
+        let length = read_u16(data, offset + 8)? as usize;
+        offset += 10;
+
+        let rdata = match data.get(offset..offset + length) {
+            None => return Err(ResolverError::InternalError(0, "truncated nameserver response".to_string())),
+            Some(rdata) => rdata,
+        };
+
+        offset += length;
+        if answer_class != DNS_CLASS_IN || answer_type != record_type {
+            continue;
+        }
+
+        match (answer_type, length) {
+            (DNS_TYPE_A, 4) => {
+                let address = libc::in_addr { s_addr: u32::from_ne_bytes([rdata[0], rdata[1], rdata[2], rdata[3]]) };
+                result.push(IpAddress::from_inet4(&address));
+            },
+            (DNS_TYPE_AAAA, 16) => {
+                let mut address = libc::in6_addr { s6_addr: [0; 16] };
+                address.s6_addr.copy_from_slice(rdata);
+                result.push(IpAddress::from_inet6(&address));
+            },
+            (_, _) => (),
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_a_response_test() {
+        let mut response = build_query(0x1234, "example.com", DNS_TYPE_A).unwrap();
+        response[2..4].copy_from_slice(&(DNS_FLAG_RESPONSE | DNS_FLAG_RECURSION_DESIRED).to_be_bytes());
+        response[6..8].copy_from_slice(&1u16.to_be_bytes());
+
+        response.extend_from_slice(&[0xC0, 0x0C]);
+        response.extend_from_slice(&DNS_TYPE_A.to_be_bytes());
+        response.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&300u32.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&[93, 184, 216, 34]);
+
+        let result = parse_response(0x1234, DNS_TYPE_A, &response).unwrap();
+        assert_eq!(result, vec![IpAddress::from_text("93.184.216.34").unwrap()]);
+    }
+
+    #[test]
+    fn parse_name_error_response_test() {
+        let mut response = build_query(0x1234, "example.com", DNS_TYPE_A).unwrap();
+        response[2..4].copy_from_slice(&(DNS_FLAG_RESPONSE | DNS_RCODE_NAME_ERROR).to_be_bytes());
+
+        let result = parse_response(0x1234, DNS_TYPE_A, &response);
+        assert!(matches!(result, Err(ResolverError::NoRecord)));
+    }
+
+    #[test]
+    fn truncated_response_test() {
+        let mut response = build_query(0x1234, "example.com", DNS_TYPE_A).unwrap();
+        response[2..4].copy_from_slice(&(DNS_FLAG_RESPONSE | DNS_FLAG_TRUNCATED).to_be_bytes());
+
+        assert!(is_truncated(0x1234, &response));
+        assert!(!is_truncated(0x4321, &response));
+        assert!(!is_truncated(0x1234, &response[..1]));
+
+        response[2..4].copy_from_slice(&DNS_FLAG_RESPONSE.to_be_bytes());
+        assert!(!is_truncated(0x1234, &response));
+    }
+
+    #[test]
+    fn truncated_response_tcp_fallback_test() {
+        use std::io::{Read, Write};
+
+        // fake nameserver, answers over UDP with truncated response and over TCP with full one
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = udp.local_addr().unwrap().port();
+        let tcp = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+
+        let nameserver = std::thread::spawn(move || {
+            let mut query = [0u8; DNS_MAX_UDP_SIZE];
+            let (length, peer) = udp.recv_from(&mut query).unwrap();
+
+            let mut response = query[..length].to_vec();
+            response[2..4].copy_from_slice(&(DNS_FLAG_RESPONSE | DNS_FLAG_TRUNCATED | DNS_FLAG_RECURSION_DESIRED).to_be_bytes());
+            udp.send_to(&response, peer).unwrap();
+
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut length = [0u8; 2];
+            stream.read_exact(&mut length).unwrap();
+
+            let mut response = vec![0; u16::from_be_bytes(length) as usize];
+            stream.read_exact(&mut response).unwrap();
+
+            response[2..4].copy_from_slice(&(DNS_FLAG_RESPONSE | DNS_FLAG_RECURSION_DESIRED).to_be_bytes());
+            response[6..8].copy_from_slice(&1u16.to_be_bytes());
+            response.extend_from_slice(&[0xC0, 0x0C]);
+            response.extend_from_slice(&DNS_TYPE_A.to_be_bytes());
+            response.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&300u32.to_be_bytes());
+            response.extend_from_slice(&4u16.to_be_bytes());
+            response.extend_from_slice(&[93, 184, 216, 34]);
+
+            stream.write_all(&(response.len() as u16).to_be_bytes()).unwrap();
+            stream.write_all(&response).unwrap();
+        });
+
+        let server = SocketIpAddress::from_text("127.0.0.1", Some(port)).unwrap();
+        let result = fbs_runtime::async_run(async move {
+            query_nameserver("example.com".to_string(), DnsQueryFlags::default(), server).await
+        });
+
+        nameserver.join().unwrap();
+        assert_eq!(result.unwrap().all_record(), vec![IpAddress::from_text("93.184.216.34").unwrap()]);
+    }
+}
@@ -9,11 +9,12 @@ use std::collections::VecDeque;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use fbs_library::eventfd::*;
 use fbs_library::system_error::SystemError;
 
-use super::{async_read_struct, async_write_struct};
+use super::{async_read_struct, async_write_struct, async_sleep};
 
 #[derive(Debug)]
 pub struct AsyncChannelRx<T> {
@@ -70,6 +71,11 @@ impl<T> AsyncChannelRx<T> {
         self.backend.is_empty()
     }
 
+    // Pops next value if one is already queued, never suspends
+    pub fn try_receive(&self) -> Option<T> {
+        self.backend.receive()
+    }
+
     pub fn tx(&self) -> AsyncChannelTx<T> {
         AsyncChannelTx {
             backend: self.backend.clone(),
@@ -136,6 +142,118 @@ pub fn async_channel_create<T>() -> (AsyncChannelRx<T>, AsyncChannelTx<T>) {
     )
 }
 
+#[derive(Debug)]
+struct AsyncBroadcastBackend<T> {
+    messages: RefCell<VecDeque<T>>,
+    first_sequence: Cell<u64>,      // sequence number of the oldest buffered message
+    capacity: usize,
+    wakers: RefCell<Vec<Waker>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsyncBroadcastMessage<T> {
+    Value(T),
+    Lagged(u64),    // number of messages dropped before subscriber could read them
+}
+
+// Every subscriber sees every message sent after it subscribed. Only last capacity messages
+// are buffered, subscriber falling further behind gets Lagged and continues from the oldest one.
+#[derive(Debug)]
+pub struct AsyncBroadcastTx<T: Clone> {
+    backend: Rc<AsyncBroadcastBackend<T>>,
+}
+
+impl<T: Clone> Clone for AsyncBroadcastTx<T> {
+    fn clone(&self) -> Self {
+        AsyncBroadcastTx { backend: self.backend.clone() }
+    }
+}
+
+// Clone gets its own cursor, starting where original currently is
+#[derive(Debug)]
+pub struct AsyncBroadcastRx<T: Clone> {
+    backend: Rc<AsyncBroadcastBackend<T>>,
+    next_sequence: Cell<u64>,
+}
+
+impl<T: Clone> Clone for AsyncBroadcastRx<T> {
+    fn clone(&self) -> Self {
+        AsyncBroadcastRx { backend: self.backend.clone(), next_sequence: self.next_sequence.clone() }
+    }
+}
+
+pub struct AsyncBroadcastValue<'a, T: Clone> {
+    rx: &'a AsyncBroadcastRx<T>,
+}
+
+impl<T: Clone> Future for AsyncBroadcastValue<'_, T> {
+    type Output = AsyncBroadcastMessage<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.rx.try_receive() {
+            None => {
+                self.rx.backend.wakers.borrow_mut().push(cx.waker().clone());
+                Poll::Pending
+            },
+            Some(message) => Poll::Ready(message),
+        }
+    }
+}
+
+impl<T: Clone> AsyncBroadcastTx<T> {
+    pub fn send(&self, value: T) {
+        let mut messages = self.backend.messages.borrow_mut();
+        messages.push_back(value);
+
+        if messages.len() > self.backend.capacity {
+            messages.pop_front();
+            self.backend.first_sequence.set(self.backend.first_sequence.get() + 1);
+        }
+
+        drop(messages);
+        self.backend.wakers.take().into_iter().for_each(|w| w.wake());
+    }
+
+    // Subscriber receives only messages sent from now on
+    pub fn subscribe(&self) -> AsyncBroadcastRx<T> {
+        let next_sequence = self.backend.first_sequence.get() + self.backend.messages.borrow().len() as u64;
+        AsyncBroadcastRx { backend: self.backend.clone(), next_sequence: Cell::new(next_sequence) }
+    }
+}
+
+impl<T: Clone> AsyncBroadcastRx<T> {
+    pub fn receive(&self) -> AsyncBroadcastValue<'_, T> {
+        AsyncBroadcastValue { rx: self }
+    }
+
+    pub fn try_receive(&self) -> Option<AsyncBroadcastMessage<T>> {
+        let first_sequence = self.backend.first_sequence.get();
+        let next_sequence = self.next_sequence.get();
+
+        if next_sequence < first_sequence {
+            self.next_sequence.set(first_sequence);
+            return Some(AsyncBroadcastMessage::Lagged(first_sequence - next_sequence));
+        }
+
+        let value = self.backend.messages.borrow().get((next_sequence - first_sequence) as usize).cloned()?;
+        self.next_sequence.set(next_sequence + 1);
+        Some(AsyncBroadcastMessage::Value(value))
+    }
+}
+
+pub fn async_broadcast_create<T: Clone>(capacity: usize) -> AsyncBroadcastTx<T> {
+    assert!(capacity > 0, "Broadcast channel capacity must be positive");
+
+    AsyncBroadcastTx {
+        backend: Rc::new(AsyncBroadcastBackend {
+            messages: RefCell::new(VecDeque::with_capacity(capacity)),
+            first_sequence: Cell::new(0),
+            capacity,
+            wakers: RefCell::new(Vec::new()),
+        }),
+    }
+}
+
 struct AsyncSignalBackend {
     fired: Cell<bool>,
     waiters: Cell<Vec<Waker>>,
@@ -234,6 +352,117 @@ impl AsyncSignalTriggerMT {
     }
 }
 
+// Ticks are scheduled relative to previous deadline, so time spent between ticks doesn't
+// accumulate as drift. When caller falls behind by more than a period, missed ticks are skipped.
+#[derive(Debug)]
+pub struct Interval {
+    period: Duration,
+    deadline: Instant,
+}
+
+pub fn interval(period: Duration) -> Interval {
+    Interval { period, deadline: Instant::now() + period }
+}
+
+impl Interval {
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    // Changes period, next tick happens one new period from now
+    pub fn reset(&mut self, period: Duration) {
+        self.period = period;
+        self.deadline = Instant::now() + period;
+    }
+
+    pub async fn tick(&mut self) {
+        let now = Instant::now();
+        if self.deadline > now {
+            async_sleep(self.deadline - now).await;
+        }
+
+        let now = Instant::now();
+        self.deadline += self.period;
+        if self.deadline < now {
+            self.deadline = now + self.period;
+        }
+    }
+}
+
+struct WaitGroupBackend {
+    counter: Cell<usize>,
+    waiters: Cell<Vec<Waker>>,
+}
+
+impl Debug for WaitGroupBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WaitGroupBackend")
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
+// Counts outstanding work, waiters are woken once counter drops back to zero. Clones share
+// the same counter, so one can be moved into each spawned task.
+#[derive(Debug, Clone)]
+pub struct WaitGroup {
+    ptr: Rc<WaitGroupBackend>,
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WaitGroup {
+    pub fn new() -> Self {
+        Self { ptr: Rc::new(WaitGroupBackend { counter: Cell::new(0), waiters: Cell::new(Vec::new()) }) }
+    }
+
+    pub fn add(&self, count: usize) {
+        self.ptr.counter.set(self.ptr.counter.get() + count);
+    }
+
+    pub fn done(&self) {
+        let counter = self.ptr.counter.get();
+        assert!(counter > 0, "WaitGroup::done called more times than added");
+
+        self.ptr.counter.set(counter - 1);
+        if counter == 1 {
+            self.ptr.waiters.take().into_iter().for_each(|w| w.wake());
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.ptr.counter.get()
+    }
+
+    // Resolves immediately when nothing was added
+    pub async fn wait(&self) {
+        WaitGroupWait { ptr: self.ptr.clone() }.await
+    }
+}
+
+struct WaitGroupWait {
+    ptr: Rc<WaitGroupBackend>,
+}
+
+impl Future for WaitGroupWait {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.ptr.counter.get() == 0 {
+            return Poll::Ready(());
+        }
+
+        let mut waiters = self.ptr.waiters.take();
+        waiters.push(cx.waker().clone());
+        self.ptr.waiters.set(waiters);
+
+        Poll::Pending
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{async_run, async_spawn};
@@ -260,6 +489,52 @@ mod test {
         });
     }
 
+    #[test]
+    fn async_channel_try_receive_test() {
+        let (rx, tx) = async_channel_create::<i32>();
+        assert_eq!(rx.try_receive(), None);
+
+        tx.send(1);
+        tx.send(2);
+        assert_eq!(rx.try_receive(), Some(1));
+        assert_eq!(rx.try_receive(), Some(2));
+        assert_eq!(rx.try_receive(), None);
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn async_broadcast_test() {
+        let result = async_run(async {
+            let tx = async_broadcast_create::<i32>(2);
+            let rx1 = tx.subscribe();
+            let rx2 = tx.subscribe();
+
+            let task = async_spawn(async move {
+                let first = rx1.receive().await;
+                let second = rx1.receive().await;
+                (first, second)
+            });
+
+            tx.send(1);
+            tx.send(2);
+            assert_eq!(task.await, (AsyncBroadcastMessage::Value(1), AsyncBroadcastMessage::Value(2)));
+
+            // rx2 didn't read anything, so oldest message is gone by now
+            tx.send(3);
+            assert_eq!(rx2.receive().await, AsyncBroadcastMessage::Lagged(1));
+            assert_eq!(rx2.receive().await, AsyncBroadcastMessage::Value(2));
+            assert_eq!(rx2.receive().await, AsyncBroadcastMessage::Value(3));
+            assert_eq!(rx2.try_receive(), None);
+
+            // late subscriber doesn't see buffered messages
+            let rx3 = tx.subscribe();
+            assert_eq!(rx3.try_receive(), None);
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
     #[test]
     fn async_signal_test() {
         async_run(async {
@@ -335,4 +610,58 @@ mod test {
             assert_eq!(v3, 3);
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn wait_group_test() {
+        let result = async_run(async {
+            let group = WaitGroup::new();
+            let finished = Rc::new(Cell::new(0));
+
+            // nothing added yet
+            group.wait().await;
+
+            for i in 1..=3 {
+                group.add(1);
+
+                let group = group.clone();
+                let finished = finished.clone();
+                async_spawn(async move {
+                    async_sleep(Duration::new(0, 5_000_000 * i)).await;
+                    finished.set(finished.get() + 1);
+                    group.done();
+                }).detach();
+            }
+
+            assert_eq!(group.count(), 3);
+            group.wait().await;
+
+            assert_eq!(group.count(), 0);
+            finished.get()
+        });
+
+        assert_eq!(result, 3);
+    }
+
+    #[test]
+    fn interval_test() {
+        async_run(async {
+            let started = Instant::now();
+            let mut timer = interval(Duration::new(0, 10_000_000));
+
+            for i in 1..=5 {
+                timer.tick().await;
+
+                // work done between ticks must not delay the next one
+                std::thread::sleep(Duration::new(0, 3_000_000));
+                assert!(started.elapsed() >= Duration::new(0, 10_000_000 * i));
+            }
+
+            timer.reset(Duration::new(0, 20_000_000));
+            assert_eq!(timer.period(), Duration::new(0, 20_000_000));
+
+            let reset_at = Instant::now();
+            timer.tick().await;
+            assert!(reset_at.elapsed() >= Duration::new(0, 20_000_000));
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+use super::async_utils::AsyncSignalMT;
+
+const BLOCKING_POOL_SIZE: usize = 4;
+
+type BlockingJob = Box<dyn FnOnce() + Send>;
+
+static BLOCKING_POOL: OnceLock<BlockingPool> = OnceLock::new();
+
+struct BlockingPool {
+    queue: Mutex<Sender<BlockingJob>>,
+}
+
+impl BlockingPool {
+    fn new(size: usize) -> Self {
+        let (tx, rx) = channel::<BlockingJob>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..size {
+            let rx = rx.clone();
+            std::thread::Builder::new()
+                .name(format!("fbs-blocking-{}", i))
+                .spawn(move || Self::worker(rx))
+                .expect("Error while spawning blocking pool thread");
+        }
+
+        Self { queue: Mutex::new(tx) }
+    }
+
+    fn worker(rx: Arc<Mutex<Receiver<BlockingJob>>>) {
+        loop {
+            // lock is released before running the job, so other workers can pick up next one
+            let job = rx.lock().unwrap().recv();
+            match job {
+                Ok(job) => job(),
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn submit(&self, job: BlockingJob) {
+        self.queue.lock().unwrap().send(job).expect("Blocking pool workers are gone");
+    }
+}
+
+// Runs function on a shared worker thread, result is delivered back to the calling thread
+// through eventfd, so waiting for it doesn't block the reactor. Panic inside function is
+// propagated to the awaiting task.
+pub fn spawn_blocking<T, F>(function: F) -> impl Future<Output = T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let signal = AsyncSignalMT::new().expect("Error while creating blocking task signal");
+    let trigger = signal.trigger();
+
+    let result = Arc::new(Mutex::new(None));
+    let result_ptr = result.clone();
+
+    BLOCKING_POOL.get_or_init(|| BlockingPool::new(BLOCKING_POOL_SIZE)).submit(Box::new(move || {
+        let value = catch_unwind(AssertUnwindSafe(function));
+        *result_ptr.lock().unwrap() = Some(value);
+        trigger.signal();
+    }));
+
+    async move {
+        signal.wait().await;
+
+        let value = result.lock().unwrap().take();
+        match value {
+            Some(Ok(value)) => value,
+            Some(Err(panic)) => resume_unwind(panic),
+            None => panic!("Blocking task signalled without result"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use crate::{async_run, async_spawn, async_sleep};
+    use super::*;
+
+    #[test]
+    fn spawn_blocking_test() {
+        let caller = std::thread::current().id();
+
+        let result = async_run(async move {
+            let ticks = Rc::new(Cell::new(0));
+            let ticks_cpy = ticks.clone();
+
+            // reactor must keep running while blocking tasks sleep
+            let ticker = async_spawn(async move {
+                loop {
+                    async_sleep(Duration::new(0, 5_000_000)).await;
+                    ticks_cpy.set(ticks_cpy.get() + 1);
+                }
+            });
+
+            let task1 = spawn_blocking(|| {
+                std::thread::sleep(Duration::new(0, 100_000_000));
+                (1, std::thread::current().id())
+            });
+
+            let task2 = spawn_blocking(|| {
+                std::thread::sleep(Duration::new(0, 100_000_000));
+                (2, std::thread::current().id())
+            });
+
+            let (value1, thread1) = task1.await;
+            let (value2, thread2) = task2.await;
+            ticker.cancel();
+
+            assert_eq!(value1, 1);
+            assert_eq!(value2, 2);
+            assert_ne!(thread1, caller);
+            assert_ne!(thread2, caller);
+            assert!(ticks.get() > 0);
+
+            value1 + value2
+        });
+
+        assert_eq!(result, 3);
+    }
+}
@@ -0,0 +1,462 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use fbs_library::system_error::SystemError;
+
+use super::{async_read_some, async_write, AsyncFd, CancellationScope, ReadOutcome};
+
+const DEFAULT_BUFFER_CAPACITY: usize = 4096;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncBufReadError {
+    #[error("Read error - {0}")]
+    ReadError(SystemError),
+    #[error("Unexpected end of stream")]
+    UnexpectedEof,
+}
+
+// Reads from fd in chunks of buffer capacity, so parsing small fields doesn't cost a syscall each
+pub struct AsyncBufReader<T: AsyncFd> {
+    fd: T,
+    buffer: Vec<u8>,
+    offset: usize,
+    scope: Option<CancellationScope>,
+    timeout: Option<Duration>,
+}
+
+impl<T: AsyncFd> AsyncBufReader<T> {
+    pub fn new(fd: T) -> Self {
+        Self::with_capacity(fd, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    pub fn with_capacity(fd: T, capacity: usize) -> Self {
+        // at least one byte, so reads can tell end of stream apart
+        Self { fd, buffer: Vec::with_capacity(std::cmp::max(capacity, 1)), offset: 0, scope: None, timeout: None }
+    }
+
+    // Reads are bound to scope, so they can be cancelled together with other ops
+    pub fn scope(mut self, scope: &CancellationScope) -> Self {
+        self.scope = Some(scope.clone());
+        self
+    }
+
+    // Applies to each read syscall separately, expired read reports timed_out()
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.fd
+    }
+
+    pub fn into_inner(self) -> T {
+        self.fd
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    // Already buffered data is preserved
+    pub fn reserve_capacity(&mut self, capacity: usize) {
+        if capacity > self.buffer.capacity() {
+            self.buffer.reserve_exact(capacity - self.buffer.len());
+        }
+    }
+
+    // Releases memory after occasional large reads, capacity never goes below buffered data
+    pub fn shrink_capacity(&mut self, capacity: usize) {
+        self.buffer.drain(..self.offset);
+        self.offset = 0;
+        self.buffer.shrink_to(std::cmp::max(capacity, 1));
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.offset
+    }
+
+    // Returns number of buffered bytes, 0 means end of stream
+    async fn fill_buffer(&mut self) -> Result<usize, AsyncBufReadError> {
+        if self.offset < self.buffer.len() {
+            return Ok(self.buffer.len() - self.offset);
+        }
+
+        self.offset = 0;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+
+        let mut op = async_read_some(&self.fd, buffer, None);
+        if let Some(scope) = &self.scope {
+            op = op.scope(scope);
+        }
+
+        if let Some(timeout) = self.timeout {
+            op = op.timeout(timeout);
+        }
+
+        match op.await {
+            Ok(ReadOutcome::Data(buffer)) => {
+                self.buffer = buffer;
+                Ok(self.buffer.len())
+            },
+            Ok(ReadOutcome::Eof(buffer)) => {
+                self.buffer = buffer;
+                Ok(0)
+            },
+            Err((error, buffer)) => {
+                self.buffer = buffer;
+                Err(AsyncBufReadError::ReadError(error))
+            },
+        }
+    }
+
+    pub async fn read_exact(&mut self, mut target: &mut [u8]) -> Result<(), AsyncBufReadError> {
+        while !target.is_empty() {
+            let available = self.fill_buffer().await?;
+            if available == 0 {
+                return Err(AsyncBufReadError::UnexpectedEof);
+            }
+
+            let to_copy = std::cmp::min(target.len(), available);
+            target[..to_copy].copy_from_slice(&self.buffer[self.offset..self.offset + to_copy]);
+            self.offset += to_copy;
+
+            target = &mut target[to_copy..];
+        }
+
+        Ok(())
+    }
+
+    async fn read_array<const N: usize>(&mut self) -> Result<[u8; N], AsyncBufReadError> {
+        let mut bytes = [0; N];
+        self.read_exact(&mut bytes).await?;
+
+        Ok(bytes)
+    }
+
+    pub async fn read_u8(&mut self) -> Result<u8, AsyncBufReadError> {
+        Ok(u8::from_be_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u16_be(&mut self) -> Result<u16, AsyncBufReadError> {
+        Ok(u16::from_be_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u16_le(&mut self) -> Result<u16, AsyncBufReadError> {
+        Ok(u16::from_le_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u32_be(&mut self) -> Result<u32, AsyncBufReadError> {
+        Ok(u32::from_be_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u32_le(&mut self) -> Result<u32, AsyncBufReadError> {
+        Ok(u32::from_le_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u64_be(&mut self) -> Result<u64, AsyncBufReadError> {
+        Ok(u64::from_be_bytes(self.read_array().await?))
+    }
+
+    pub async fn read_u64_le(&mut self) -> Result<u64, AsyncBufReadError> {
+        Ok(u64::from_le_bytes(self.read_array().await?))
+    }
+
+    // Appends data up to and including delimiter to target. Returns number of bytes appended,
+    // at the end of stream it can be less than full line, or 0 if there was nothing left.
+    pub async fn read_until(&mut self, delimiter: u8, target: &mut Vec<u8>) -> Result<usize, AsyncBufReadError> {
+        let mut read = 0;
+
+        loop {
+            let available = self.fill_buffer().await?;
+            if available == 0 {
+                return Ok(read);
+            }
+
+            let data = &self.buffer[self.offset..];
+            let (to_copy, found) = match data.iter().position(|byte| *byte == delimiter) {
+                Some(position) => (position + 1, true),
+                None => (data.len(), false),
+            };
+
+            target.extend_from_slice(&data[..to_copy]);
+            self.offset += to_copy;
+            read += to_copy;
+
+            if found {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+// Collects small writes and sends them with a single write when flushed, or once buffered
+// data reaches high water mark
+pub struct AsyncBufWriter<T: AsyncFd> {
+    fd: T,
+    buffer: Vec<u8>,
+    high_water_mark: usize,
+    writes: u64,
+}
+
+impl<T: AsyncFd> AsyncBufWriter<T> {
+    pub fn new(fd: T) -> Self {
+        Self::with_capacity(fd, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    pub fn with_capacity(fd: T, high_water_mark: usize) -> Self {
+        Self { fd, buffer: Vec::with_capacity(high_water_mark), high_water_mark, writes: 0 }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.fd
+    }
+
+    // Buffered data is lost, flush has to be called first
+    pub fn into_inner(self) -> T {
+        self.fd
+    }
+
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+
+    pub fn set_high_water_mark(&mut self, high_water_mark: usize) {
+        self.high_water_mark = high_water_mark;
+        if high_water_mark > self.buffer.capacity() {
+            self.buffer.reserve_exact(high_water_mark - self.buffer.len());
+        }
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Number of write ops issued so far, useful to check how well writes are coalesced
+    pub fn write_count(&self) -> u64 {
+        self.writes
+    }
+
+    pub async fn write_all(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= self.high_water_mark {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), SystemError> {
+        while !self.buffer.is_empty() {
+            let length = self.buffer.len();
+            self.writes += 1;
+            let result = async_write(&self.fd, std::mem::take(&mut self.buffer), None).await;
+
+            // returned vec is truncated to written size (nothing on error), but write leaves
+            // the rest of data intact, so unwritten part stays buffered for the next flush
+            let (mut buffer, error) = match result {
+                Ok(buffer) if buffer.is_empty() => (buffer, Some(SystemError::new(libc::EIO))),
+                Ok(buffer) => (buffer, None),
+                Err((error, buffer)) => (buffer, Some(error)),
+            };
+
+            let written = buffer.len();
+            unsafe { buffer.set_len(length) };
+            buffer.drain(..written);
+            self.buffer = buffer;
+
+            if let Some(error) = error {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::fd::{OwnedFd, FromRawFd};
+
+    use crate::{async_run, async_write, async_read_into};
+    use super::*;
+
+    fn create_pipe() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+        assert_eq!(error, 0);
+
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn buf_reader_integers_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            let mut data = vec![];
+            data.push(7u8);
+            data.extend_from_slice(&0x0102u16.to_be_bytes());
+            data.extend_from_slice(&0x0102u16.to_le_bytes());
+            data.extend_from_slice(&0x01020304u32.to_be_bytes());
+            data.extend_from_slice(&0x01020304u32.to_le_bytes());
+            data.extend_from_slice(&0x0102030405060708u64.to_be_bytes());
+            data.extend_from_slice(&0x0102030405060708u64.to_le_bytes());
+
+            assert!(async_write(&write_end, data, None).await.is_ok());
+            drop(write_end);
+
+            // small capacity forces values to be split between reads
+            let mut reader = AsyncBufReader::with_capacity(read_end, 3);
+            assert_eq!(reader.read_u8().await, Ok(7));
+            assert_eq!(reader.read_u16_be().await, Ok(0x0102));
+            assert_eq!(reader.read_u16_le().await, Ok(0x0102));
+            assert_eq!(reader.read_u32_be().await, Ok(0x01020304));
+            assert_eq!(reader.read_u32_le().await, Ok(0x01020304));
+            assert_eq!(reader.read_u64_be().await, Ok(0x0102030405060708));
+            assert_eq!(reader.read_u64_le().await, Ok(0x0102030405060708));
+            assert_eq!(reader.read_u8().await, Err(AsyncBufReadError::UnexpectedEof));
+
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn buf_reader_shrink_capacity_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            assert!(async_write(&write_end, b"abcdef".to_vec(), None).await.is_ok());
+            drop(write_end);
+
+            let mut reader = AsyncBufReader::with_capacity(read_end, 4);
+            reader.reserve_capacity(1024);
+            assert_eq!(reader.read_u8().await, Ok(b'a'));
+
+            // buffered data survives shrinking
+            reader.shrink_capacity(2);
+            assert!(reader.capacity() < 1024);
+            assert!(reader.capacity() >= reader.buffered());
+
+            let mut rest = [0; 5];
+            assert!(reader.read_exact(&mut rest).await.is_ok());
+            assert_eq!(&rest, b"bcdef");
+
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn buf_reader_read_until_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            assert!(async_write(&write_end, b"first line\nsecond\nrest".to_vec(), None).await.is_ok());
+            drop(write_end);
+
+            let mut reader = AsyncBufReader::with_capacity(read_end, 4);
+            let mut line = vec![];
+
+            assert_eq!(reader.read_until(b'\n', &mut line).await, Ok(11));
+            assert_eq!(line, b"first line\n");
+
+            line.clear();
+            assert_eq!(reader.read_until(b'\n', &mut line).await, Ok(7));
+            assert_eq!(line, b"second\n");
+
+            line.clear();
+            assert_eq!(reader.read_until(b'\n', &mut line).await, Ok(4));
+            assert_eq!(line, b"rest");
+
+            line.clear();
+            assert_eq!(reader.read_until(b'\n', &mut line).await, Ok(0));
+            assert!(line.is_empty());
+
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn buf_writer_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            let mut writer = AsyncBufWriter::with_capacity(write_end, 8);
+            assert!(writer.write_all(b"abc").await.is_ok());
+            assert!(writer.write_all(b"def").await.is_ok());
+            assert_eq!(writer.buffered(), 6);
+
+            // reaching high water mark flushes everything at once
+            assert!(writer.write_all(b"gh").await.is_ok());
+            assert_eq!(writer.buffered(), 0);
+
+            let data = async_read_into(&read_end, Vec::with_capacity(100), None).await.unwrap();
+            assert_eq!(data, b"abcdefgh");
+
+            assert!(writer.write_all(b"tail").await.is_ok());
+            assert_eq!(writer.buffered(), 4);
+            assert!(writer.flush().await.is_ok());
+            assert_eq!(writer.buffered(), 0);
+
+            let data = async_read_into(&read_end, Vec::with_capacity(100), None).await.unwrap();
+            assert_eq!(data, b"tail");
+
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn buf_writer_write_count_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            let mut writer = AsyncBufWriter::with_capacity(write_end, 8);
+            for _ in 0..3 {
+                writer.write_all(b"ab").await.unwrap();
+            }
+            assert_eq!(writer.write_count(), 0);
+
+            writer.flush().await.unwrap();
+            assert_eq!(writer.write_count(), 1);
+
+            // crossing high water mark flushes right away
+            writer.write_all(b"0123456789").await.unwrap();
+            assert_eq!(writer.write_count(), 2);
+            drop(writer);
+
+            let data = async_read_into(&read_end, Vec::with_capacity(32), None).await.unwrap();
+            assert_eq!(data, b"ababab0123456789");
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn buf_writer_flush_error_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = create_pipe();
+
+            let mut writer = AsyncBufWriter::with_capacity(write_end, 8);
+            writer.write_all(b"abc").await.unwrap();
+            drop(read_end);
+
+            // nobody reads anymore, data stays buffered
+            let result = writer.flush().await;
+            assert_eq!(result.err().unwrap().errno(), libc::EPIPE);
+            assert_eq!(writer.buffered(), 3);
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::REACTOR;
+
+// Collects tokens of ops bound to it with AsyncOp::scope, so all of them can be cancelled together,
+// e.g. when connection owning them is torn down. Clones share the same set of ops.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationScope {
+    tokens: Rc<RefCell<Vec<(u64, usize)>>>,
+}
+
+impl CancellationScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, token: (u64, usize)) {
+        let mut tokens = self.tokens.borrow_mut();
+
+        // tokens of completed ops are dropped only when vector would grow, which keeps it amortized O(1)
+        if tokens.len() == tokens.capacity() {
+            REACTOR.with(|r| {
+                let reactor = r.borrow();
+                tokens.retain(|token| reactor.is_op_in_flight(*token));
+            });
+        }
+
+        tokens.push(token);
+    }
+
+    pub fn cancel_all(&self) {
+        let tokens = std::mem::take(&mut *self.tokens.borrow_mut());
+        if tokens.is_empty() {
+            return;
+        }
+
+        // reactor skips tokens of ops which are already completed
+        REACTOR.with(|r| {
+            r.borrow_mut().cancel_op(&tokens);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use crate::{async_run, async_sleep_with_result, async_nop};
+    use super::*;
+
+    #[test]
+    fn cancel_all_test() {
+        let result = async_run(async {
+            let scope = CancellationScope::new();
+            let cancelled = Rc::new(Cell::new(0));
+
+            for _ in 0..3 {
+                let cancelled = cancelled.clone();
+                async_sleep_with_result(Duration::new(10, 0)).scope(&scope).schedule(move |result| {
+                    if result.is_err_and(|e| e.cancelled()) {
+                        cancelled.set(cancelled.get() + 1);
+                    }
+                });
+            }
+
+            // completed op in scope is ignored on cancel
+            let result = async_nop().scope(&scope).await;
+            assert!(result.is_ok());
+
+            let start = Instant::now();
+            scope.cancel_all();
+
+            while cancelled.get() < 3 {
+                async_nop().await.unwrap();
+            }
+
+            assert!(start.elapsed() < Duration::new(10, 0));
+            cancelled.get()
+        });
+
+        assert_eq!(result, 3);
+    }
+}
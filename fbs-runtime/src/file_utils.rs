@@ -0,0 +1,171 @@
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+
+use fbs_library::open_mode::OpenMode;
+use fbs_library::system_error::SystemError;
+
+use super::{async_open, async_read_some, async_write, async_fsync, async_close, async_close_with_result, async_rename, ReadOutcome};
+
+const COPY_CHUNK_SIZE: usize = 65536;
+
+thread_local! {
+    static TEMP_FILE_COUNTER: Cell<u64> = Cell::new(0);
+}
+
+fn temp_file_path(path: &Path) -> Result<PathBuf, SystemError> {
+    let name = match path.file_name() {
+        None => return Err(SystemError::new(libc::EINVAL)),
+        Some(name) => name.to_string_lossy(),
+    };
+
+    let counter = TEMP_FILE_COUNTER.with(|c| { c.set(c.get() + 1); c.get() });
+    Ok(path.with_file_name(format!(".{}.tmp.{}.{}", name, std::process::id(), counter)))
+}
+
+// Temp file is created in the same directory as target, so rename never crosses filesystems
+pub async fn async_write_atomic<P: AsRef<Path>>(path: P, data: Vec<u8>) -> Result<(), SystemError> {
+    let path = path.as_ref();
+    let temp_path = temp_file_path(path)?;
+
+    let result = write_and_rename(path, &temp_path, data).await;
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+async fn write_and_rename(path: &Path, temp_path: &Path, data: Vec<u8>) -> Result<(), SystemError> {
+    let fd = async_open(temp_path, OpenMode::new().create(true, 0o644).exists(true).close_on_exec(true)).await?;
+
+    let mut written = 0;
+    while written < data.len() {
+        let result = async_write(&fd, data[written..].to_vec(), Some(written as u64)).await;
+        match result {
+            Ok(buffer) if buffer.is_empty() => return Err(SystemError::new(libc::EIO)),
+            Ok(buffer) => written += buffer.len(),
+            Err((error, _)) => return Err(error),
+        }
+    }
+
+    async_fsync(&fd, false).await?;
+    async_close_with_result(fd).await?;
+    async_rename(temp_path, path).await?;
+
+    // persist directory entry as well, failure here doesn't invalidate already renamed file
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    if let Ok(directory) = async_open(directory, OpenMode::new().set_flags(libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC)).await {
+        let _ = async_fsync(&directory, false).await;
+        async_close(directory).await;
+    }
+
+    Ok(())
+}
+
+// Destination is opened with given mode, so it decides whether existing file is truncated or
+// appended to. Returns number of bytes copied.
+pub async fn async_copy_file<P: AsRef<Path>, Q: AsRef<Path>>(source: P, destination: Q, mode: &OpenMode) -> Result<u64, SystemError> {
+    let source = async_open(source.as_ref(), OpenMode::new().read_only().close_on_exec(true)).await?;
+    let destination = match async_open(destination.as_ref(), mode).await {
+        Ok(destination) => destination,
+        Err(error) => {
+            async_close(source).await;
+            return Err(error);
+        },
+    };
+
+    let result = copy_data(&source, &destination).await;
+    async_close(source).await;
+
+    match result {
+        Ok(copied) => async_close_with_result(destination).await.map(|_| copied),
+        Err(error) => {
+            async_close(destination).await;
+            Err(error)
+        },
+    }
+}
+
+async fn copy_data<T: std::os::fd::AsRawFd, U: std::os::fd::AsRawFd>(source: &T, destination: &U) -> Result<u64, SystemError> {
+    let mut copied = 0;
+    let mut buffer = Vec::with_capacity(COPY_CHUNK_SIZE);
+
+    loop {
+        let data = match async_read_some(source, buffer, Some(copied)).await {
+            Ok(ReadOutcome::Eof(_)) => return Ok(copied),
+            Ok(ReadOutcome::Data(data)) => data,
+            Err((error, _)) => return Err(error),
+        };
+
+        // short write leaves the rest of chunk to be written in next iteration
+        let mut written = 0;
+        while written < data.len() {
+            match async_write(destination, data[written..].to_vec(), None).await {
+                Ok(chunk) if chunk.is_empty() => return Err(SystemError::new(libc::EIO)),
+                Ok(chunk) => written += chunk.len(),
+                Err((error, _)) => return Err(error),
+            }
+        }
+
+        copied += data.len() as u64;
+        buffer = data;
+        buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::async_run;
+    use super::*;
+
+    #[test]
+    fn async_write_atomic_test() {
+        async_run(async {
+            let directory = std::env::temp_dir().join(format!("fbs-write-atomic-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+
+            let target = directory.join("state.txt");
+            std::fs::write(&target, b"old content").unwrap();
+
+            let content = b"new content".repeat(1000);
+            let result = async_write_atomic(&target, content.clone()).await;
+            assert!(result.is_ok());
+
+            assert_eq!(std::fs::read(&target).unwrap(), content);
+
+            let entries: Vec<_> = std::fs::read_dir(&directory).unwrap().collect();
+            assert_eq!(entries.len(), 1);
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        });
+    }
+
+    #[test]
+    fn async_copy_file_test() {
+        async_run(async {
+            let directory = std::env::temp_dir().join(format!("fbs-copy-file-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+
+            // spans several chunks and ends with a partial one
+            let content: Vec<u8> = (0..COPY_CHUNK_SIZE * 3 + 123).map(|i| (i % 251) as u8).collect();
+            let source = directory.join("source.bin");
+            std::fs::write(&source, &content).unwrap();
+
+            let target = directory.join("target.bin");
+            std::fs::write(&target, b"previous content that is longer than nothing").unwrap();
+
+            let copied = async_copy_file(&source, &target, OpenMode::new().write_only().create(true, 0o644).truncate(true).close_on_exec(true)).await;
+            assert_eq!(copied, Ok(content.len() as u64));
+            assert_eq!(std::fs::read(&target).unwrap(), content);
+
+            let missing = async_copy_file(directory.join("missing.bin"), &target, OpenMode::new().write_only()).await;
+            assert_eq!(missing.map_err(|error| error.errno()), Err(libc::ENOENT));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        });
+    }
+}
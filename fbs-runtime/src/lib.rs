@@ -3,6 +3,7 @@ use std::cell::RefCell;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::cell::Cell;
+use std::os::fd::RawFd;
 use std::slice;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -15,16 +16,34 @@ use fbs_reactor::*;
 
 mod ops;
 mod linked_ops;
+mod file_utils;
+mod pipe_utils;
+mod signal_utils;
+mod blocking;
+mod cancellation;
+mod buffered;
+mod poll_stream;
+mod remote_waker;
 
 pub mod async_utils;
 
 pub use ops::*;
 pub use linked_ops::*;
+pub use file_utils::*;
+pub use pipe_utils::*;
+pub use signal_utils::*;
+pub use blocking::*;
+pub use cancellation::*;
+pub use buffered::*;
+pub use poll_stream::*;
+pub use remote_waker::*;
 
 #[derive(Error, Debug)]
 pub enum RuntimeError {
     #[error("reactor error")]
     ReactorError(#[from] ReactorError),
+    #[error("io_uring error")]
+    IoUringError(#[from] IoUringError),
 }
 
 thread_local! {
@@ -34,6 +53,10 @@ thread_local! {
     });
     static REACTOR: RefCell<Reactor> = RefCell::new(Reactor::new().expect("Error creating io_uring reactor"));
     static COMPLETIONS: RefCell<Vec<Box<dyn FnOnce()>>> = RefCell::new(Vec::new());
+    static ON_IDLE: RefCell<Option<Box<dyn FnMut()>>> = RefCell::new(None);
+    static ON_IDLE_GENERATION: Cell<u64> = Cell::new(0);
+    static DEFAULT_OP_TIMEOUT: Cell<Option<Duration>> = Cell::new(None);
+    static RUN_BUDGET: Cell<Option<usize>> = Cell::new(None);
 }
 
 #[must_use]
@@ -43,6 +66,20 @@ pub fn async_spawn<T: 'static>(future: impl Future<Output = T> + 'static) -> Tas
     })
 }
 
+#[must_use]
+pub fn async_spawn_named<T: 'static>(name: &str, future: impl Future<Output = T> + 'static) -> TaskHandle<T>  {
+    FRONTEND.with(|e| {
+        e.spawn_named(name, future)
+    })
+}
+
+// Debugging aid, lists tasks of current thread that haven't finished yet
+pub fn list_tasks() -> Vec<TaskInfo> {
+    FRONTEND.with(|e| {
+        e.list_tasks()
+    })
+}
+
 pub fn async_yield() -> Yield {
     FRONTEND.with(|e| {
         e.yield_execution()
@@ -55,11 +92,123 @@ pub fn async_op_supported(opcode: u32) -> bool {
     })
 }
 
+// See Reactor::register_buffers for lifetime requirements
+pub unsafe fn async_register_buffers(buffers: &[&mut [u8]]) -> Result<(), RuntimeError> {
+    REACTOR.with(|r| {
+        Ok(r.borrow_mut().register_buffers(buffers)?)
+    })
+}
+
+pub fn async_unregister_buffers() -> Result<(), RuntimeError> {
+    REACTOR.with(|r| {
+        Ok(r.borrow_mut().unregister_buffers()?)
+    })
+}
+
+// Replaces registered files table, returned handles are in the same order as fds. Handles from
+// previous call refer to the new table afterwards.
+pub fn async_register_files(fds: &[RawFd]) -> Result<Vec<RegisteredFd>, RuntimeError> {
+    REACTOR.with(|r| {
+        r.borrow_mut().register_files(fds)?;
+        Ok((0..fds.len()).map(|index| RegisteredFd::new(index as u32)).collect())
+    })
+}
+
+// Points already registered slot to another fd, e.g. after reconnect
+pub fn async_update_file(registered: &RegisteredFd, fd: RawFd) -> Result<(), RuntimeError> {
+    REACTOR.with(|r| {
+        Ok(r.borrow_mut().update_files(registered.index(), slice::from_ref(&fd))?)
+    })
+}
+
+pub fn async_unregister_files() -> Result<(), RuntimeError> {
+    REACTOR.with(|r| {
+        Ok(r.borrow_mut().unregister_files()?)
+    })
+}
+
+pub fn async_pending_ops() -> u32 {
+    REACTOR.with(|r| {
+        r.borrow().pending_ops()
+    })
+}
+
+pub fn async_reactor_stats() -> ReactorStats {
+    REACTOR.with(|r| {
+        r.borrow().stats()
+    })
+}
+
+pub fn async_reactor_clear_last_error() {
+    REACTOR.with(|r| {
+        r.borrow_mut().clear_last_error()
+    })
+}
+
+// Callback is invoked when the loop is about to block on the reactor (or exit) - no task is ready
+// and no completion is waiting to be handled
+pub fn runtime_on_idle(callback: Box<dyn FnMut()>) {
+    ON_IDLE_GENERATION.with(|g| g.set(g.get() + 1));
+    ON_IDLE.with(|c| {
+        *c.borrow_mut() = Some(callback);
+    })
+}
+
+pub fn runtime_clear_on_idle() {
+    ON_IDLE_GENERATION.with(|g| g.set(g.get() + 1));
+    ON_IDLE.with(|c| {
+        c.borrow_mut().take();
+    })
+}
+
+// Limits number of tasks polled before reactor gets a turn, so tasks that keep yielding can't
+// starve IO completions. None (the default) runs tasks until none is ready.
+pub fn runtime_set_run_budget(budget: Option<usize>) {
+    assert!(budget != Some(0));
+    RUN_BUDGET.with(|b| b.set(budget))
+}
+
+// Timeout applied to reads, writes, splices and connects created afterwards on this thread, so stuck
+// peer can't block them forever. Explicit timeout() or clear_timeout() on an op takes precedence.
+// Accept, poll and sleep ops are never affected, waiting indefinitely is their normal state.
+pub fn set_default_op_timeout(timeout: Option<Duration>) {
+    DEFAULT_OP_TIMEOUT.with(|t| t.set(timeout))
+}
+
+pub fn default_op_timeout() -> Option<Duration> {
+    DEFAULT_OP_TIMEOUT.with(|t| t.get())
+}
+
+fn op_default_timeout(op: &IOUringOp) -> Option<Duration> {
+    match op {
+        IOUringOp::Read(..) | IOUringOp::Write(..) | IOUringOp::ReadFixed(..) | IOUringOp::WriteFixed(..)
+            | IOUringOp::Splice(..) | IOUringOp::Tee(..) | IOUringOp::Connect(..) => default_op_timeout(),
+        _ => None,
+    }
+}
+
 pub fn async_run<T: 'static>(future: impl Future<Output = T> + 'static) -> T {
     let handle = async_spawn(future);
 
     loop {
-        local_executor_run_all();
+        if local_executor_run_budget() {
+            // ready tasks are still waiting, so reactor can't block
+            local_reactor_poll_ops();
+            continue;
+        }
+
+        // completions that already arrived don't require waiting
+        if local_reactor_poll_ops() {
+            continue;
+        }
+
+        local_runtime_idle();
+
+        // idle callback may have spawned or woken tasks
+        if local_executor_has_ready_tasks() {
+            continue;
+        }
+
         let made_progress = local_reactor_process_ops();
         if !made_progress {
             break;
@@ -69,13 +218,45 @@ pub fn async_run<T: 'static>(future: impl Future<Output = T> + 'static) -> T {
     handle.result().unwrap()
 }
 
-fn local_executor_run_all() {
+// Returns true when budget was exhausted before all ready tasks were run
+fn local_executor_run_budget() -> bool {
+    let budget = RUN_BUDGET.with(|b| b.get());
     EXECUTOR.with(|e| {
         let mut e = e.borrow_mut();
-        while e.has_ready_tasks() {
-            e.run_all();
+        match budget {
+            None => {
+                while e.has_ready_tasks() {
+                    e.run_all();
+                }
+
+                false
+            },
+            Some(budget) => {
+                e.run_budget(budget);
+                e.has_ready_tasks()
+            },
         }
-    });
+    })
+}
+
+fn local_executor_has_ready_tasks() -> bool {
+    EXECUTOR.with(|e| {
+        e.borrow().has_ready_tasks()
+    })
+}
+
+fn local_runtime_idle() {
+    // callback is taken out for the call, so it can replace or clear itself. It is put back only
+    // if neither runtime_on_idle nor runtime_clear_on_idle was called in the meantime.
+    let callback = ON_IDLE.with(|c| c.borrow_mut().take());
+    if let Some(mut callback) = callback {
+        let generation = ON_IDLE_GENERATION.with(|g| g.get());
+        callback();
+
+        if ON_IDLE_GENERATION.with(|g| g.get()) == generation {
+            ON_IDLE.with(|c| *c.borrow_mut() = Some(callback));
+        }
+    }
 }
 
 fn local_reactor_process_ops() -> bool {
@@ -83,10 +264,22 @@ fn local_reactor_process_ops() -> bool {
         r.borrow_mut().process_ops().expect("io_uring error")
     });
 
+    local_run_completions();
+    processed
+}
+
+fn local_reactor_poll_ops() -> bool {
+    let handled = REACTOR.with(|r| {
+        r.borrow_mut().poll_ops().expect("io_uring error")
+    });
+
+    local_run_completions();
+    handled
+}
+
+fn local_run_completions() {
     let completions = COMPLETIONS.with(|c| std::mem::take(&mut *c.borrow_mut()));
     completions.into_iter().for_each(|f| f());
-
-    processed
 }
 
 pub trait AsyncOpResult : Unpin {
@@ -110,8 +303,8 @@ impl<T> AsyncValue<T> {
     }
 }
 
-// iouring request, result, auto-cancel flag, submit-immediately
-pub struct AsyncOp<T: AsyncOpResult> (IOUringReq, Rc<Cell<AsyncValue<T::Output>>>, bool, bool);
+// iouring request, result, auto-cancel flag, submit-immediately, cancellation scope
+pub struct AsyncOp<T: AsyncOpResult> (IOUringReq, Rc<Cell<AsyncValue<T::Output>>>, bool, bool, Option<CancellationScope>);
 
 impl<T: AsyncOpResult> Drop for AsyncOp<T> {
     fn drop(&mut self) {
@@ -141,12 +334,15 @@ impl<T: AsyncOpResult> Drop for AsyncOp<T> {
 impl<T: AsyncOpResult> AsyncOp<T> {
     fn new(op: IOUringOp) -> Self {
         let req = IOUringReq {
+            timeout: op_default_timeout(&op),
             op,
             completion: None,
-            timeout: None,
+            multishot: None,
+            fixed_file: false,
+            drain: false,
         };
 
-        Self(req, Rc::new(Cell::new(AsyncValue::InProgress)), false, false)
+        Self(req, Rc::new(Cell::new(AsyncValue::InProgress)), false, false, None)
     }
 
     pub fn schedule(mut self, handler: impl FnOnce(T::Output) + 'static) -> (u64, usize) {
@@ -166,10 +362,23 @@ impl<T: AsyncOpResult> AsyncOp<T> {
             }
         });
 
-        match &self.0.op {
+        let token = match &self.0.op {
             &IOUringOp::InProgress(cancel) => cancel,
             _ => panic!("io_uring schedling failed"),
+        };
+
+        if let Some(scope) = &self.4 {
+            scope.add(token);
         }
+
+        token
+    }
+
+    // Op is driven by its own task, so several of them can be started and awaited later.
+    // Task runs before reactor gets control again, so ops spawned together are submitted in one
+    // batch. Dropping or cancelling the handle cancels the op.
+    pub fn spawn(self) -> TaskHandle<T::Output> where T: 'static {
+        async_spawn(self)
     }
 
     pub fn timeout(mut self, timeout: Duration) -> Self {
@@ -182,10 +391,27 @@ impl<T: AsyncOpResult> AsyncOp<T> {
         self
     }
 
+    pub fn scope(mut self, scope: &CancellationScope) -> Self {
+        self.4 = Some(scope.clone());
+        self
+    }
+
+    // Acts as a barrier - op waits for everything submitted before it and ops submitted
+    // after wait for it. Serializes the whole ring, so use sparingly.
+    pub fn drain(mut self, value: bool) -> Self {
+        self.0.drain = value;
+        self
+    }
+
     pub fn submit_immediately(mut self, value: bool) -> Self {
         self.3 = value;
         self
     }
+
+    fn fixed_file(mut self, value: bool) -> Self {
+        self.0.fixed_file = value;
+        self
+    }
 }
 
 impl<T: AsyncOpResult> Future for AsyncOp<T> {
@@ -213,6 +439,10 @@ impl<T: AsyncOpResult> Future for AsyncOp<T> {
                     r.borrow_mut().schedule_linked2(slice::from_mut(&mut &mut self.0))
                 });
 
+                if let (IOUringOp::InProgress(token), Some(scope)) = (&self.0.op, &self.4) {
+                    scope.add(*token);
+                }
+
                 self.2 = true;
                 Poll::Pending
             },
@@ -236,9 +466,10 @@ fn async_run_all() {
 
 #[cfg(test)]
 mod tests {
-    use std::os::fd::{OwnedFd, FromRawFd};
+    use std::os::fd::{OwnedFd, FromRawFd, AsRawFd};
 
     use fbs_library::poll::PollMask;
+    use fbs_library::socket_address::SocketIpAddress;
 
     use super::*;
 
@@ -254,6 +485,50 @@ mod tests {
         assert_eq!(handle1.result(), Some(123));
     }
 
+    #[test]
+    fn local_list_tasks_test() {
+        let result = async_run(async {
+            let sleeper = async_spawn_named("sleeper", async_sleep(Duration::new(1, 0)));
+            async_yield().await;
+
+            let tasks = list_tasks();
+            assert!(tasks.contains(&TaskInfo { name: Some("sleeper".to_string()), state: TaskState::Waiting }));
+
+            // calling task is running, so it's reported as ready
+            assert!(tasks.contains(&TaskInfo { name: None, state: TaskState::Ready }));
+
+            sleeper.cancel();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_op_spawn_test() {
+        let result = async_run(async {
+            let started = std::time::Instant::now();
+
+            let handles: Vec<_> = (1..=3).map(|i| async_sleep_with_result(Duration::new(0, 10_000_000 * i)).spawn()).collect();
+            let cancelled = async_sleep_with_result(Duration::new(10, 0)).spawn();
+
+            let mut completed = 0;
+            for handle in handles {
+                assert!(handle.await.is_ok());
+                completed += 1;
+            }
+
+            // sleeps ran concurrently
+            assert!(started.elapsed() < Duration::new(0, 60_000_000));
+            assert_eq!(cancelled.cancel(), TaskCancelResult::Cancelled);
+            completed
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 3);
+    }
+
     #[test]
     fn local_yield_test() {
         let handle1 = async_spawn(async {
@@ -301,6 +576,125 @@ mod tests {
         assert_eq!(result, 1);
     }
 
+    #[test]
+    fn local_openat_relative_test() {
+        let result = async_run(async {
+            let directory = std::env::temp_dir().join(format!("fbs-openat-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+
+            let dirfd = async_open(&directory, OpenMode::new().set_flags(libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC)).await;
+            assert!(dirfd.is_ok());
+            let dirfd = dirfd.unwrap();
+
+            let fd = async_openat(&dirfd, "relative.txt", OpenMode::new().create(true, 0o644)).await;
+            assert!(fd.is_ok());
+
+            let result = async_write(&fd.unwrap(), b"relative".to_vec(), None).await;
+            assert!(result.is_ok());
+
+            assert_eq!(std::fs::read(directory.join("relative.txt")).unwrap(), b"relative");
+            std::fs::remove_dir_all(&directory).unwrap();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_fallocate_test() {
+        let result = async_run(async {
+            let path = std::env::temp_dir().join(format!("fbs-fallocate-{}", std::process::id()));
+            let fd = async_open(&path, OpenMode::new().create(true, 0o644).close_on_exec(true)).await;
+            assert!(fd.is_ok());
+            let fd = fd.unwrap();
+
+            let result = async_fallocate(&fd, 0, 0, 1024 * 1024).await;
+            assert!(result.is_ok());
+
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            assert_eq!(unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) }, 0);
+            assert_eq!(stat.st_size, 1024 * 1024);
+
+            std::fs::remove_file(&path).unwrap();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_fadvise_test() {
+        let result = async_run(async {
+            let fd = async_open("/proc/self/exe", OpenMode::new().read_only().close_on_exec(true)).await;
+            assert!(fd.is_ok());
+
+            let result = async_fadvise(&fd.unwrap(), 0, 0, libc::POSIX_FADV_SEQUENTIAL).await;
+            assert!(result.is_ok());
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    #[allow(clippy::zombie_processes)]   // child is reaped by waitid
+    fn local_waitid_test() {
+        let result = async_run(async {
+            if !async_op_supported(IOUringOpType::WAITID) {
+                return 1;
+            }
+
+            let child = std::process::Command::new("sh").args(["-c", "exit 7"]).spawn().unwrap();
+            let status = async_waitid(child.id() as libc::pid_t).await;
+            assert_eq!(status.unwrap(), fbs_library::process::ChildExitStatus::Exited(7));
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_drain_test() {
+        let result = async_run(async {
+            let directory = std::env::temp_dir().join(format!("fbs-drain-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+
+            let order = Rc::new(RefCell::new(vec![]));
+            let mut files = vec![];
+            for i in 0..4 {
+                let fd = async_open(directory.join(format!("file-{}", i)), OpenMode::new().create(true, 0o644).close_on_exec(true)).await.unwrap();
+                let order = order.clone();
+                async_write(&fd, vec![0; 64 * 1024], None).schedule(move |result| {
+                    assert!(result.is_ok());
+                    order.borrow_mut().push(i);
+                });
+
+                files.push(fd);
+            }
+
+            let order_cpy = order.clone();
+            async_fsync(&files[0], false).drain(true).schedule(move |result| {
+                assert!(result.is_ok());
+                order_cpy.borrow_mut().push(100);
+            });
+
+            while order.borrow().len() < 5 {
+                async_sleep(Duration::from_millis(1)).await;
+            }
+
+            assert_eq!(order.borrow().last(), Some(&100));
+            std::fs::remove_dir_all(&directory).unwrap();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
     #[test]
     fn local_openat2_and_write_test() {
         #[repr(C, packed)]
@@ -438,6 +832,58 @@ mod tests {
         assert_eq!(result, 1);
     }
 
+    #[test]
+    fn local_linked_ops_scope_test() {
+        let result = async_run(async {
+            let scope = CancellationScope::new();
+            let mut ops = AsyncLinkedOps::new();
+
+            let r1 = ops.add(async_nop());
+            let r2 = ops.add(async_sleep_with_result(Duration::new(10, 0)).scope(&scope));
+
+            let chain = async_spawn(ops);
+            async_yield().await;
+
+            // chain is bound to scope once scheduled
+            scope.cancel_all();
+
+            assert_eq!(chain.await, false);
+            assert!(r1.value().is_ok());
+            assert!(r2.value().is_err_and(|e| e.cancelled()));
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_linked_ops_timeout_broken_chain_test() {
+        use fbs_library::system_error::SystemError;
+
+        let result = async_run(async {
+            let testfd = unsafe { OwnedFd::from_raw_fd(libc::dup(0)) };
+            let mut ops = AsyncLinkedOps::new();
+
+            let r1 = ops.add(async_read_into(&-1, vec![], None));
+            let r2 = ops.add(async_read_into(&testfd, Vec::with_capacity(10), None).timeout(Duration::new(10, 0)));
+
+            assert_eq!(ops.await, false);
+            assert_eq!(r1.value(), Err((SystemError::new(libc::EBADF), vec![])));
+
+            // link never ran because chain broke, its own timeout didn't fire
+            let error = r2.value().err().unwrap().0;
+            assert!(error.cancelled());
+            assert!(!error.timed_out());
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
     #[test]
     fn local_schedule_close() {
         let called = Rc::new(Cell::new(false));
@@ -541,7 +987,7 @@ mod tests {
             let data = data.await;
 
             assert!(data.is_err());
-            assert!(data.err().unwrap().0.cancelled());
+            assert!(data.err().unwrap().0.timed_out());
             1
         });
 
@@ -550,34 +996,153 @@ mod tests {
     }
 
     #[test]
-    fn local_read_timeout_test_notimeout() {
-        let result = async_run(async {
+    fn local_read_timeout_cancel_test() {
+        let called = Rc::new(Cell::new(false));
+        let called_orig = called.clone();
+
+        let result = async_run(async move {
+            let testfd = unsafe { OwnedFd::from_raw_fd(libc::dup(0)) };
             let mut buffer = Vec::new();
             buffer.resize(100, 0);
 
-            let data = async_read_into(&-1, buffer, None).timeout(Duration::new(0, 1_000_000));
-            let data = data.await;
+            // cancel before timeout fires is still reported as cancel
+            let token = async_read_into(&testfd, buffer, None).timeout(Duration::new(10, 0)).schedule(move |result| {
+                let error = result.err().unwrap().0;
+                assert!(error.cancelled());
+                assert!(!error.timed_out());
+                called.set(true);
+            });
 
-            assert!(data.is_err());
-            assert_eq!(data.err().unwrap().0.errno(), libc::EBADF);
+            let _ = async_cancel(token).await;
             1
         });
 
+        assert_eq!(called_orig.get(), true);
+
         // ensure it actually executed
         assert_eq!(result, 1);
     }
 
     #[test]
-    fn local_cancel() {
-        use std::time::{Duration, SystemTime};
-
+    fn local_default_op_timeout_test() {
         let result = async_run(async {
-            let handle1 = async_spawn(async {
-                async_sleep(Duration::new(4, 0)).await;
-            });
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, _write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            set_default_op_timeout(Some(Duration::new(0, 1_000_000)));
+            let data = async_read_into(&read_end, Vec::with_capacity(10), None).await;
+            assert!(data.err().unwrap().0.timed_out());
+
+            // explicit timeout wins over default one
+            set_default_op_timeout(Some(Duration::new(10, 0)));
+            let now = std::time::Instant::now();
+            let data = async_read_into(&read_end, Vec::with_capacity(10), None).timeout(Duration::new(0, 1_000_000)).await;
+            assert!(data.err().unwrap().0.timed_out());
+            assert!(now.elapsed() < Duration::new(10, 0));
+
+            // sleeps are not bounded by default timeout
+            set_default_op_timeout(Some(Duration::new(0, 1_000_000)));
+            let result = async_sleep_with_result(Duration::new(0, 5_000_000)).await;
+            assert!(result.is_ok());
 
-            let now = SystemTime::now();
-            async_sleep(Duration::new(0, 1_000_000)).await;
+            set_default_op_timeout(None);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_read_some_eof_test() {
+        let result = async_run(async {
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            let written = async_write(&write_end, b"test".to_vec(), None).await;
+            assert!(written.is_ok());
+
+            let data = async_read_some(&read_end, Vec::with_capacity(10), None).await;
+            assert_eq!(data, Ok(ReadOutcome::Data(b"test".to_vec())));
+
+            drop(write_end);
+            let data = async_read_some(&read_end, Vec::with_capacity(10), None).await;
+            match data {
+                Ok(ReadOutcome::Eof(buffer)) => assert_eq!(buffer.capacity(), 10),
+                other => panic!("expected eof, got {:?}", other),
+            }
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_buf_reader_timeout_test() {
+        let result = async_run(async {
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            let written = async_write(&write_end, b"te".to_vec(), None).await;
+            assert!(written.is_ok());
+
+            // only part of the data arrives, so the read stalls until timeout
+            let mut reader = AsyncBufReader::new(read_end);
+            reader.set_timeout(Some(Duration::from_millis(10)));
+
+            let mut target = [0; 4];
+            match reader.read_exact(&mut target).await {
+                Err(AsyncBufReadError::ReadError(error)) => assert!(error.timed_out()),
+                other => panic!("expected timeout, got {:?}", other),
+            }
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_read_timeout_test_notimeout() {
+        let result = async_run(async {
+            let mut buffer = Vec::new();
+            buffer.resize(100, 0);
+
+            let data = async_read_into(&-1, buffer, None).timeout(Duration::new(0, 1_000_000));
+            let data = data.await;
+
+            assert!(data.is_err());
+            assert_eq!(data.err().unwrap().0.errno(), libc::EBADF);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_cancel() {
+        use std::time::{Duration, SystemTime};
+
+        let result = async_run(async {
+            let handle1 = async_spawn(async {
+                async_sleep(Duration::new(4, 0)).await;
+            });
+
+            let now = SystemTime::now();
+            async_sleep(Duration::new(0, 1_000_000)).await;
 
             handle1.cancel();
             let elapsed = now.elapsed();
@@ -590,6 +1155,393 @@ mod tests {
         assert_eq!(result, 1);
     }
 
+    #[test]
+    fn local_cancel_result() {
+        use std::time::Duration;
+
+        let result = async_run(async {
+            let handle1 = async_spawn(async {
+                async_sleep(Duration::new(10, 0)).await;
+            });
+
+            // let the task start and submit its sleep
+            async_yield().await;
+            let pending = async_pending_ops();
+            assert!(pending > 0);
+
+            assert_eq!(handle1.cancel(), TaskCancelResult::Cancelled);
+
+            async_sleep(Duration::new(0, 1_000_000)).await;
+            assert!(async_pending_ops() < pending);
+
+            let handle2 = async_spawn(async {});
+            async_yield().await;
+            assert_eq!(handle2.cancel(), TaskCancelResult::AlreadyCompleted);
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_ops_table_compaction() {
+        use std::time::Duration;
+
+        let result = async_run(async {
+            let baseline = async_reactor_stats().ops_table_size;
+
+            let handles: Vec<_> = (0..1000).map(|_| async_spawn(async {
+                async_sleep(Duration::new(0, 1_000_000)).await;
+            })).collect();
+
+            async_yield().await;
+            assert!(async_reactor_stats().ops_table_size >= 1000);
+
+            for handle in handles {
+                handle.await;
+            }
+
+            // compaction runs when reactor processes completions
+            async_nop().await.unwrap();
+
+            let stats = async_reactor_stats();
+            assert!(stats.ops_table_size <= std::cmp::max(baseline, 64));
+            assert!(stats.ops_table_capacity < 1000);
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_fixed_buffers_test() {
+        let mut write_buffer = vec![0u8; 4096];
+        let mut read_buffer = vec![0u8; 4096];
+
+        write_buffer[..4].copy_from_slice(b"test");
+
+        let result = async_run(async move {
+            let registered = unsafe { async_register_buffers(&[write_buffer.as_mut_slice(), read_buffer.as_mut_slice()]) };
+            assert!(registered.is_ok());
+
+            let fd = async_open("/tmp/testowy-uring-fixed.txt", OpenMode::new().create(true, 0o777)).await.unwrap();
+
+            let written = async_write_fixed(&fd, 0, 4, Some(0)).await;
+            assert_eq!(written, Ok(4));
+
+            let read = async_read_fixed(&fd, 1, 4096, Some(0)).await;
+            assert_eq!(read, Ok(4));
+
+            let unregistered = async_unregister_buffers();
+            assert!(unregistered.is_ok());
+
+            // buffers must stay alive until unregistered
+            assert_eq!(&read_buffer[..4], b"test");
+            drop(write_buffer);
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_registered_files_test() {
+        let result = async_run(async {
+            let fd = async_open("/tmp/testowy-uring-registered.txt", OpenMode::new().create(true, 0o777)).await.unwrap();
+
+            let registered = async_register_files(&[fd.as_raw_fd()]).unwrap();
+            assert_eq!(registered.len(), 1);
+
+            let result = async_write(&registered[0], b"test".to_vec(), Some(0)).await;
+            assert_eq!(result.unwrap().len(), 4);
+
+            let result = async_read_into(&registered[0], Vec::with_capacity(10), Some(0)).await;
+            assert_eq!(result.unwrap(), b"test");
+
+            // slot now points to a different file
+            let other = async_open("/tmp/testowy-uring-registered2.txt", OpenMode::new().create(true, 0o777).truncate(true)).await.unwrap();
+            assert!(async_update_file(&registered[0], other.as_raw_fd()).is_ok());
+
+            let result = async_read_into(&registered[0], Vec::with_capacity(10), Some(0)).await;
+            assert_eq!(result.unwrap().len(), 0);
+
+            assert!(async_unregister_files().is_ok());
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_register_files_twice_test() {
+        let result = async_run(async {
+            let first = async_open("/tmp/testowy-uring-register-twice.txt", OpenMode::new().create(true, 0o777).truncate(true)).await.unwrap();
+            let second = async_open("/tmp/testowy-uring-register-twice2.txt", OpenMode::new().create(true, 0o777).truncate(true)).await.unwrap();
+
+            assert!(async_register_files(&[first.as_raw_fd()]).is_ok());
+
+            // previous table is replaced instead of failing with EBUSY
+            let registered = async_register_files(&[first.as_raw_fd(), second.as_raw_fd()]).unwrap();
+            assert_eq!(registered.len(), 2);
+
+            let result = async_write(&registered[1], b"test".to_vec(), Some(0)).await;
+            assert_eq!(result.unwrap().len(), 4);
+
+            let result = async_read_into(&second, Vec::with_capacity(10), Some(0)).await;
+            assert_eq!(result.unwrap(), b"test");
+
+            assert!(async_unregister_files().is_ok());
+            assert!(async_unregister_files().is_ok());
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_splice_test() {
+        use fbs_library::pipe::*;
+
+        let (rx, tx) = pipe(PipeFlags::default()).unwrap();
+
+        let result = async_run(async move {
+            let fd = async_open("/tmp/testowy-uring-splice.txt", OpenMode::new().create(true, 0o777).truncate(true)).await.unwrap();
+
+            let written = async_write(&tx, b"test".to_vec(), None).await;
+            assert!(written.is_ok());
+
+            let moved = async_splice(&rx, None, &fd, Some(0), 4, 0).await;
+            assert_eq!(moved, Ok(4));
+
+            let result = async_read_into(&fd, Vec::with_capacity(10), Some(0)).await;
+            assert_eq!(result.unwrap(), b"test");
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_tee_test() {
+        use fbs_library::pipe::*;
+
+        let (rx1, tx1) = pipe(PipeFlags::default()).unwrap();
+        let (rx2, tx2) = pipe(PipeFlags::default()).unwrap();
+
+        let result = async_run(async move {
+            let written = async_write(&tx1, b"test".to_vec(), None).await;
+            assert!(written.is_ok());
+
+            let duplicated = async_tee(&rx1, &tx2, 4, 0).await;
+            assert_eq!(duplicated, Ok(4));
+
+            // data is still available in the source pipe
+            let result = async_read_into(&rx1, Vec::with_capacity(10), None).await;
+            assert_eq!(result.unwrap(), b"test");
+
+            let result = async_read_into(&rx2, Vec::with_capacity(10), None).await;
+            assert_eq!(result.unwrap(), b"test");
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_shutdown_test() {
+        let mut fds = [0; 2];
+        let error = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr()) };
+        assert_eq!(error, 0);
+
+        let (first, second) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        let result = async_run(async move {
+            let result = async_shutdown(&first, SocketShutdown::Write).await;
+            assert!(result.is_ok());
+
+            // peer sees end of stream
+            let result = async_read_into(&second, Vec::with_capacity(10), None).await;
+            assert_eq!(result.unwrap().len(), 0);
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_connect_timeout_test() {
+        let result = async_run(async {
+            let listener = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+            listener.set_option(SocketOptions::ReuseAddr(true)).unwrap();
+            listener.listen(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap(), 0).unwrap();
+            let address = listener.local_address().unwrap();
+
+            // nothing is accepted, so once backlog is full, SYNs are dropped and connect hangs
+            let mut sockets = vec![];
+            let mut timed_out = false;
+            for _ in 0..8 {
+                let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+                let result = async_connect_timeout(&socket, address, Duration::new(0, 100_000_000)).await;
+                sockets.push(socket);
+
+                match result {
+                    Ok(_) => (),
+                    Err(error) => {
+                        assert!(error.timed_out());
+                        timed_out = true;
+                        break;
+                    },
+                }
+            }
+
+            assert!(timed_out);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_write_struct_roundtrip_test() {
+        // padded and aligned fields, unlike packed byte struct used in openat2 test
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct TestHeader {
+            kind: u8,
+            flags: u16,
+            length: u32,
+            sequence: u64,
+        }
+
+        let result = async_run(async {
+            let (read_end, write_end) = async_pipe().unwrap();
+            let header = TestHeader { kind: 3, flags: 0x0102, length: 1024, sequence: u64::MAX - 1 };
+
+            let written = async_write_struct(&write_end, header, None).await.unwrap();
+            assert_eq!(written.len(), std::mem::size_of::<TestHeader>());
+
+            let result = async_read_struct::<TestHeader>(&read_end, None).await;
+            assert_eq!(result, Ok(header));
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_read_struct_exact_test() {
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct TestStruct(u32, u32);
+
+        let result = async_run(async {
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            let mut data = vec![];
+            data.extend_from_slice(&7u32.to_ne_bytes());
+            data.extend_from_slice(&9u32.to_ne_bytes());
+            let tail = data.split_off(3);
+
+            // struct arrives in two parts
+            let writer = async_spawn(async move {
+                assert!(async_write(&write_end, data, None).await.is_ok());
+                async_sleep(Duration::new(0, 1_000_000)).await;
+                assert!(async_write(&write_end, tail, None).await.is_ok());
+            });
+
+            let result = async_read_struct_exact::<TestStruct>(&read_end, None).await;
+            assert_eq!(result, Ok(TestStruct(7, 9)));
+            writer.await;
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_accept_from_test() {
+        let result = async_run(async {
+            let listener = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+            listener.set_option(SocketOptions::ReuseAddr(true)).unwrap();
+            listener.listen(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap(), 10).unwrap();
+            let address = listener.local_address().unwrap();
+
+            let client = async_spawn(async move {
+                let socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
+                assert!(async_connect(&socket, address).await.is_ok());
+                socket
+            });
+
+            let result = async_accept_from(&listener, libc::SOCK_CLOEXEC).await;
+            assert!(result.is_ok());
+
+            let (_socket, peer) = result.unwrap();
+            assert_eq!(peer.address(), address.address());
+            assert_ne!(peer.port(), 0);
+            assert_ne!(peer.port(), address.port());
+
+            client.await;
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_datagram_test() {
+        let result = async_run(async {
+            let server = Socket::new(SocketDomain::Inet, SocketType::Datagram, SocketFlags::new().close_on_exec(true).flags());
+            server.bind(&SocketIpAddress::from_text("127.0.0.1:0", None).unwrap()).unwrap();
+            let server_address = server.local_address().unwrap();
+
+            let client = Socket::new(SocketDomain::Inet, SocketType::Datagram, SocketFlags::new().close_on_exec(true).flags());
+            let result = async_sendto(&client, b"ping".to_vec(), server_address).await;
+            assert_eq!(result.unwrap().len(), 4);
+
+            let result = async_recvfrom(&server, Vec::with_capacity(64)).await;
+            let (data, peer) = result.unwrap();
+            assert_eq!(data, b"ping");
+            assert_eq!(peer.address(), server_address.address());
+
+            let result = async_sendto(&server, data, peer).await;
+            assert!(result.is_ok());
+
+            let (data, peer) = async_recvfrom(&client, Vec::with_capacity(64)).await.unwrap();
+            assert_eq!(data, b"ping");
+            assert_eq!(peer.port(), server_address.port());
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
     #[test]
     fn local_linked_cancel_test() {
         use std::time::{Duration, SystemTime};
@@ -656,6 +1608,99 @@ mod tests {
         assert_eq!(result, 1);
     }
 
+    #[test]
+    fn local_linked_drop_test() {
+        let result = async_run(async {
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            let handle = async_spawn(async move {
+                let mut ops = AsyncLinkedOps::new();
+
+                ops.add(async_read_into(&read_end, Vec::with_capacity(100), None));
+                ops.add(async_sleep(Duration::new(10, 0)));
+
+                ops.await
+            });
+
+            async_sleep(Duration::new(0, 1_000_000)).await;
+            assert_eq!(async_pending_ops(), 2);
+
+            // dropping chain mid-flight must not leave any link behind
+            handle.cancel();
+            async_sleep(Duration::new(0, 10_000_000)).await;
+            assert_eq!(async_pending_ops(), 0);
+
+            drop(write_end);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_read_reuse_test() {
+        let result = async_run(async {
+            let (read_end, write_end) = async_pipe().unwrap();
+            let mut buffer = Vec::with_capacity(64);
+            let capacity = buffer.capacity();
+
+            for chunk in [&b"first"[..], b"2", b"third chunk"] {
+                async_write(&write_end, chunk.to_vec(), None).await.unwrap();
+
+                buffer = async_read_reuse(&read_end, buffer, None).await.unwrap();
+                assert_eq!(buffer, chunk);
+                assert_eq!(buffer.capacity(), capacity);
+            }
+
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_linked_deadline_test() {
+        use std::time::Instant;
+
+        let result = async_run(async {
+            let (read_end, write_end) = async_pipe().unwrap();
+
+            // deadline cancels the chain, even though it is stuck in the first link
+            let mut ops = AsyncLinkedOps::new();
+            let r1 = ops.add(async_read_into(&read_end, Vec::with_capacity(100), None));
+            let r2 = ops.add(async_sleep_with_result(Duration::new(10, 0)));
+            ops.deadline(Duration::from_millis(20));
+
+            let now = Instant::now();
+            assert_eq!(ops.await, false);
+            assert!(now.elapsed() < Duration::new(1, 0));
+            assert!(r1.value().is_err_and(|(e, _)| e.cancelled()));
+            assert!(r2.value().is_err_and(|e| e.cancelled()));
+
+            // chain completing in time removes deadline timer as well
+            let mut ops = AsyncLinkedOps::new();
+            ops.add(async_nop());
+            ops.add(async_sleep(Duration::from_millis(1)));
+            ops.deadline(Duration::new(10, 0));
+
+            assert_eq!(ops.await, true);
+            async_sleep(Duration::from_millis(10)).await;
+            assert_eq!(async_pending_ops(), 0);
+
+            drop(write_end);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
     #[test]
     fn local_schedule_timeout_update() {
         use std::time::SystemTime;
@@ -719,4 +1764,211 @@ mod tests {
         assert_eq!(called_orig.get(), true);
     }
 
+    #[test]
+    fn local_on_idle_test() {
+        let idle_calls = Rc::new(Cell::new(0));
+        let idle_calls_orig = idle_calls.clone();
+
+        runtime_on_idle(Box::new(move || {
+            idle_calls.set(idle_calls.get() + 1);
+        }));
+
+        let idle_calls = idle_calls_orig.clone();
+        async_run(async move {
+            for _ in 0..3 {
+                // busy processing - no idle callbacks expected while tasks are runnable
+                let before = idle_calls.get();
+                for _ in 0..10 {
+                    async_yield().await;
+                }
+                assert_eq!(idle_calls.get(), before);
+
+                async_sleep(Duration::new(0, 1_000_000)).await;
+                assert!(idle_calls.get() > before);
+            }
+        });
+
+        runtime_clear_on_idle();
+        assert!(idle_calls_orig.get() >= 3);
+    }
+
+    #[test]
+    fn local_on_idle_clear_itself_test() {
+        let idle_calls = Rc::new(Cell::new(0));
+        let idle_calls_orig = idle_calls.clone();
+
+        runtime_on_idle(Box::new(move || {
+            idle_calls.set(idle_calls.get() + 1);
+            runtime_clear_on_idle();
+        }));
+
+        async_run(async {
+            for _ in 0..3 {
+                async_sleep(Duration::new(0, 1_000_000)).await;
+            }
+        });
+
+        // cleared during the first call, so it is not restored afterwards
+        assert_eq!(idle_calls_orig.get(), 1);
+    }
+
+    #[test]
+    fn local_on_idle_before_block_test() {
+        let pending = Rc::new(RefCell::new(Vec::new()));
+        let pending_clone = pending.clone();
+
+        runtime_on_idle(Box::new(move || {
+            pending_clone.borrow_mut().push(async_pending_ops());
+        }));
+
+        async_run(async {
+            for _ in 0..3 {
+                async_sleep(Duration::new(0, 1_000_000)).await;
+            }
+        });
+
+        runtime_clear_on_idle();
+
+        // every call but the last one (before exit) happened with an op to block on
+        let pending = pending.borrow();
+        assert!(pending.len() >= 4);
+        assert!(pending[..pending.len() - 1].iter().all(|ops| *ops > 0));
+        assert_eq!(*pending.last().unwrap(), 0);
+    }
+
+    #[test]
+    fn local_detach_test() {
+        let completed = Rc::new(Cell::new(false));
+        let completed_clone = completed.clone();
+
+        let result = async_run(async move {
+            async_spawn(async move {
+                async_sleep(Duration::from_millis(10)).await;
+                completed_clone.set(true);
+            }).detach();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+        assert!(completed.get());
+    }
+
+    #[test]
+    fn local_pipe_wakeup_test() {
+        let result = async_run(async {
+            let (rx, tx) = async_pipe().unwrap();
+            let woken = Rc::new(Cell::new(0));
+            let woken_clone = woken.clone();
+
+            let waiter = async_spawn(async move {
+                let mut total = 0;
+                while total < 3 {
+                    let count = async_pipe_read(&rx).await.unwrap();
+                    assert!(count > 0);
+                    total += count;
+                    woken_clone.set(total);
+                }
+            });
+
+            for _ in 0..3 {
+                async_pipe_write(&tx).await.unwrap();
+            }
+
+            waiter.await;
+            assert_eq!(woken.get(), 3);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_read_signals_test() {
+        use fbs_library::signalfd::{SignalFd, SignalFdFlags};
+        use fbs_library::sigset::{Signal, SignalSet, SignalMask, set_process_signal_mask};
+
+        let result = async_run(async {
+            let mut mask = SignalSet::empty();
+            mask.add(Signal::SIGUSR1);
+            mask.add(Signal::SIGUSR2);
+
+            set_process_signal_mask(SignalMask::Block, mask).unwrap();
+            let sigfd = SignalFd::new(mask, SignalFdFlags::new().close_on_exec(true).flags()).unwrap();
+
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+                libc::raise(libc::SIGUSR2);
+            }
+
+            let infos = async_read_signals(&sigfd).await.unwrap();
+            assert_eq!(infos.len(), 2);
+
+            set_process_signal_mask(SignalMask::Unblock, mask).unwrap();
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_cq_overflow_test() {
+        let result = async_run(async {
+            // far more ops than completion queue has room for (64 entries by default)
+            let handles: Vec<_> = (0..1000).map(|_| async_nop().spawn()).collect();
+
+            let mut completed = 0;
+            for handle in handles {
+                handle.await;
+                completed += 1;
+            }
+
+            assert_eq!(completed, 1000);
+            assert_eq!(async_pending_ops(), 0);
+
+            let stats = async_reactor_stats();
+            assert!(stats.cq_overflows > 0);
+            assert_eq!(stats.last_error, Some(ReactorError::CompletionOverflow));
+
+            async_reactor_clear_last_error();
+            assert_eq!(async_reactor_stats().last_error, None);
+            1
+        });
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn local_run_budget_test() {
+        runtime_set_run_budget(Some(16));
+
+        let result = async_run(async {
+            let slept = Rc::new(Cell::new(false));
+            let slept_clone = slept.clone();
+
+            let sleeper = async_spawn(async move {
+                async_sleep(Duration::new(0, 1_000_000)).await;
+                slept_clone.set(true);
+            });
+
+            // without budget this task alone would keep executor busy forever
+            let mut yields = 0;
+            while !slept.get() {
+                async_yield().await;
+                yields += 1;
+            }
+
+            sleeper.await;
+            assert!(yields > 0);
+            1
+        });
+
+        runtime_set_run_budget(None);
+
+        // ensure it actually executed
+        assert_eq!(result, 1);
+    }
 }
@@ -4,6 +4,8 @@ use super::IOUringReq;
 use super::IOUringOp;
 use super::IoUringCQE;
 use super::AsyncValue;
+use super::async_sleep_with_result;
+use super::CancellationScope;
 
 use std::mem::ManuallyDrop;
 use std::task::{Context, Poll};
@@ -11,12 +13,15 @@ use std::pin::Pin;
 use std::future::Future;
 use std::cell::Cell;
 use std::rc::Rc;
+use std::time::Duration;
 
 use super::REACTOR;
 
 pub struct AsyncLinkedOps {
-    ops: Vec<(IOUringReq, Rc<Cell<Option<IoUringCQE>>>)>,
+    ops: Vec<(IOUringReq, Rc<Cell<Option<IoUringCQE>>>, Option<CancellationScope>)>,
     auto_cancel: bool,
+    deadline: Option<Duration>,
+    deadline_timer: Option<(u64, usize)>,
 }
 
 pub struct DelayedResult<T> {
@@ -43,13 +48,37 @@ impl<T> DelayedResult<T> {
 
 impl AsyncLinkedOps {
     pub fn new() -> Self {
-        AsyncLinkedOps { ops: vec![], auto_cancel: false }
+        AsyncLinkedOps { ops: vec![], auto_cancel: false, deadline: None, deadline_timer: None }
+    }
+
+    // Bounds the whole chain - once deadline expires, every link that hasn't completed yet is
+    // cancelled. Link timeout only covers the op right before it, so separate timer is used and
+    // per-op timeouts keep working independently.
+    pub fn deadline(&mut self, deadline: Duration) {
+        self.deadline = Some(deadline);
+    }
+
+    fn cancel_tags(&self) -> Vec<(u64, usize)> {
+        self.ops.iter().filter_map(|e| {
+            match (&e.0.op, e.1.get()) {
+                (IOUringOp::InProgress(cancel), None) => Some(*cancel),
+                (_, _) => None,
+            }
+        }).collect()
+    }
+
+    fn cancel_deadline_timer(&mut self) {
+        if let Some(token) = self.deadline_timer.take() {
+            REACTOR.with(|r| {
+                r.borrow_mut().cancel_op(&[token]);
+            });
+        }
     }
 
     pub fn add<T: AsyncOpResult>(&mut self, op: AsyncOp<T>) -> DelayedResult<T::Output> {
         // AsyncOp has a custom Drop trait, so unsafe is needed for destructurization
         let op = ManuallyDrop::new(op);
-        let (mut op_req, result_ptr) = unsafe { (std::ptr::read(&op.0), std::ptr::read(&op.1)) };
+        let (mut op_req, result_ptr, scope) = unsafe { (std::ptr::read(&op.0), std::ptr::read(&op.1), std::ptr::read(&op.4)) };
 
         let result = DelayedResult::new(result_ptr.clone());
         let result_generic = Rc::new(Cell::new(None));
@@ -60,7 +89,7 @@ impl AsyncLinkedOps {
             result_ptr.set(AsyncValue::Stored(T::get_result(cqe, params)));
         }));
 
-        self.ops.push((op_req, result_generic));
+        self.ops.push((op_req, result_generic, scope));
         result
     }
 }
@@ -76,7 +105,11 @@ impl Future for AsyncLinkedOps {
         };
 
         match (&last_op.0.op, last_op.1.get()) {
-            (IOUringOp::InProgress(_), Some(cqe))   => { return Poll::Ready(cqe.result >= 0) },
+            (IOUringOp::InProgress(_), Some(cqe))   => {
+                let succeeded = cqe.result >= 0;
+                self.cancel_deadline_timer();
+                return Poll::Ready(succeeded)
+            },
             (IOUringOp::InProgress(_), None)                    => { return Poll::Pending },
             (_, _) => (),   /* handled below */
         }
@@ -100,6 +133,25 @@ impl Future for AsyncLinkedOps {
             r.borrow_mut().schedule_linked2(&mut ops);
         });
 
+        // scoped links get their tokens only now, cancelling one of them fails the rest of the chain
+        for (req, _, scope) in &self.ops {
+            if let (IOUringOp::InProgress(token), Some(scope)) = (&req.op, scope) {
+                scope.add(*token);
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            // cancelling links that already completed is a no-op, so stale tags are fine
+            let cancel_tags = self.cancel_tags();
+            self.deadline_timer = Some(async_sleep_with_result(deadline).schedule(move |result| {
+                if result.is_ok() {
+                    REACTOR.with(|r| {
+                        r.borrow_mut().cancel_op(&cancel_tags);
+                    });
+                }
+            }));
+        }
+
         Poll::Pending
     }
 }
@@ -110,12 +162,12 @@ impl Drop for AsyncLinkedOps {
             return;
         }
 
-        let cancel_tags = self.ops.iter().filter_map(|e| {
-            match (&e.0.op, e.1.get()) {
-                (IOUringOp::InProgress(cancel), None) => Some(*cancel),
-                (_, _) => None,
-            }
-        }).collect::<Vec<_>>();
+        self.cancel_deadline_timer();
+
+        // Every link which hasn't completed yet is cancelled, in chain order. The first of them is the one
+        // actually running, cancelling it fails the rest of the chain; cancels for links which weren't
+        // started yet are no-ops, but they keep already running ones covered if chain broke earlier.
+        let cancel_tags = self.cancel_tags();
 
         REACTOR.with(|r| {
             r.borrow_mut().cancel_op(&cancel_tags);
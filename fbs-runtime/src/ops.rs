@@ -17,9 +17,49 @@ use super::Buffer;
 use super::MaybeFd;
 
 use fbs_library::system_error::SystemError;
-use fbs_library::socket::Socket;
+use fbs_library::socket::{Socket, SocketShutdown};
 use fbs_library::socket_address::SocketIpAddress;
 use fbs_library::poll::PollMask;
+use fbs_library::process::ChildExitStatus;
+
+// Index into table registered with async_register_files. Ops on it skip per-op fd lookup in the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisteredFd(u32);
+
+impl RegisteredFd {
+    pub(super) fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+// Descriptor accepted by read/write ops - either regular fd or registered one
+pub trait AsyncFd {
+    fn op_fd(&self) -> i32;
+
+    fn is_registered(&self) -> bool {
+        false
+    }
+}
+
+impl<T: AsRawFd> AsyncFd for T {
+    fn op_fd(&self) -> i32 {
+        self.as_raw_fd()
+    }
+}
+
+impl AsyncFd for RegisteredFd {
+    fn op_fd(&self) -> i32 {
+        self.0 as i32
+    }
+
+    fn is_registered(&self) -> bool {
+        true
+    }
+}
 
 trait AsyncResultEx {
     fn cancelled(&self) -> bool;
@@ -46,6 +86,15 @@ impl<T> AsyncResultEx for Result<T, (SystemError, Vec<u8>)> {
     }
 }
 
+// Op cancelled by its linked timeout is reported as ETIMEDOUT, so it can be told apart from user cancel
+fn op_error(cqe: &IoUringCQE, params: &ReactorOpParameters) -> SystemError {
+    if params.timed_out {
+        SystemError::new(libc::ETIMEDOUT)
+    } else {
+        SystemError::new(-cqe.result)
+    }
+}
+
 pub struct ResultSuccess;
 
 impl AsyncOpResult for ResultSuccess {
@@ -80,17 +129,31 @@ pub struct ResultErrno;
 impl AsyncOpResult for ResultErrno {
     type Output = Result<i32, SystemError>;
 
-    fn get_result(cqe: IoUringCQE, _params: ReactorOpParameters) -> Self::Output {
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
         let result = if cqe.result >= 0 {
             Ok(cqe.result)
         } else {
-            Err(SystemError::new(-cqe.result))
+            Err(op_error(&cqe, &params))
         };
 
         result
     }
 }
 
+pub struct ResultPollMask;
+
+impl AsyncOpResult for ResultPollMask {
+    type Output = Result<PollMask, SystemError>;
+
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        if cqe.result >= 0 {
+            Ok(PollMask::from(cqe.result))
+        } else {
+            Err(op_error(&cqe, &params))
+        }
+    }
+}
+
 pub struct ResultErrnoTimeout;
 
 impl AsyncOpResult for ResultErrnoTimeout {
@@ -110,11 +173,11 @@ pub struct ResultDescriptor;
 impl AsyncOpResult for ResultDescriptor {
     type Output = Result<OwnedFd, SystemError>;
 
-    fn get_result(cqe: IoUringCQE, _params: ReactorOpParameters) -> Self::Output {
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
         let result = if cqe.result >= 0 {
             Ok(unsafe { OwnedFd::from_raw_fd(cqe.result) } )
         } else {
-            Err(SystemError::new(-cqe.result))
+            Err(op_error(&cqe, &params))
         };
 
         result
@@ -126,23 +189,59 @@ pub struct ResultSocket;
 impl AsyncOpResult for ResultSocket {
     type Output = Result<Socket, SystemError>;
 
-    fn get_result(cqe: IoUringCQE, _params: ReactorOpParameters) -> Self::Output {
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
         let result = if cqe.result >= 0 {
             Ok(unsafe { Socket::from_raw_fd(cqe.result) } )
         } else {
-            Err(SystemError::new(-cqe.result))
+            Err(op_error(&cqe, &params))
         };
 
         result
     }
 }
 
+pub struct ResultSocketFrom;
+
+impl AsyncOpResult for ResultSocketFrom {
+    type Output = Result<(Socket, SocketIpAddress), SystemError>;
+
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        if cqe.result < 0 {
+            return Err(op_error(&cqe, &params));
+        }
+
+        let socket = unsafe { Socket::from_raw_fd(cqe.result) };
+        match params.address().to_socket_address() {
+            Some(address) => Ok((socket, address)),
+            None => Err(SystemError::new(libc::EAFNOSUPPORT)),
+        }
+    }
+}
+
+pub struct ResultChildStatus;
+
+impl AsyncOpResult for ResultChildStatus {
+    type Output = Result<ChildExitStatus, SystemError>;
+
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        if cqe.result < 0 {
+            return Err(op_error(&cqe, &params));
+        }
+
+        match params.siginfo() {
+            Some(info) => Ok(ChildExitStatus::from_siginfo(info)),
+            None => Err(SystemError::new(libc::EINVAL)),
+        }
+    }
+}
+
 pub struct ResultBuffer;
 
 impl AsyncOpResult for ResultBuffer {
     type Output = Result<Vec<u8>, (SystemError, Vec<u8>)>;
 
     fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        let error = op_error(&cqe, &params);
         let buffer = params.buffer;
 
         let result = if cqe.result >= 0 {
@@ -150,13 +249,55 @@ impl AsyncOpResult for ResultBuffer {
             Ok(buffer)
         } else {
             let buffer = unsafe { buffer.to_vec(0) };
-            Err((SystemError::new(-cqe.result), buffer))
+            Err((error, buffer))
         };
 
         result
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadOutcome {
+    Data(Vec<u8>),
+    Eof(Vec<u8>),   // peer closed or end of file, empty buffer is handed back for reuse
+}
+
+pub struct ResultReadOutcome;
+
+impl AsyncOpResult for ResultReadOutcome {
+    type Output = Result<ReadOutcome, (SystemError, Vec<u8>)>;
+
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        match ResultBuffer::get_result(cqe, params) {
+            Ok(buffer) if buffer.is_empty() => Ok(ReadOutcome::Eof(buffer)),
+            Ok(buffer) => Ok(ReadOutcome::Data(buffer)),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+pub struct ResultDatagram;
+
+impl AsyncOpResult for ResultDatagram {
+    type Output = Result<(Vec<u8>, SocketIpAddress), (SystemError, Vec<u8>)>;
+
+    fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        let error = op_error(&cqe, &params);
+        let address = params.address().to_socket_address();
+        let buffer = params.buffer;
+
+        if cqe.result < 0 {
+            return Err((error, unsafe { buffer.to_vec(0) }));
+        }
+
+        let buffer = unsafe { buffer.to_vec(cqe.result as usize) };
+        match address {
+            Some(address) => Ok((buffer, address)),
+            None => Err((SystemError::new(libc::EAFNOSUPPORT), buffer)),
+        }
+    }
+}
+
 pub struct ResultStruct<T: Copy + Unpin> {
     data: PhantomData<T>,
 }
@@ -165,6 +306,7 @@ impl<T: Copy + Unpin + 'static> AsyncOpResult for ResultStruct<T> {
     type Output = Result<T, SystemError>;
 
     fn get_result(cqe: IoUringCQE, params: ReactorOpParameters) -> Self::Output {
+        let error = op_error(&cqe, &params);
         let buffer = params.buffer;
 
         let result = if cqe.result == std::mem::size_of::<T>() as i32 {
@@ -172,7 +314,7 @@ impl<T: Copy + Unpin + 'static> AsyncOpResult for ResultStruct<T> {
         } else if cqe.result > 0 {
             Err(SystemError::new(libc::ENOENT))
         } else {
-            Err(SystemError::new(-cqe.result))
+            Err(error)
         };
 
         result
@@ -185,14 +327,30 @@ pub type AsyncCloseWithResult = AsyncOp::<ResultErrno>;
 pub type AsyncOpen = AsyncOp::<ResultDescriptor>;
 pub type AsyncSocket = AsyncOp::<ResultErrno>;
 pub type AsyncReadBytes = AsyncOp::<ResultBuffer>;
+pub type AsyncReadSome = AsyncOp::<ResultReadOutcome>;
 pub type AsyncReadStruct<T> = AsyncOp::<ResultStruct<T>>;
 pub type AsyncWrite = AsyncOp::<ResultBuffer>;
 pub type AsyncAccept = AsyncOp::<ResultSocket>;
+pub type AsyncAcceptFrom = AsyncOp::<ResultSocketFrom>;
 pub type AsyncConnect = AsyncOp::<ResultErrno>;
 pub type AsyncTimeout = AsyncOp::<ResultSuccessSleep>;
 pub type AsyncTimeoutWithResult = AsyncOp::<ResultErrnoTimeout>;
 pub type AsyncCancel = AsyncOp::<ResultErrno>;
-pub type AsyncPoll = AsyncOp::<ResultErrno>;
+pub type AsyncPoll = AsyncOp::<ResultPollMask>;
+pub type AsyncPollUpdate = AsyncOp::<ResultErrno>;
+pub type AsyncFsync = AsyncOp::<ResultErrno>;
+pub type AsyncRename = AsyncOp::<ResultErrno>;
+pub type AsyncReadFixed = AsyncOp::<ResultErrno>;
+pub type AsyncWriteFixed = AsyncOp::<ResultErrno>;
+pub type AsyncSplice = AsyncOp::<ResultErrno>;
+pub type AsyncShutdown = AsyncOp::<ResultErrno>;
+pub type AsyncFallocate = AsyncOp::<ResultErrno>;
+pub type AsyncFadvise = AsyncOp::<ResultErrno>;
+pub type AsyncMadvise = AsyncOp::<ResultErrno>;
+pub type AsyncWaitId = AsyncOp::<ResultChildStatus>;
+pub type AsyncRecvFrom = AsyncOp::<ResultDatagram>;
+pub type AsyncSendTo = AsyncOp::<ResultBuffer>;
+pub type AsyncTee = AsyncOp::<ResultErrno>;
 
 pub fn async_nop() -> AsyncNop {
     AsyncOp::new(IOUringOp::Nop())
@@ -208,23 +366,69 @@ pub fn async_close_with_result<T: IntoRawFd>(fd: T) -> AsyncCloseWithResult {
 
 pub fn async_open<P: AsRef<Path>>(path: P, options: &OpenMode) -> AsyncOpen {
     let path = CString::new(path.as_ref().as_os_str().as_bytes()).expect("Null character in filename");
-    AsyncOp::new(IOUringOp::Open(path, options.flags(), options.mode()))
+    AsyncOp::new(IOUringOp::Open(libc::AT_FDCWD, path, options.flags(), options.mode()))
+}
+
+// Relative path is resolved against dirfd instead of current working directory
+pub fn async_openat<T: AsRawFd, P: AsRef<Path>>(dirfd: &T, path: P, options: &OpenMode) -> AsyncOpen {
+    let path = CString::new(path.as_ref().as_os_str().as_bytes()).expect("Null character in filename");
+    AsyncOp::new(IOUringOp::Open(dirfd.as_raw_fd(), path, options.flags(), options.mode()))
 }
 
 pub fn async_socket(domain: SocketDomain, socket_type: SocketType, options: i32) -> AsyncSocket {
     AsyncOp::new(IOUringOp::Socket(domain as i32, socket_type as i32 | options, 0))
 }
 
-pub fn async_read_into<T: AsRawFd>(fd: &T, buffer: Vec<u8>, offset: Option<u64>) -> AsyncReadBytes {
-    AsyncOp::new(IOUringOp::Read(fd.as_raw_fd(), Buffer::from_vec(buffer), offset))
+pub fn async_read_into<T: AsyncFd>(fd: &T, buffer: Vec<u8>, offset: Option<u64>) -> AsyncReadBytes {
+    AsyncOp::new(IOUringOp::Read(fd.op_fd(), Buffer::from_vec(buffer), offset)).fixed_file(fd.is_registered())
+}
+
+// Previous contents are discarded and read always uses full capacity. Returned vec is sized to
+// bytes read but keeps the allocation, so read loop can pass it back without reallocating.
+pub fn async_read_reuse<T: AsyncFd>(fd: &T, mut buffer: Vec<u8>, offset: Option<u64>) -> AsyncReadBytes {
+    assert!(buffer.capacity() > 0, "async_read_reuse requires buffer with non-zero capacity");
+    buffer.clear();
+    async_read_into(fd, buffer, offset)
+}
+
+// Like async_read_into, but end of stream is reported as ReadOutcome::Eof. Buffer must have spare
+// capacity, so empty read can't be caused by asking for zero bytes.
+pub fn async_read_some<T: AsyncFd>(fd: &T, buffer: Vec<u8>, offset: Option<u64>) -> AsyncReadSome {
+    assert!(buffer.capacity() > 0, "async_read_some requires buffer with non-zero capacity");
+    AsyncOp::new(IOUringOp::Read(fd.op_fd(), Buffer::from_vec(buffer), offset)).fixed_file(fd.is_registered())
 }
 
+// Single read, so struct has to arrive in one piece (files, signalfd, eventfd). Short read is
+// reported as ENOENT, for streams use async_read_struct_exact.
 pub fn async_read_struct<U: Copy + Unpin + 'static>(fd: &impl AsRawFd, offset: Option<u64>) -> AsyncReadStruct<U> {
     AsyncOp::new(IOUringOp::Read(fd.as_raw_fd(), Buffer::new_struct::<U>(), offset))
 }
 
-pub fn async_write<T: AsRawFd>(fd: &T, buffer: Vec<u8>, offset: Option<u64>) -> AsyncWrite {
-    AsyncOp::new(IOUringOp::Write(fd.as_raw_fd(), Buffer::from_vec(buffer), offset))
+pub fn async_write<T: AsyncFd>(fd: &T, buffer: Vec<u8>, offset: Option<u64>) -> AsyncWrite {
+    AsyncOp::new(IOUringOp::Write(fd.op_fd(), Buffer::from_vec(buffer), offset)).fixed_file(fd.is_registered())
+}
+
+// Keeps reading until whole struct is received, end of stream in the middle gives ENODATA
+pub async fn async_read_struct_exact<U: Copy + Unpin + 'static>(fd: &impl AsRawFd, mut offset: Option<u64>) -> Result<U, SystemError> {
+    let size = std::mem::size_of::<U>();
+    let mut data: Vec<u8> = Vec::with_capacity(size);
+
+    while data.len() < size {
+        let buffer = Vec::with_capacity(size - data.len());
+        let buffer = match async_read_into(fd, buffer, offset).await {
+            Ok(buffer) => buffer,
+            Err((error, _)) => return Err(error),
+        };
+
+        if buffer.is_empty() {
+            return Err(SystemError::new(libc::ENODATA));
+        }
+
+        offset = offset.map(|offset| offset + buffer.len() as u64);
+        data.extend_from_slice(&buffer);
+    }
+
+    Ok(unsafe { std::ptr::read_unaligned(data.as_ptr() as *const U) })
 }
 
 pub fn async_write_struct<U: Copy + Unpin + 'static>(fd: &impl AsRawFd, value: U, offset: Option<u64>) -> AsyncWrite {
@@ -235,10 +439,19 @@ pub fn async_accept<T: AsRawFd>(fd: &T, flags: i32) -> AsyncAccept {
     AsyncOp::new(IOUringOp::Accept(fd.as_raw_fd(), flags))
 }
 
+pub fn async_accept_from<T: AsRawFd>(fd: &T, flags: i32) -> AsyncAcceptFrom {
+    AsyncOp::new(IOUringOp::AcceptFrom(fd.as_raw_fd(), flags))
+}
+
 pub fn async_connect<T: AsRawFd>(fd: &T, address: SocketIpAddress) -> AsyncConnect {
     AsyncOp::new(IOUringOp::Connect(fd.as_raw_fd(), address))
 }
 
+// Connect is cancelled once timeout expires, error then reports timed_out()
+pub fn async_connect_timeout<T: AsRawFd>(fd: &T, address: SocketIpAddress, timeout: Duration) -> AsyncConnect {
+    async_connect(fd, address).timeout(timeout)
+}
+
 pub fn async_sleep(timeout: Duration) -> AsyncTimeout {
     AsyncOp::new(IOUringOp::Sleep(timeout))
 }
@@ -255,10 +468,74 @@ pub fn async_sleep_update(token: (u64, usize), timeout: Duration) -> AsyncTimeou
     AsyncOp::new(IOUringOp::SleepUpdate(token, timeout))
 }
 
+// Returned mask also includes error and hangup conditions, which are reported even if not requested
 pub fn async_poll<T: AsRawFd>(fd: &T, mask: PollMask) -> AsyncPoll {
     AsyncOp::new(IOUringOp::Poll(fd.as_raw_fd(), mask))
 }
 
-pub fn async_poll_update(token: (u64, usize), mask: PollMask) -> AsyncPoll {
+pub fn async_poll_update(token: (u64, usize), mask: PollMask) -> AsyncPollUpdate {
     AsyncOp::new(IOUringOp::PollUpdate(token, mask))
 }
+
+pub fn async_fsync<T: AsRawFd>(fd: &T, data_only: bool) -> AsyncFsync {
+    AsyncOp::new(IOUringOp::Fsync(fd.as_raw_fd(), data_only))
+}
+
+pub fn async_rename<P: AsRef<Path>, Q: AsRef<Path>>(path: P, target_path: Q) -> AsyncRename {
+    let path = CString::new(path.as_ref().as_os_str().as_bytes()).expect("Null character in filename");
+    let target_path = CString::new(target_path.as_ref().as_os_str().as_bytes()).expect("Null character in filename");
+    AsyncOp::new(IOUringOp::Rename(path, target_path))
+}
+
+// Data is read into buffer registered with async_register_buffers, result is number of bytes read
+pub fn async_read_fixed<T: AsRawFd>(fd: &T, buffer_index: u16, length: u32, offset: Option<u64>) -> AsyncReadFixed {
+    AsyncOp::new(IOUringOp::ReadFixed(fd.as_raw_fd(), buffer_index, length, offset))
+}
+
+pub fn async_write_fixed<T: AsRawFd>(fd: &T, buffer_index: u16, length: u32, offset: Option<u64>) -> AsyncWriteFixed {
+    AsyncOp::new(IOUringOp::WriteFixed(fd.as_raw_fd(), buffer_index, length, offset))
+}
+
+// One of the descriptors has to be a pipe, result is number of bytes moved
+pub fn async_splice<T: AsRawFd, U: AsRawFd>(fd_in: &T, offset_in: Option<i64>, fd_out: &U, offset_out: Option<i64>, length: u32, flags: u32) -> AsyncSplice {
+    AsyncOp::new(IOUringOp::Splice(fd_in.as_raw_fd(), offset_in, fd_out.as_raw_fd(), offset_out, length, flags))
+}
+
+pub fn async_shutdown<T: AsRawFd>(fd: &T, how: SocketShutdown) -> AsyncShutdown {
+    AsyncOp::new(IOUringOp::Shutdown(fd.as_raw_fd(), how as i32))
+}
+
+// Mode is the same as for fallocate(2), 0 allocates space and extends file size if needed
+pub fn async_fallocate<T: AsRawFd>(fd: &T, mode: i32, offset: u64, length: u64) -> AsyncFallocate {
+    AsyncOp::new(IOUringOp::Fallocate(fd.as_raw_fd(), mode, offset, length))
+}
+
+// Advice is one of libc::POSIX_FADV_*, length 0 means until the end of file
+pub fn async_fadvise<T: AsRawFd>(fd: &T, offset: u64, length: u64, advice: i32) -> AsyncFadvise {
+    AsyncOp::new(IOUringOp::Fadvise(fd.as_raw_fd(), offset, length, advice))
+}
+
+// Region must be page aligned and mapped by the caller, advice is one of libc::MADV_*
+pub fn async_madvise(address: *mut libc::c_void, length: u64, advice: i32) -> AsyncMadvise {
+    AsyncOp::new(IOUringOp::Madvise(address as usize, length, advice))
+}
+
+// Reaps child once it exits. Needs kernel 6.5+, check async_op_supported(IOUringOpType::WAITID)
+pub fn async_waitid(pid: libc::pid_t) -> AsyncWaitId {
+    AsyncOp::new(IOUringOp::WaitId(libc::P_PID, pid as u32, libc::WEXITED))
+}
+
+// Receives single datagram into buffer capacity, excess bytes of longer datagram are discarded
+pub fn async_recvfrom<T: AsRawFd>(fd: &T, buffer: Vec<u8>) -> AsyncRecvFrom {
+    AsyncOp::new(IOUringOp::RecvFrom(fd.as_raw_fd(), Buffer::from_vec(buffer), 0))
+}
+
+pub fn async_sendto<T: AsRawFd>(fd: &T, buffer: Vec<u8>, destination: SocketIpAddress) -> AsyncSendTo {
+    AsyncOp::new(IOUringOp::SendTo(fd.as_raw_fd(), Buffer::from_vec(buffer), destination, 0))
+}
+
+// Both descriptors have to be pipes, data is duplicated without being consumed from fd_in.
+// Result is number of bytes duplicated.
+pub fn async_tee<T: AsRawFd, U: AsRawFd>(fd_in: &T, fd_out: &U, length: u32, flags: u32) -> AsyncTee {
+    AsyncOp::new(IOUringOp::Tee(fd_in.as_raw_fd(), fd_out.as_raw_fd(), length, flags))
+}
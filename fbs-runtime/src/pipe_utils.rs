@@ -0,0 +1,25 @@
+use std::os::fd::OwnedFd;
+
+use fbs_library::pipe::{pipe, PipeFlags};
+use fbs_library::system_error::SystemError;
+
+use super::{async_read_into, async_write, AsyncFd};
+
+const PIPE_DRAIN_SIZE: usize = 64;
+
+// Self-pipe, lighter alternative to eventfd for waking a task from other task or completion
+// handler. Returns (rx, tx), both close on exec.
+pub fn async_pipe() -> Result<(OwnedFd, OwnedFd), SystemError> {
+    pipe(PipeFlags::default().close_on_exec(true))
+}
+
+// Single byte per notification, so concurrent writers never interleave
+pub async fn async_pipe_write<T: AsyncFd>(tx: &T) -> Result<(), SystemError> {
+    async_write(tx, vec![1], None).await.map(|_| ()).map_err(|(error, _)| error)
+}
+
+// Waits for notification and drains what is pending, up to PIPE_DRAIN_SIZE at once. Returns number
+// of notifications consumed, 0 means all write ends have been closed.
+pub async fn async_pipe_read<T: AsyncFd>(rx: &T) -> Result<usize, SystemError> {
+    async_read_into(rx, Vec::with_capacity(PIPE_DRAIN_SIZE), None).await.map(|data| data.len()).map_err(|(error, _)| error)
+}
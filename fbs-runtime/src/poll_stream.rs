@@ -0,0 +1,133 @@
+use std::cell::Cell;
+use std::os::fd::AsRawFd;
+use std::rc::Rc;
+use std::slice;
+
+use fbs_library::poll::PollMask;
+use fbs_library::system_error::SystemError;
+
+use super::async_utils::{async_channel_create, AsyncChannelRx};
+use super::{IOUringOp, IOUringReq, REACTOR};
+
+// Poll which stays armed after reporting readiness, so long-lived watches don't need to be
+// re-issued after every event. Stream ends after kernel drops the poll or it gets cancelled.
+pub struct AsyncPollStream {
+    token: (u64, usize),
+    events: AsyncChannelRx<Result<PollMask, SystemError>>,
+    finished: Rc<Cell<bool>>,
+}
+
+impl AsyncPollStream {
+    pub fn token(&self) -> (u64, usize) {
+        self.token
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.get()
+    }
+
+    // Returns None once the final event has been consumed
+    pub async fn next(&mut self) -> Option<Result<PollMask, SystemError>> {
+        if self.events.is_empty() && self.finished.get() {
+            return None;
+        }
+
+        Some(self.events.receive().await)
+    }
+
+    pub fn cancel(&self) {
+        if self.finished.get() {
+            return;
+        }
+
+        REACTOR.with(|r| {
+            r.borrow_mut().cancel_op(slice::from_ref(&self.token));
+        });
+    }
+}
+
+impl Drop for AsyncPollStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn poll_result(result: i32) -> Result<PollMask, SystemError> {
+    if result >= 0 {
+        Ok(PollMask::from(result))
+    } else {
+        Err(SystemError::new(-result))
+    }
+}
+
+// Poll is armed immediately, events which happen before first next() call are queued
+pub fn async_poll_multishot<T: AsRawFd>(fd: &T, mask: PollMask) -> AsyncPollStream {
+    let (events, tx) = async_channel_create();
+    let finished = Rc::new(Cell::new(false));
+
+    let final_tx = events.tx();
+    let final_finished = finished.clone();
+
+    let mut req = IOUringReq {
+        op: IOUringOp::PollMultishot(fd.as_raw_fd(), mask),
+        completion: Some(Box::new(move |cqe, _params| {
+            // final event is sent as well, so task waiting in next() is always woken up
+            final_finished.set(true);
+            final_tx.send(poll_result(cqe.result));
+        })),
+        multishot: Some(Box::new(move |cqe| {
+            tx.send(poll_result(cqe.result));
+        })),
+        timeout: None,
+        fixed_file: false,
+        drain: false,
+    };
+
+    REACTOR.with(|r| {
+        r.borrow_mut().schedule_linked2(slice::from_mut(&mut &mut req));
+    });
+
+    let token = match req.op {
+        IOUringOp::InProgress(token) => token,
+        _ => panic!("io_uring schedling failed"),
+    };
+
+    AsyncPollStream { token, events, finished }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::fd::{OwnedFd, FromRawFd};
+
+    use crate::{async_run, async_write};
+    use super::*;
+
+    #[test]
+    fn poll_multishot_test() {
+        let result = async_run(async {
+            let mut fds = [0; 2];
+            let error = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+            assert_eq!(error, 0);
+
+            let (read_end, write_end) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+            let mut stream = async_poll_multishot(&read_end, PollMask::default().read(true));
+
+            // every write is reported without re-arming poll
+            for _ in 0..3 {
+                assert!(async_write(&write_end, vec![1], None).await.is_ok());
+
+                let event = stream.next().await.unwrap();
+                assert!(event.is_ok_and(|mask| mask.is_readable()));
+            }
+
+            stream.cancel();
+            assert!(stream.next().await.unwrap().is_err_and(|e| e.cancelled()));
+            assert!(stream.next().await.is_none());
+            assert!(stream.is_finished());
+
+            1
+        });
+
+        assert_eq!(result, 1);
+    }
+}
@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::os::fd::AsRawFd;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use fbs_library::eventfd::{EventFd, EventFdFlags};
+
+use super::async_utils::AsyncSignal;
+use super::{async_spawn, async_read_struct};
+
+thread_local! {
+    static REMOTE_WAKER: RefCell<Weak<RemoteWakerShared>> = RefCell::new(Weak::new());
+    static REMOTE_WAKE_SIGNAL: AsyncSignal = AsyncSignal::new();
+}
+
+// Handle that can be sent to other threads. Each wake() makes the owning thread's reactor
+// return from waiting and fires signal awaited by async_remote_woken. Wakes may be coalesced.
+#[derive(Debug)]
+pub struct RemoteWaker {
+    shared: Arc<RemoteWakerShared>,
+}
+
+// Handles are counted explicitly, Arc count can't tell last handle apart when two are dropped at once
+#[derive(Debug)]
+struct RemoteWakerShared {
+    eventfd: EventFd,
+    handles: AtomicUsize,
+}
+
+impl RemoteWaker {
+    fn new(shared: Arc<RemoteWakerShared>) -> Self {
+        shared.handles.fetch_add(1, Ordering::SeqCst);
+        Self { shared }
+    }
+
+    pub fn wake(&self) {
+        self.shared.eventfd.write(1);
+    }
+}
+
+impl Clone for RemoteWaker {
+    fn clone(&self) -> Self {
+        Self::new(self.shared.clone())
+    }
+}
+
+impl Drop for RemoteWaker {
+    fn drop(&mut self) {
+        // last handle is going away, listener has to be woken to exit
+        if self.shared.handles.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.eventfd.write(1);
+        }
+    }
+}
+
+// Listener read is armed only while some RemoteWaker exists, so it doesn't keep async_run alive forever
+pub fn remote_waker() -> RemoteWaker {
+    let existing = REMOTE_WAKER.with(|w| w.borrow().upgrade());
+    if let Some(shared) = existing {
+        return RemoteWaker::new(shared);
+    }
+
+    let eventfd = EventFd::new(0, EventFdFlags::new().close_on_exec(true)).expect("Error while creating remote waker eventfd");
+    let shared = Arc::new(RemoteWakerShared { eventfd, handles: AtomicUsize::new(0) });
+    REMOTE_WAKER.with(|w| *w.borrow_mut() = Arc::downgrade(&shared));
+
+    let waker = RemoteWaker::new(shared.clone());
+    let listener = shared;
+    async_spawn(async move {
+        loop {
+            let result = async_read_struct::<u64>(&listener.eventfd.as_raw_fd(), None).await;
+            if result.is_err() || listener.handles.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+
+            REMOTE_WAKE_SIGNAL.with(|s| s.signal());
+        }
+
+        // dropped handle on other thread may still keep shared state alive, next remote_waker() starts anew
+        REMOTE_WAKER.with(|w| *w.borrow_mut() = Weak::new());
+    }).detach();
+
+    waker
+}
+
+pub async fn async_remote_woken() {
+    let signal = REMOTE_WAKE_SIGNAL.with(|s| s.clone());
+    signal.await;
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::async_run;
+    use super::*;
+
+    #[test]
+    fn remote_waker_test() {
+        let result = async_run(async {
+            let waker = remote_waker();
+            let started = Instant::now();
+
+            let thread = std::thread::spawn(move || {
+                std::thread::sleep(Duration::new(0, 50_000_000));
+                waker.wake();
+            });
+
+            async_remote_woken().await;
+            assert!(started.elapsed() >= Duration::new(0, 50_000_000));
+
+            thread.join().unwrap();
+            1
+        });
+
+        // async_run returned, so listener exited once last waker was dropped
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn remote_waker_concurrent_drop_test() {
+        let result = async_run(async {
+            let waker = remote_waker();
+            let threads: Vec<_> = (0..8).map(|_| {
+                let waker = waker.clone();
+                std::thread::spawn(move || drop(waker))
+            }).collect();
+
+            drop(waker);
+            for thread in threads {
+                thread.join().unwrap();
+            }
+
+            1
+        });
+
+        // handles dropped at the same time still wake listener, otherwise async_run would hang
+        assert_eq!(result, 1);
+    }
+}
@@ -0,0 +1,11 @@
+use fbs_library::signalfd::{SignalFd, SignalFdInfo, SIGNALFD_READ_BATCH_SIZE};
+use fbs_library::system_error::SystemError;
+
+use super::async_read_into;
+
+// Waits for signals and returns all that are pending, up to SIGNALFD_READ_BATCH_SIZE at once
+pub async fn async_read_signals(sigfd: &SignalFd) -> Result<Vec<SignalFdInfo>, SystemError> {
+    async_read_into(sigfd, Vec::with_capacity(SIGNALFD_READ_BATCH_SIZE), None).await
+        .map(|data| SignalFdInfo::from_bytes(&data))
+        .map_err(|(error, _)| error)
+}
@@ -1,19 +1,20 @@
 use fbs_library::signalfd::SignalFd;
 use fbs_library::signalfd::SignalFdFlags;
-use fbs_library::signalfd::SignalFdInfo;
 use fbs_library::sigset::{SignalSet, SignalMask, set_process_signal_mask};
 use fbs_runtime::*;
 use fbs_library::socket_address::*;
 use fbs_library::socket::*;
 use fbs_library::sigset::Signal;
+use std::time::Duration;
 
 async fn try_connect()
 {
     let sock = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().close_on_exec(true).flags());
     let destination = SocketIpAddress::from_text("99.83.207.202:80", None).unwrap();
 
-    let connect_result = async_connect(&sock, destination).await;
+    let connect_result = async_connect_timeout(&sock, destination, Duration::new(5, 0)).await;
     match connect_result {
+        Err(error) if error.timed_out() => println!("Timed out while connecting"),
         Err(_) => println!("Error while connecting"),
         Ok(_) => println!("connected"),
     }
@@ -48,6 +49,31 @@ async fn handle_client(fd: Socket)
     async_close(fd).await
 }
 
+async fn udp_echo_server()
+{
+    let server_address = SocketIpAddress::from_text("0.0.0.0:2405", None).unwrap();
+    let socket = Socket::new(SocketDomain::Inet, SocketType::Datagram, SocketFlags::new().close_on_exec(true).flags());
+    socket.bind(&server_address).unwrap();
+
+    loop {
+        let received = async_recvfrom(&socket, Vec::with_capacity(1500)).await;
+        match received {
+            Ok((buffer, peer)) => {
+                println!("Datagram from {}: {:?}", peer.to_text(), &buffer);
+                if let Err((errno, _)) = async_sendto(&socket, buffer, peer).await {
+                    println!("Error while sending: {}", errno);
+                }
+            },
+            Err((errno, _)) => {
+                println!("Error while receiving: {}", errno);
+                break;
+            },
+        }
+    }
+
+    async_close(socket).await;
+}
+
 fn main() {
     println!("Hello, world!");
 
@@ -64,20 +90,26 @@ fn main() {
             let sigfd = SignalFd::new(mask, SignalFdFlags::new().close_on_exec(true).flags()).unwrap();
 
             loop {
-                let received = async_read_struct::<SignalFdInfo>(&sigfd, None).await;
+                let received = async_read_signals(&sigfd).await;
                 match received {
                     Err(error) => panic!("Got error {}", error),
-                    Ok(info) => {
-                        println!("Got {:?}", info.signal());
+                    Ok(infos) => {
+                        for info in infos {
+                            println!("Got {:?}", info.signal());
+                        }
                     }
                 }
             }
         });
 
-        let server_address = SocketIpAddress::from_text("0.0.0.0:2404", None).unwrap();
-        let mut socket = Socket::new(SocketDomain::Inet, SocketType::Stream, SocketFlags::new().flags());
+        async_spawn(async { udp_echo_server().await });
+
+        // dual stack, serves both IPv4 and IPv6 clients
+        let server_address = SocketIpAddress::from_text("[::]:2404", None).unwrap();
+        let mut socket = Socket::new(SocketDomain::Inet6, SocketType::Stream, SocketFlags::new().flags());
 
         socket.set_option(SocketOptions::ReuseAddr(true)).unwrap();
+        socket.set_option(SocketOptions::V6Only(false)).unwrap();
         socket.listen(&server_address, 100).unwrap();
         loop {
             let client = async_accept(&socket, 0).await;